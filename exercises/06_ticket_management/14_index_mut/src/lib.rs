@@ -3,6 +3,11 @@
 use std::ops::Index;
 use ticket_fields::{TicketDescription, TicketTitle};
 
+// Run the shared `TicketRepository` conformance suite against your store
+// with `cargo test --features repository`, once you're done with the exercise.
+#[cfg(feature = "repository")]
+mod repository;
+
 #[derive(Clone)]
 pub struct TicketStore {
     tickets: Vec<Ticket>,
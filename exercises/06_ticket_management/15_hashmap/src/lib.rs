@@ -5,6 +5,11 @@ use std::collections::HashMap;
 use std::ops::{Index, IndexMut};
 use ticket_fields::{TicketDescription, TicketTitle};
 
+// Run the shared `TicketRepository` conformance suite against your store
+// with `cargo test --features repository`, once you're done with the exercise.
+#[cfg(feature = "repository")]
+mod repository;
+
 #[derive(Clone)]
 pub struct TicketStore {
     tickets: HashMap<TicketId, Ticket>,
@@ -0,0 +1,51 @@
+use crate::{Status, Ticket, TicketDraft, TicketId, TicketStore};
+use ticket_fields::{TicketDescription, TicketTitle};
+use ticket_repository::{TicketChanges, TicketNotFound, TicketRecord, TicketRepository};
+
+impl TicketRepository for TicketStore {
+    type Id = TicketId;
+    type Status = Status;
+
+    fn insert(&mut self, title: TicketTitle, description: TicketDescription) -> TicketId {
+        self.add_ticket(TicketDraft { title, description })
+    }
+
+    fn get(&self, id: TicketId) -> Option<TicketRecord<TicketId, Status>> {
+        TicketStore::get(self, id).map(record)
+    }
+
+    fn update(
+        &mut self,
+        id: TicketId,
+        changes: TicketChanges<Status>,
+    ) -> Result<(), TicketNotFound> {
+        let ticket = self.get_mut(id).ok_or(TicketNotFound)?;
+        if let Some(title) = changes.title {
+            ticket.title = title;
+        }
+        if let Some(description) = changes.description {
+            ticket.description = description;
+        }
+        if let Some(status) = changes.status {
+            ticket.status = status;
+        }
+        Ok(())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = TicketRecord<TicketId, Status>> + '_ {
+        self.tickets.values().map(record)
+    }
+
+    fn count(&self) -> usize {
+        self.tickets.len()
+    }
+}
+
+fn record(ticket: &Ticket) -> TicketRecord<TicketId, Status> {
+    TicketRecord {
+        id: ticket.id,
+        title: ticket.title.clone(),
+        description: ticket.description.clone(),
+        status: ticket.status,
+    }
+}
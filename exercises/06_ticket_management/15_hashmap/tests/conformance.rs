@@ -0,0 +1,9 @@
+#![cfg(feature = "repository")]
+
+use hashmap::{Status, TicketStore};
+
+ticket_repository::conformance_tests!(
+    TicketStore::new(),
+    initial = Status::ToDo,
+    updated = Status::InProgress,
+);
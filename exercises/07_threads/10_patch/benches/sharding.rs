@@ -0,0 +1,68 @@
+//! Compare the throughput of a single server thread with a sharded server.
+//!
+//! Run with `cargo bench -p patch --bench sharding`.
+use std::thread;
+use std::time::{Duration, Instant};
+
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::{launch, launch_sharded, TicketStoreClient};
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+const CLIENTS: usize = 8;
+const TICKETS_PER_CLIENT: usize = 2_000;
+const CAPACITY: usize = 128;
+
+/// Every client inserts its tickets, then reads and updates each of them.
+fn workload(client: &TicketStoreClient) -> Duration {
+    let start = Instant::now();
+    let handles: Vec<_> = (0..CLIENTS)
+        .map(|_| {
+            let client = client.clone();
+            thread::spawn(move || {
+                for _ in 0..TICKETS_PER_CLIENT {
+                    let id = client
+                        .insert(TicketDraft {
+                            title: ticket_title(),
+                            description: ticket_description(),
+                        })
+                        .unwrap();
+                    client.get(id).unwrap();
+                    client
+                        .update(TicketPatch {
+                            id,
+                            title: None,
+                            description: None,
+                            status: Some(Status::Done),
+                        })
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let commands = CLIENTS * TICKETS_PER_CLIENT * 3;
+    let throughput = commands as f64 / elapsed.as_secs_f64();
+    println!("{name:<12} {elapsed:>10.2?} {throughput:>12.0} commands/s");
+}
+
+fn main() {
+    let backpressure = patch::backpressure::Backpressure::Block;
+
+    let (client, server) = launch(CAPACITY);
+    let elapsed = workload(&client.with_backpressure(backpressure));
+    server.shutdown();
+    report("1 thread", elapsed);
+
+    for n_shards in [2, 4, 8] {
+        let (client, server) = launch_sharded(n_shards, CAPACITY);
+        let elapsed = workload(&client.with_backpressure(backpressure));
+        server.shutdown();
+        report(&format!("{n_shards} shards"), elapsed);
+    }
+}
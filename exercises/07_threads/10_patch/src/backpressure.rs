@@ -0,0 +1,183 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::mpsc::{SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancelHandle;
+
+/// What a client does when the server's queue is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backpressure {
+    /// Give up immediately.
+    #[default]
+    FailFast,
+    /// Wait for as long as it takes.
+    Block,
+    /// Wait, but give up once the deadline has passed.
+    BlockFor(Duration),
+    /// Retry up to `max_attempts` times in total, sleeping between attempts.
+    /// There is always at least one attempt, even with `max_attempts: 0`.
+    /// The sleep starts at `initial_backoff` and doubles after every attempt,
+    /// up to `max_backoff`, with random jitter so that clients don't retry in lockstep.
+    Retry {
+        max_attempts: u32,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    },
+}
+
+impl fmt::Display for Backpressure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backpressure::FailFast => write!(f, "fail fast"),
+            Backpressure::Block => write!(f, "block"),
+            Backpressure::BlockFor(timeout) => write!(f, "block for {timeout:?}"),
+            Backpressure::Retry { max_attempts, .. } => {
+                write!(f, "retry up to {max_attempts} times with backoff")
+            }
+        }
+    }
+}
+
+/// Why [`Backpressure::send`] failed.
+pub(crate) enum SendError {
+    /// The queue stayed full for longer than the policy allows.
+    Full,
+    Disconnected,
+    /// The call was cancelled while waiting for room in the queue.
+    Cancelled,
+}
+
+/// The longest a caller waiting for room in the queue goes without checking
+/// whether it was cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(5);
+
+impl Backpressure {
+    /// Send `message`, waiting for room in the queue as the policy allows.
+    /// Cancelling `cancel` stops the wait.
+    pub(crate) fn send<T>(
+        self,
+        sender: &SyncSender<T>,
+        message: T,
+        cancel: Option<&CancelHandle>,
+    ) -> Result<(), SendError> {
+        match self {
+            Backpressure::FailFast => sender.try_send(message).map_err(|e| match e {
+                TrySendError::Full(_) => SendError::Full,
+                TrySendError::Disconnected(_) => SendError::Disconnected,
+            }),
+            Backpressure::Block => send_until(sender, message, None, cancel),
+            // `None` if too far in the future to be told apart from blocking.
+            Backpressure::BlockFor(timeout) => {
+                send_until(sender, message, Instant::now().checked_add(timeout), cancel)
+            }
+            Backpressure::Retry {
+                max_attempts,
+                initial_backoff,
+                max_backoff,
+            } => {
+                let mut message = message;
+                let mut backoff = initial_backoff;
+                let max_attempts = max_attempts.max(1);
+                for attempt in 1..=max_attempts {
+                    match sender.try_send(message) {
+                        Ok(()) => return Ok(()),
+                        Err(TrySendError::Disconnected(_)) => return Err(SendError::Disconnected),
+                        Err(TrySendError::Full(m)) => message = m,
+                    }
+                    if attempt < max_attempts {
+                        // "Equal jitter": sleep somewhere between half and all of the backoff.
+                        let pause = (backoff / 2).saturating_add(jitter(backoff / 2));
+                        backoff = backoff.saturating_mul(2).min(max_backoff);
+                        sleep(pause, cancel)?;
+                    }
+                }
+                Err(SendError::Full)
+            }
+        }
+    }
+}
+
+/// Wait for room in the queue until `deadline`, or forever if it's `None`.
+///
+/// `SyncSender` has no `send_timeout`, and its `send` can't be interrupted:
+/// poll with a short, growing sleep instead, unless there is nothing to stop the wait.
+fn send_until<T>(
+    sender: &SyncSender<T>,
+    mut message: T,
+    deadline: Option<Instant>,
+    cancel: Option<&CancelHandle>,
+) -> Result<(), SendError> {
+    if deadline.is_none() && cancel.is_none() {
+        return sender.send(message).map_err(|_| SendError::Disconnected);
+    }
+    let mut pause = Duration::from_micros(50);
+    loop {
+        if cancel.is_some_and(CancelHandle::is_cancelled) {
+            return Err(SendError::Cancelled);
+        }
+        match sender.try_send(message) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Disconnected(_)) => return Err(SendError::Disconnected),
+            Err(TrySendError::Full(m)) => message = m,
+        }
+        let now = Instant::now();
+        match deadline {
+            Some(deadline) if now >= deadline => return Err(SendError::Full),
+            Some(deadline) => thread::sleep(pause.min(deadline - now)),
+            None => thread::sleep(pause),
+        }
+        pause = (pause * 2).min(CANCEL_POLL);
+    }
+}
+
+/// Sleep for `duration`, unless the call gets cancelled in the meantime.
+fn sleep(duration: Duration, cancel: Option<&CancelHandle>) -> Result<(), SendError> {
+    let Some(cancel) = cancel else {
+        thread::sleep(duration);
+        return Ok(());
+    };
+    let deadline = Instant::now().checked_add(duration);
+    loop {
+        if cancel.is_cancelled() {
+            return Err(SendError::Cancelled);
+        }
+        let left = deadline.map_or(Duration::MAX, |deadline| {
+            deadline.saturating_duration_since(Instant::now())
+        });
+        if left.is_zero() {
+            return Ok(());
+        }
+        thread::sleep(left.min(CANCEL_POLL));
+    }
+}
+
+/// A random duration between zero and `max`.
+fn jitter(max: Duration) -> Duration {
+    // Every `RandomState` is seeded differently, which is all the randomness we need here.
+    let random = RandomState::new().build_hasher().finish();
+    max.mul_f64(random as f64 / u64::MAX as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::sync_channel;
+
+    #[test]
+    fn huge_backoffs_do_not_overflow() {
+        let (sender, _receiver) = sync_channel(1);
+        sender.send(()).unwrap();
+        let cancel = CancelHandle::new();
+        cancel.cancel();
+        let policy = Backpressure::Retry {
+            max_attempts: 3,
+            initial_backoff: Duration::MAX,
+            max_backoff: Duration::MAX,
+        };
+        let outcome = policy.send(&sender, (), Some(&cancel));
+        assert!(matches!(outcome, Err(SendError::Cancelled)));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::data::{TicketDraft, TicketPatch};
+use crate::store::{TicketId, TicketStore};
+
+/// A single step of a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+    Insert(TicketDraft),
+    Update(TicketPatch),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperationOutcome {
+    Inserted(TicketId),
+    Updated(TicketId),
+}
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum OperationError {
+    #[error("There is no ticket with id {0}")]
+    NotFound(TicketId),
+}
+
+/// At least one operation in the batch failed, so none of them were applied.
+///
+/// `results` has one entry per operation, in order: it shows what each operation
+/// did (or would have done) when run against the batch's working copy.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+#[error("{} out of {} operations in the batch failed", self.failures().count(), self.results.len())]
+pub struct BatchError {
+    pub results: Vec<Result<OperationOutcome, OperationError>>,
+}
+
+impl BatchError {
+    /// The failed operations, with their position in the batch.
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &OperationError)> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter_map(|(i, result)| result.as_ref().err().map(|e| (i, e)))
+    }
+}
+
+impl TicketStore {
+    /// Apply all `operations` in order, or none of them.
+    pub fn apply_batch(
+        &mut self,
+        operations: Vec<Operation>,
+    ) -> Result<Vec<OperationOutcome>, BatchError> {
+        self.transaction(|tx| {
+            let results: Vec<_> = operations.into_iter().map(|op| tx.apply(op)).collect();
+            if results.iter().any(Result::is_err) {
+                Err(BatchError { results })
+            } else {
+                Ok(results.into_iter().map(Result::unwrap).collect())
+            }
+        })
+    }
+
+    pub fn apply(&mut self, operation: Operation) -> Result<OperationOutcome, OperationError> {
+        match operation {
+            Operation::Insert(draft) => Ok(OperationOutcome::Inserted(self.add_ticket(draft))),
+            Operation::Update(patch) => {
+                let id = patch.id;
+                self.update(patch).ok_or(OperationError::NotFound(id))?;
+                Ok(OperationOutcome::Updated(id))
+            }
+        }
+    }
+}
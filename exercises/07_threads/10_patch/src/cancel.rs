@@ -0,0 +1,161 @@
+//! Giving up on commands that are no longer needed.
+//!
+//! A command can carry a deadline, set with
+//! [`TicketStoreClient::with_timeout`](crate::TicketStoreClient::with_timeout) or the
+//! `*_timeout` methods, and a [`CancelHandle`], set with
+//! [`TicketStoreClient::with_cancel`](crate::TicketStoreClient::with_cancel).
+//!
+//! The server skips commands whose deadline has passed, or that were cancelled, while
+//! they sat in its queue: they fail with [`ClientError::Timeout`](crate::ClientError::Timeout)
+//! and [`ClientError::Cancelled`](crate::ClientError::Cancelled) and have no effect.
+//! A command the server has already started still runs to completion, but its caller
+//! stops waiting for the reply as soon as it's cancelled. So does a caller still waiting
+//! for room in a full queue, whatever its [`Backpressure`](crate::backpressure::Backpressure)
+//! policy: its command never gets queued.
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::client::ClientError;
+use crate::server::Responder;
+
+/// Cancels every command sent with it, pending or future.
+///
+/// Clones share their state: keep one, and give the other to the client making the calls.
+#[derive(Clone, Default)]
+pub struct CancelHandle(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    /// Wake up the callers waiting for a reply. Only updated while holding the lock,
+    /// so that no caller starts waiting after the handle was cancelled.
+    waiters: Mutex<Waiters>,
+}
+
+#[derive(Default)]
+struct Waiters {
+    next: u64,
+    wake: HashMap<u64, Box<dyn FnOnce() + Send>>,
+}
+
+/// Why the caller gave up on a command, see [`Trace::abandoned`](crate::trace::Trace::abandoned).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Abandoned {
+    Cancelled,
+    /// Its deadline has passed.
+    Expired,
+}
+
+/// Stops waking up its caller once dropped.
+pub(crate) struct Waiter<'a> {
+    handle: &'a CancelHandle,
+    id: u64,
+}
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the commands still queued, and stop waiting for the ones being processed.
+    pub fn cancel(&self) {
+        let wake = {
+            let mut waiters = self.0.waiters.lock().unwrap();
+            self.0.cancelled.store(true, Ordering::Release);
+            std::mem::take(&mut waiters.wake)
+        };
+        for wake in wake.into_values() {
+            wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Call `wake` if the handle gets cancelled while the returned [`Waiter`] is alive.
+    /// `None` if it has been cancelled already.
+    pub(crate) fn on_cancel(&self, wake: impl FnOnce() + Send + 'static) -> Option<Waiter<'_>> {
+        let mut waiters = self.0.waiters.lock().unwrap();
+        if self.is_cancelled() {
+            return None;
+        }
+        let id = waiters.next;
+        waiters.next += 1;
+        waiters.wake.insert(id, Box::new(wake));
+        Some(Waiter { handle: self, id })
+    }
+}
+
+/// Answers a command with [`ClientError::Cancelled`] in place of the server.
+///
+/// The command owns it, through its [`Trace`](crate::trace::Trace), while the caller
+/// waiting for the reply only holds a weak reference: once the server drops the command
+/// without replying, the response channel is closed and the caller knows it's gone.
+#[derive(Clone)]
+pub(crate) struct CancelReply(Arc<dyn Fn() + Send + Sync>);
+
+impl CancelReply {
+    pub(crate) fn new<T: Send + 'static>(response_channel: Responder<T>) -> Self {
+        Self(Arc::new(move || {
+            let _ = response_channel.try_send(Err(ClientError::Cancelled));
+        }))
+    }
+
+    /// Reply once `cancel` is cancelled, if the command is still around by then.
+    /// `None` if it has been cancelled already.
+    pub(crate) fn on_cancel<'a>(&self, cancel: &'a CancelHandle) -> Option<Waiter<'a>> {
+        let reply = Arc::downgrade(&self.0);
+        cancel.on_cancel(move || {
+            if let Some(reply) = reply.upgrade() {
+                reply();
+            }
+        })
+    }
+}
+
+impl fmt::Debug for CancelReply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CancelReply")
+    }
+}
+
+impl fmt::Debug for CancelHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelHandle")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.handle.0.waiters.lock().unwrap().wake.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn cancelling_wakes_up_the_waiters_still_around() {
+        let handle = CancelHandle::new();
+        let (sender, receiver) = channel();
+        let waiting = {
+            let sender = sender.clone();
+            handle.on_cancel(move || sender.send("waiting").unwrap())
+        };
+        let done = handle.on_cancel(move || sender.send("done").unwrap());
+        drop(done);
+
+        handle.clone().cancel();
+        assert!(handle.is_cancelled());
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), ["waiting"]);
+        drop(waiting);
+        assert!(handle.on_cancel(|| ()).is_none());
+    }
+}
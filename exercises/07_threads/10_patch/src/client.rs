@@ -0,0 +1,573 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, RecvTimeoutError, SyncSender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tracing::info_span;
+
+use crate::backpressure::{Backpressure, SendError};
+use crate::batch::{BatchError, Operation, OperationOutcome};
+use crate::cancel::{CancelHandle, CancelReply};
+use crate::csv_io::Column;
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::events::{EventFilter, Subscriber, Subscription};
+use crate::limits::ClientId;
+use crate::metrics::CommandKind;
+use crate::replication::{ReadFrom, WriteToken};
+use crate::server::{Command, Endpoint, Responder};
+use crate::store::{TicketId, TicketStore};
+use crate::trace::{CorrelationId, Trace};
+use crate::wire::ProtocolError;
+
+/// How many events [`TicketStoreClient::subscribe`] buffers.
+pub const SUBSCRIPTION_BUFFER: usize = 128;
+
+#[derive(Clone)]
+pub struct TicketStoreClient {
+    /// One per shard: tickets live on the shard at index `id % endpoints.len()`.
+    endpoints: Arc<[Endpoint]>,
+    /// Which shard gets the next insert.
+    next_insert: Arc<AtomicUsize>,
+    backpressure: Backpressure,
+    /// Tags every command sent by this client, if set.
+    correlation_id: Option<CorrelationId>,
+    identity: ClientId,
+    read_from: ReadFrom,
+    /// The writes seen by this client and its clones, per shard, see [`WriteToken`].
+    session: Arc<[AtomicU64]>,
+    /// How long each call may take, if set.
+    timeout: Option<Duration>,
+    cancel: Option<CancelHandle>,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum ClientError {
+    #[error("The store is overloaded (backpressure policy: {0})")]
+    Overloaded(Backpressure),
+    #[error("The server didn't reply in time")]
+    Timeout,
+    #[error("There is no ticket with id {0}")]
+    NotFound(TicketId),
+    #[error("The server is no longer running")]
+    ServerGone,
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+    #[error("Ticket {0} was modified concurrently")]
+    Conflict(TicketId),
+    /// The server panicked while processing the command, which had no effect.
+    #[error("The server crashed while processing the command")]
+    Crashed,
+    /// The client went over its rate limit or its quota, see [`limits`](crate::limits).
+    /// The command had no effect.
+    #[error("Too many requests, retry in {retry_after:?}")]
+    RateLimited { retry_after: Duration },
+    /// The call was cancelled through its [`CancelHandle`], see [`cancel`](crate::cancel).
+    #[error("The call was cancelled")]
+    Cancelled,
+    /// Talking to a store in another process failed, see [`socket`](crate::socket).
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum ValidationError {
+    #[error("The patch for ticket {0} doesn't change anything")]
+    EmptyPatch(TicketId),
+    #[error(transparent)]
+    Batch(#[from] BatchError),
+    #[error("The batch updates tickets that live on different shards")]
+    CrossShardBatch,
+}
+
+impl TicketStoreClient {
+    /// A client for a server you started yourself, e.g. with [`server`](crate::server).
+    pub fn from_sender(sender: SyncSender<Command>) -> Self {
+        Self::new(vec![Endpoint {
+            sender,
+            state: Arc::default(),
+            throttles: false,
+        }])
+    }
+
+    pub(crate) fn new(endpoints: Vec<Endpoint>) -> Self {
+        assert!(!endpoints.is_empty(), "A client needs at least one server");
+        Self {
+            session: endpoints.iter().map(|_| AtomicU64::new(0)).collect(),
+            endpoints: endpoints.into(),
+            next_insert: Arc::new(AtomicUsize::new(0)),
+            backpressure: Backpressure::default(),
+            correlation_id: None,
+            identity: ClientId::anonymous(),
+            read_from: ReadFrom::Primary,
+            timeout: None,
+            cancel: None,
+        }
+    }
+
+    /// Use `backpressure` when the server's queue is full.
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
+    pub fn backpressure(&self) -> Backpressure {
+        self.backpressure
+    }
+
+    /// Send commands as `identity`: the server applies the rate limits and quotas
+    /// of that client to them, see [`limits`](crate::limits).
+    ///
+    /// Clients that don't pick an identity are [`ClientId::anonymous`].
+    pub fn with_identity(mut self, identity: impl Into<ClientId>) -> Self {
+        self.identity = identity.into();
+        self
+    }
+
+    pub fn identity(&self) -> &ClientId {
+        &self.identity
+    }
+
+    /// Tag every command sent by this client with `correlation_id`, see [`trace`](crate::trace).
+    ///
+    /// Clients are cheap to clone: derive one per incoming request to follow it
+    /// through the store, e.g. `client.clone().with_correlation_id(request_id)`.
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<CorrelationId>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    /// The id set with [`with_correlation_id`](Self::with_correlation_id), if any.
+    /// Otherwise every call gets a new one.
+    pub fn correlation_id(&self) -> Option<&CorrelationId> {
+        self.correlation_id.as_ref()
+    }
+
+    /// Give up on calls that can't be queued and answered within `timeout`,
+    /// like the `*_timeout` methods do. The server skips the commands that are still
+    /// queued once it expired, see [`cancel`](crate::cancel).
+    ///
+    /// Calls that talk to every shard, such as [`search`](Self::search), get `timeout`
+    /// for each shard.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The timeout set with [`with_timeout`](Self::with_timeout), if any.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Send every command with `cancel`: once it's cancelled, pending calls fail with
+    /// [`ClientError::Cancelled`], and so do the calls made afterwards.
+    ///
+    /// Clients are cheap to clone: derive one per request that may be abandoned,
+    /// e.g. `client.clone().with_cancel(handle.clone())`.
+    pub fn with_cancel(mut self, cancel: CancelHandle) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// The handle set with [`with_cancel`](Self::with_cancel), if any.
+    pub fn cancel_handle(&self) -> Option<&CancelHandle> {
+        self.cancel.as_ref()
+    }
+
+    /// Send `get`s and `search`es to `read_from`, see [`replication`](crate::replication).
+    pub fn with_read_from(mut self, read_from: ReadFrom) -> Self {
+        self.read_from = read_from;
+        self
+    }
+
+    pub fn read_from(&self) -> ReadFrom {
+        self.read_from
+    }
+
+    /// Covers every write made so far by this client and its clones.
+    pub fn last_write(&self) -> WriteToken {
+        WriteToken {
+            positions: self
+                .session
+                .iter()
+                .map(|position| position.load(Ordering::Acquire))
+                .collect(),
+        }
+    }
+
+    /// Only read from replicas that have applied the writes covered by `token`,
+    /// on top of those made by this client.
+    ///
+    /// The returned client, and its clones, keep track of their writes separately
+    /// from this one.
+    pub fn with_read_after(mut self, token: &WriteToken) -> Self {
+        self.session = self
+            .session
+            .iter()
+            .enumerate()
+            .map(|(shard, position)| {
+                let seen = token.positions.get(shard).copied().unwrap_or(0);
+                AtomicU64::new(position.load(Ordering::Acquire).max(seen))
+            })
+            .collect();
+        self
+    }
+
+    pub fn insert(&self, draft: TicketDraft) -> Result<TicketId, ClientError> {
+        self.send(self.next_shard(), |response_channel, trace| {
+            Command::Insert {
+                draft,
+                trace,
+                response_channel,
+            }
+        })
+    }
+
+    /// Like [`insert`](Self::insert), but give up if the command can't be queued
+    /// and answered within `timeout`.
+    ///
+    /// The server skips the insert if it's still queued once the timeout expired,
+    /// but may have inserted the ticket already.
+    pub fn insert_timeout(
+        &self,
+        draft: TicketDraft,
+        timeout: Duration,
+    ) -> Result<TicketId, ClientError> {
+        self.send_timeout(
+            self.next_shard(),
+            |response_channel, trace| Command::Insert {
+                draft,
+                trace,
+                response_channel,
+            },
+            timeout,
+        )
+    }
+
+    pub fn get(&self, id: TicketId) -> Result<Option<Ticket>, ClientError> {
+        let shard = self.shard_index(id);
+        if let Some(ticket) = self.read_replica(shard, |store| store.get(id).cloned()) {
+            return Ok(ticket);
+        }
+        self.send(self.shard(id), |response_channel, trace| Command::Get {
+            id,
+            trace,
+            response_channel,
+        })
+    }
+
+    /// Like [`get`](Self::get), but give up if the command can't be queued
+    /// and answered within `timeout`.
+    pub fn get_timeout(
+        &self,
+        id: TicketId,
+        timeout: Duration,
+    ) -> Result<Option<Ticket>, ClientError> {
+        let shard = self.shard_index(id);
+        if let Some(ticket) = self.read_replica(shard, |store| store.get(id).cloned()) {
+            return Ok(ticket);
+        }
+        self.send_timeout(
+            self.shard(id),
+            |response_channel, trace| Command::Get {
+                id,
+                trace,
+                response_channel,
+            },
+            timeout,
+        )
+    }
+
+    pub fn update(&self, ticket_patch: TicketPatch) -> Result<(), ClientError> {
+        self.send(self.shard(ticket_patch.id), |response_channel, trace| {
+            Command::Update {
+                patch: ticket_patch,
+                expected: None,
+                trace,
+                response_channel,
+            }
+        })
+    }
+
+    /// Apply `ticket_patch` only if the ticket still looks exactly like `expected`,
+    /// e.g. the copy returned by an earlier `get`.
+    ///
+    /// Fails with [`ClientError::Conflict`] if someone else changed the ticket in the meantime.
+    pub fn update_if_unchanged(
+        &self,
+        expected: Ticket,
+        ticket_patch: TicketPatch,
+    ) -> Result<(), ClientError> {
+        self.send(self.shard(ticket_patch.id), |response_channel, trace| {
+            Command::Update {
+                patch: ticket_patch,
+                expected: Some(expected),
+                trace,
+                response_channel,
+            }
+        })
+    }
+
+    /// Apply all `operations` in order, or none of them.
+    ///
+    /// With a sharded server, all the tickets updated by a batch must live on the same shard.
+    pub fn batch(&self, operations: Vec<Operation>) -> Result<Vec<OperationOutcome>, ClientError> {
+        let mut shards = operations.iter().filter_map(|operation| match operation {
+            Operation::Insert(_) => None,
+            Operation::Update(patch) => Some(self.shard_index(patch.id)),
+        });
+        let shard = match shards.next() {
+            None => self.next_shard(),
+            Some(shard) if shards.all(|other| other == shard) => &self.endpoints[shard],
+            Some(_) => return Err(ValidationError::CrossShardBatch.into()),
+        };
+        self.send(shard, |response_channel, trace| Command::Batch {
+            operations,
+            trace,
+            response_channel,
+        })
+    }
+
+    /// The tickets whose title or description contains `query`, ignoring case, in id order.
+    pub fn search(&self, query: &str) -> Result<Vec<Ticket>, ClientError> {
+        let client = self.for_one_call();
+        let mut tickets = Vec::new();
+        for (shard, endpoint) in self.endpoints.iter().enumerate() {
+            let from_replica = self.read_replica(shard, |store| {
+                store.search(query).cloned().collect::<Vec<_>>()
+            });
+            if let Some(found) = from_replica {
+                tickets.extend(found);
+                continue;
+            }
+            tickets.extend(
+                client.send(endpoint, |response_channel, trace| Command::Search {
+                    query: query.to_string(),
+                    trace,
+                    response_channel,
+                })?,
+            );
+        }
+        tickets.sort_by_key(|ticket| ticket.id);
+        Ok(tickets)
+    }
+
+    /// Every ticket as CSV, see [`export_csv`](crate::csv_io::export_csv).
+    ///
+    /// With a sharded server, rows are grouped by shard.
+    pub fn export_csv(&self, columns: &[Column]) -> Result<Vec<u8>, ClientError> {
+        let client = self.for_one_call();
+        let mut csv = Vec::new();
+        for (shard, endpoint) in self.endpoints.iter().enumerate() {
+            csv.extend(
+                client.send(endpoint, |response_channel, trace| Command::Export {
+                    columns: columns.to_vec(),
+                    header: shard == 0,
+                    trace,
+                    response_channel,
+                })?,
+            );
+        }
+        Ok(csv)
+    }
+
+    /// Receive the changes applied by the server from now on, see [`Subscription`].
+    ///
+    /// Up to [`SUBSCRIPTION_BUFFER`] events are buffered, use
+    /// [`subscribe_with_buffer`](Self::subscribe_with_buffer) to pick a different size.
+    pub fn subscribe(&self, filter: EventFilter) -> Result<Subscription, ClientError> {
+        self.subscribe_with_buffer(filter, SUBSCRIPTION_BUFFER)
+    }
+
+    /// Like [`subscribe`](Self::subscribe), buffering up to `buffer` events.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is zero.
+    pub fn subscribe_with_buffer(
+        &self,
+        filter: EventFilter,
+        buffer: usize,
+    ) -> Result<Subscription, ClientError> {
+        assert!(buffer > 0, "The subscription buffer can't be empty");
+        let client = self.for_one_call();
+        let (sender, receiver) = sync_channel(buffer);
+        // Every shard publishes its own changes into the same buffer.
+        for endpoint in self.endpoints.iter() {
+            client.send(endpoint, |response_channel, trace| Command::Subscribe {
+                subscriber: Subscriber::new(filter, sender.clone()),
+                trace,
+                response_channel,
+            })?;
+        }
+        Ok(Subscription::new(receiver))
+    }
+
+    /// This client, with the correlation id shared by the commands of a call
+    /// that talks to every shard.
+    fn for_one_call(&self) -> Self {
+        let mut client = self.clone();
+        client
+            .correlation_id
+            .get_or_insert_with(CorrelationId::generate);
+        client
+    }
+
+    /// Run `read` on a replica of `shard`, if the client reads from replicas
+    /// and one of them is fresh enough.
+    fn read_replica<T>(&self, shard: usize, read: impl FnOnce(&TicketStore) -> T) -> Option<T> {
+        let ReadFrom::Replica { max_staleness } = self.read_from else {
+            return None;
+        };
+        let state = &self.endpoints[shard].state;
+        // Let the server report that it's gone.
+        if state.is_stopping() {
+            return None;
+        }
+        let after = self.session[shard].load(Ordering::Acquire);
+        state.replication.as_ref()?.read(after, max_staleness, read)
+    }
+
+    fn shard_index(&self, id: TicketId) -> usize {
+        (id.value() % self.endpoints.len() as u64) as usize
+    }
+
+    /// The shard that owns `id`.
+    fn shard(&self, id: TicketId) -> &Endpoint {
+        &self.endpoints[self.shard_index(id)]
+    }
+
+    /// New tickets are spread across shards in a round-robin fashion.
+    fn next_shard(&self) -> &Endpoint {
+        let shard = self.next_insert.fetch_add(1, Ordering::Relaxed);
+        &self.endpoints[shard % self.endpoints.len()]
+    }
+
+    fn send<T: Send + 'static>(
+        &self,
+        endpoint: &Endpoint,
+        command: impl FnOnce(Responder<T>, Trace) -> Command,
+    ) -> Result<T, ClientError> {
+        match self.timeout {
+            Some(timeout) => self.send_timeout(endpoint, command, timeout),
+            None => self.dispatch(endpoint, command, self.backpressure, None),
+        }
+    }
+
+    fn send_timeout<T: Send + 'static>(
+        &self,
+        endpoint: &Endpoint,
+        command: impl FnOnce(Responder<T>, Trace) -> Command,
+        timeout: Duration,
+    ) -> Result<T, ClientError> {
+        // No deadline if `timeout` is too large to have one, e.g. `Duration::MAX`.
+        let deadline = Instant::now().checked_add(timeout);
+        self.dispatch(endpoint, command, Backpressure::BlockFor(timeout), deadline)
+    }
+
+    fn dispatch<T: Send + 'static>(
+        &self,
+        endpoint: &Endpoint,
+        command: impl FnOnce(Responder<T>, Trace) -> Command,
+        backpressure: Backpressure,
+        deadline: Option<Instant>,
+    ) -> Result<T, ClientError> {
+        if endpoint.state.is_stopping() {
+            return Err(ClientError::ServerGone);
+        }
+        let metrics = &endpoint.state.metrics;
+        let correlation_id = self
+            .correlation_id
+            .clone()
+            .unwrap_or_else(CorrelationId::generate);
+        let span = info_span!(
+            "ticket_store.call",
+            command = tracing::field::Empty,
+            correlation_id = %correlation_id,
+            client = %self.identity,
+        );
+        let _call = span.enter();
+        // Over the rate limit: don't take a queue slot the other clients could use.
+        if endpoint.throttles {
+            if let Err(retry_after) = endpoint.state.throttle(&self.identity) {
+                metrics.rate_limited();
+                return Err(ClientError::RateLimited { retry_after });
+            }
+        }
+        // The command never reaches the queue: the token wasn't used.
+        let refund = |error| {
+            if endpoint.throttles {
+                endpoint.state.refund(&self.identity);
+            }
+            error
+        };
+        let (response_sender, response_receiver) = sync_channel(1);
+        let mut trace = Trace::new(self.identity.clone(), correlation_id);
+        trace.deadline = deadline;
+        trace.cancel = self.cancel.clone();
+        trace.throttled = endpoint.throttles;
+        // Stop waiting for the reply as soon as the call is cancelled. The waiter
+        // mustn't keep the response channel open: the command does.
+        let mut _waiter = None;
+        if let Some(cancel) = &self.cancel {
+            let reply = CancelReply::new(response_sender.clone());
+            _waiter = Some(
+                reply
+                    .on_cancel(cancel)
+                    .ok_or_else(|| refund(ClientError::Cancelled))?,
+            );
+            trace.cancel_reply = Some(reply);
+        }
+        let command = command(response_sender, trace);
+        let kind = CommandKind::of(&command);
+        if let Some(kind) = kind {
+            span.record("command", kind.as_str());
+        }
+        let started = Instant::now();
+        info_span!("enqueue")
+            .in_scope(|| backpressure.send(&endpoint.sender, command, self.cancel.as_ref()))
+            .map_err(|e| {
+                refund(match e {
+                    SendError::Full => {
+                        metrics.overloaded();
+                        ClientError::Overloaded(backpressure)
+                    }
+                    SendError::Disconnected => ClientError::ServerGone,
+                    SendError::Cancelled => ClientError::Cancelled,
+                })
+            })?;
+        metrics.enqueued();
+        // The server drops the command without replying only if it died, or if it was
+        // still queued when the shutdown deadline expired.
+        let response = match deadline {
+            None => response_receiver
+                .recv()
+                .map_err(|_| ClientError::ServerGone)?,
+            Some(deadline) => response_receiver
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .map_err(|e| match e {
+                    RecvTimeoutError::Timeout => {
+                        metrics.timed_out();
+                        ClientError::Timeout
+                    }
+                    RecvTimeoutError::Disconnected => ClientError::ServerGone,
+                })?,
+        };
+        if let Some(kind) = kind {
+            metrics.answered(kind, started.elapsed());
+        }
+        // The server logs a write before replying: the head covers it.
+        let wrote = matches!(
+            kind,
+            Some(CommandKind::Insert | CommandKind::Update | CommandKind::Batch)
+        );
+        if let (true, Ok(_), Some(replication)) = (wrote, &response, &endpoint.state.replication) {
+            let shard = self
+                .endpoints
+                .iter()
+                .position(|other| Arc::ptr_eq(&other.state, &endpoint.state))
+                .expect("Endpoints belong to their client");
+            self.session[shard].fetch_max(replication.head(), Ordering::AcqRel);
+        }
+        response
+    }
+}
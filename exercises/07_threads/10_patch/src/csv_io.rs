@@ -0,0 +1,228 @@
+use std::fmt;
+use std::io;
+
+use ticket_fields::{TicketDescription, TicketTitle};
+
+use crate::data::{Status, Ticket, TicketDraft, TicketPatch};
+use crate::store::TicketStore;
+
+/// A column of an exported CSV file.
+#[derive(Clone, Debug)]
+pub enum Column {
+    Id,
+    /// The human-readable ticket key, empty if the store doesn't assign keys.
+    Key,
+    Title,
+    Description,
+    Status,
+    /// An extra column, computed from each ticket on export.
+    Computed {
+        header: String,
+        value: fn(&Ticket) -> String,
+    },
+}
+
+impl Column {
+    /// `id`, `title`, `description` and `status`.
+    pub fn defaults() -> Vec<Column> {
+        vec![
+            Column::Id,
+            Column::Title,
+            Column::Description,
+            Column::Status,
+        ]
+    }
+
+    pub fn header(&self) -> &str {
+        match self {
+            Column::Id => "id",
+            Column::Key => "key",
+            Column::Title => TITLE,
+            Column::Description => DESCRIPTION,
+            Column::Status => STATUS,
+            Column::Computed { header, .. } => header,
+        }
+    }
+
+    fn value(&self, store: &TicketStore, ticket: &Ticket) -> String {
+        match self {
+            Column::Id => ticket.id.to_string(),
+            Column::Key => store
+                .key(ticket.id)
+                .map(|key| key.to_string())
+                .unwrap_or_default(),
+            Column::Title => ticket.title.as_str().to_string(),
+            Column::Description => ticket.description.as_str().to_string(),
+            Column::Status => ticket.status.to_string(),
+            Column::Computed { value, .. } => value(ticket),
+        }
+    }
+}
+
+const TITLE: &str = "title";
+const DESCRIPTION: &str = "description";
+const STATUS: &str = "status";
+
+/// Write every ticket in `store` as a CSV row, with a header row first.
+pub fn export_csv(
+    store: &TicketStore,
+    columns: &[Column],
+    writer: impl io::Write,
+) -> Result<(), csv::Error> {
+    write_csv(store, columns, true, writer)
+}
+
+pub(crate) fn write_csv(
+    store: &TicketStore,
+    columns: &[Column],
+    header: bool,
+    writer: impl io::Write,
+) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_writer(writer);
+    if header {
+        writer.write_record(columns.iter().map(Column::header))?;
+    }
+    for ticket in store.iter() {
+        writer.write_record(columns.iter().map(|column| column.value(store, ticket)))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportMode {
+    Apply,
+    /// Validate the file and report what would be created, without touching the store.
+    DryRun,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    pub tickets: Vec<ImportedTicket>,
+}
+
+/// A ticket created (or, for a dry run, that would be created) by an import.
+#[derive(Debug, PartialEq)]
+pub struct ImportedTicket {
+    /// The line of the CSV file the ticket comes from.
+    pub line: u64,
+    pub ticket: Ticket,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FieldError {
+    pub line: u64,
+    pub column: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column `{}`: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error("The `{0}` column is missing")]
+    MissingColumn(&'static str),
+    #[error("Nothing was imported, {} fields are invalid", .0.len())]
+    Invalid(Vec<FieldError>),
+}
+
+/// Create a ticket for every row of a CSV file.
+///
+/// The file must have a header row with `title` and `description` columns.
+/// A `status` column is optional (tickets default to `To Do`); any other column is ignored.
+///
+/// Every row is validated before anything is created: if any field is invalid,
+/// nothing is imported and all the errors are reported.
+pub fn import_csv(
+    store: &mut TicketStore,
+    reader: impl io::Read,
+    mode: ImportMode,
+) -> Result<ImportReport, ImportError> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let position = |name| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name))
+    };
+    let title_column = position(TITLE).ok_or(ImportError::MissingColumn(TITLE))?;
+    let description_column =
+        position(DESCRIPTION).ok_or(ImportError::MissingColumn(DESCRIPTION))?;
+    let status_column = position(STATUS);
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |position| position.line());
+        let field = |column: usize| record.get(column).unwrap_or_default();
+        let invalid = |column, message: String| FieldError {
+            line,
+            column,
+            message,
+        };
+
+        let title =
+            TicketTitle::try_from(field(title_column)).map_err(|e| invalid(TITLE, e.to_string()));
+        let description = TicketDescription::try_from(field(description_column))
+            .map_err(|e| invalid(DESCRIPTION, e.to_string()));
+        let status = match status_column.map(field) {
+            None | Some("") => Ok(Status::ToDo),
+            Some(status) => status
+                .trim()
+                .parse::<Status>()
+                .map_err(|e| invalid(STATUS, e.to_string())),
+        };
+
+        match (title, description, status) {
+            (Ok(title), Ok(description), Ok(status)) => {
+                rows.push((line, TicketDraft { title, description }, status))
+            }
+            (title, description, status) => {
+                errors.extend(title.err());
+                errors.extend(description.err());
+                errors.extend(status.err());
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ImportError::Invalid(errors));
+    }
+
+    let mut scratch;
+    let target = match mode {
+        ImportMode::Apply => store,
+        ImportMode::DryRun => {
+            scratch = store.clone();
+            &mut scratch
+        }
+    };
+    let tickets = rows
+        .into_iter()
+        .map(|(line, draft, status)| {
+            let id = target.add_ticket(draft);
+            let ticket = target
+                .update(TicketPatch {
+                    id,
+                    title: None,
+                    description: None,
+                    status: Some(status),
+                })
+                .expect("The ticket was just created")
+                .clone();
+            ImportedTicket { line, ticket }
+        })
+        .collect();
+    Ok(ImportReport { mode, tickets })
+}
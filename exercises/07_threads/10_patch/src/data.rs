@@ -1,3 +1,6 @@
+use std::fmt;
+use std::str::FromStr;
+
 use crate::store::TicketId;
 use ticket_fields::{TicketDescription, TicketTitle};
 
@@ -29,3 +32,38 @@ pub enum Status {
     InProgress,
     Done,
 }
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            Status::ToDo => "To Do",
+            Status::InProgress => "In Progress",
+            Status::Done => "Done",
+        };
+        f.write_str(status)
+    }
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("`{0}` is not a valid status, expected one of `To Do`, `In Progress` or `Done`")]
+pub struct ParseStatusError(String);
+
+impl FromStr for Status {
+    type Err = ParseStatusError;
+
+    /// Case-insensitive, and lenient about spaces, dashes and underscores between words:
+    /// `To Do`, `todo` and `TO_DO` are all `Status::ToDo`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized: String = s
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        match normalized.as_str() {
+            "todo" => Ok(Status::ToDo),
+            "inprogress" => Ok(Status::InProgress),
+            "done" => Ok(Status::Done),
+            _ => Err(ParseStatusError(s.to_string())),
+        }
+    }
+}
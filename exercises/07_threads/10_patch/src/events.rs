@@ -0,0 +1,194 @@
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TryRecvError, TrySendError};
+use std::time::Duration;
+
+use ticket_fields::{TicketDescription, TicketTitle};
+
+use crate::data::{Status, Ticket};
+use crate::store::TicketId;
+
+/// A change applied by the server, as seen by subscribers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TicketEvent {
+    Created(Ticket),
+    /// The title and/or the description changed.
+    /// Only the fields that changed are set, with their new value.
+    Updated {
+        id: TicketId,
+        title: Option<TicketTitle>,
+        description: Option<TicketDescription>,
+    },
+    StatusChanged {
+        id: TicketId,
+        from: Status,
+        to: Status,
+    },
+}
+
+impl TicketEvent {
+    pub fn id(&self) -> TicketId {
+        match self {
+            TicketEvent::Created(ticket) => ticket.id,
+            TicketEvent::Updated { id, .. } | TicketEvent::StatusChanged { id, .. } => *id,
+        }
+    }
+
+    /// The events describing how `before` became `after`.
+    pub(crate) fn diff(before: &Ticket, after: &Ticket) -> Vec<TicketEvent> {
+        let mut events = Vec::new();
+        let title = (before.title != after.title).then(|| after.title.clone());
+        let description =
+            (before.description != after.description).then(|| after.description.clone());
+        if title.is_some() || description.is_some() {
+            events.push(TicketEvent::Updated {
+                id: after.id,
+                title,
+                description,
+            });
+        }
+        if before.status != after.status {
+            events.push(TicketEvent::StatusChanged {
+                id: after.id,
+                from: before.status,
+                to: after.status,
+            });
+        }
+        events
+    }
+}
+
+/// Which events a subscriber wants to see.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EventFilter {
+    #[default]
+    All,
+    /// Every change to a single ticket.
+    Ticket(TicketId),
+    /// Changes to tickets that have this status, or are moving in or out of it.
+    Status(Status),
+}
+
+impl EventFilter {
+    /// `ticket` is the ticket the event is about, after the change.
+    fn matches(&self, event: &TicketEvent, ticket: &Ticket) -> bool {
+        match self {
+            EventFilter::All => true,
+            EventFilter::Ticket(id) => *id == ticket.id,
+            EventFilter::Status(status) => match event {
+                TicketEvent::StatusChanged { from, to, .. } => from == status || to == status,
+                _ => ticket.status == *status,
+            },
+        }
+    }
+}
+
+pub(crate) enum Notification {
+    Event(TicketEvent),
+    /// This many events were dropped because the subscriber's buffer was full.
+    Lagged(u64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+pub enum SubscriptionError {
+    #[error("The subscriber fell behind and missed {0} events")]
+    Lagged(u64),
+    #[error("The server has shut down")]
+    Closed,
+}
+
+/// The receiving end of [`TicketStoreClient::subscribe`](crate::TicketStoreClient::subscribe).
+///
+/// Events are buffered: a subscriber that doesn't keep up misses events rather than
+/// slowing the server down, and is told how many it missed with [`SubscriptionError::Lagged`].
+/// It can keep receiving after that.
+pub struct Subscription {
+    receiver: Receiver<Notification>,
+}
+
+impl Subscription {
+    pub(crate) fn new(receiver: Receiver<Notification>) -> Self {
+        Self { receiver }
+    }
+
+    /// Wait for the next event.
+    pub fn recv(&self) -> Result<TicketEvent, SubscriptionError> {
+        match self.receiver.recv() {
+            Ok(notification) => notification.into(),
+            Err(_) => Err(SubscriptionError::Closed),
+        }
+    }
+
+    /// The next event, if one is already buffered.
+    pub fn try_recv(&self) -> Result<Option<TicketEvent>, SubscriptionError> {
+        match self.receiver.try_recv() {
+            Ok(notification) => Result::from(notification).map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(SubscriptionError::Closed),
+        }
+    }
+
+    /// Wait for the next event, for at most `timeout`.
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<TicketEvent>, SubscriptionError> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(notification) => Result::from(notification).map(Some),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(SubscriptionError::Closed),
+        }
+    }
+}
+
+impl From<Notification> for Result<TicketEvent, SubscriptionError> {
+    fn from(notification: Notification) -> Self {
+        match notification {
+            Notification::Event(event) => Ok(event),
+            Notification::Lagged(missed) => Err(SubscriptionError::Lagged(missed)),
+        }
+    }
+}
+
+/// The server's end of a [`Subscription`].
+pub struct Subscriber {
+    filter: EventFilter,
+    sender: SyncSender<Notification>,
+    /// Events dropped since the subscriber was last told it lagged behind.
+    missed: u64,
+}
+
+impl Subscriber {
+    pub(crate) fn new(filter: EventFilter, sender: SyncSender<Notification>) -> Self {
+        Self {
+            filter,
+            sender,
+            missed: 0,
+        }
+    }
+
+    /// Forward `event` if it passes the filter, without ever blocking.
+    /// Returns `false` once the subscription has been dropped.
+    pub(crate) fn notify(&mut self, event: &TicketEvent, ticket: &Ticket) -> bool {
+        if !self.filter.matches(event, ticket) {
+            return true;
+        }
+        // Report the gap before anything that came after it.
+        if self.missed > 0 {
+            match self.sender.try_send(Notification::Lagged(self.missed)) {
+                Ok(()) => self.missed = 0,
+                Err(TrySendError::Full(_)) => {
+                    self.missed += 1;
+                    return true;
+                }
+                Err(TrySendError::Disconnected(_)) => return false,
+            }
+        }
+        match self.sender.try_send(Notification::Event(event.clone())) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.missed += 1;
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
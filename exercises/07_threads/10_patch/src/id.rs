@@ -0,0 +1,474 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TicketId(u64);
+
+impl TicketId {
+    pub fn value(self) -> u64 {
+        self.0
+    }
+
+    /// Ids are only handed out by stores, this is for ids read back from the wire.
+    pub(crate) fn from_value(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Display for TicketId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("`{0}` is not a valid ticket id")]
+pub struct ParseTicketIdError(String);
+
+impl FromStr for TicketId {
+    type Err = ParseTicketIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_number(s)
+            .map(Self)
+            .ok_or_else(|| ParseTicketIdError(s.to_string()))
+    }
+}
+
+/// The short, uppercase identifier of a project, e.g. `PROJ`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProjectKey(String);
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum ProjectKeyError {
+    #[error("The project key cannot be empty")]
+    Empty,
+    #[error("The project key cannot be longer than 10 characters")]
+    TooLong,
+    #[error("The project key must start with an uppercase letter")]
+    InvalidStart,
+    #[error("The project key can only contain uppercase letters and digits")]
+    InvalidCharacter,
+}
+
+impl ProjectKey {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for ProjectKey {
+    type Error = ProjectKeyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        validate_project_key(&value)?;
+        Ok(Self(value))
+    }
+}
+
+impl TryFrom<&str> for ProjectKey {
+    type Error = ProjectKeyError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        validate_project_key(value)?;
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl fmt::Display for ProjectKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn validate_project_key(key: &str) -> Result<(), ProjectKeyError> {
+    let mut chars = key.chars();
+    match chars.next() {
+        None => return Err(ProjectKeyError::Empty),
+        Some(c) if !c.is_ascii_uppercase() => return Err(ProjectKeyError::InvalidStart),
+        Some(_) => {}
+    }
+    if key.len() > 10 {
+        Err(ProjectKeyError::TooLong)
+    } else if !chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+        Err(ProjectKeyError::InvalidCharacter)
+    } else {
+        Ok(())
+    }
+}
+
+/// A human-readable ticket key, e.g. `PROJ-42`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TicketKey {
+    pub project: ProjectKey,
+    pub number: u64,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum ParseTicketKeyError {
+    #[error("`{0}` is not a ticket key, expected something like `PROJ-42`")]
+    MissingSeparator(String),
+    #[error(transparent)]
+    InvalidProject(#[from] ProjectKeyError),
+    #[error("`{0}` is not a valid ticket number")]
+    InvalidNumber(String),
+}
+
+impl fmt::Display for TicketKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.project, self.number)
+    }
+}
+
+impl FromStr for TicketKey {
+    type Err = ParseTicketKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (project, number) = s
+            .rsplit_once('-')
+            .ok_or_else(|| ParseTicketKeyError::MissingSeparator(s.to_string()))?;
+        let project = ProjectKey::try_from(project)?;
+        let number = parse_number(number)
+            .ok_or_else(|| ParseTicketKeyError::InvalidNumber(number.to_string()))?;
+        Ok(Self { project, number })
+    }
+}
+
+/// Parse a number written the way `Display` writes it: no sign, no leading zeros,
+/// so that parsing and printing round-trip exactly.
+fn parse_number(s: &str) -> Option<u64> {
+    let canonical = s.bytes().all(|b| b.is_ascii_digit()) && (s == "0" || !s.starts_with('0'));
+    if canonical {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Anything a user can type to point at a ticket: either its numeric id or its key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TicketRef {
+    Id(TicketId),
+    Key(TicketKey),
+}
+
+impl From<TicketId> for TicketRef {
+    fn from(id: TicketId) -> Self {
+        Self::Id(id)
+    }
+}
+
+impl From<TicketKey> for TicketRef {
+    fn from(key: TicketKey) -> Self {
+        Self::Key(key)
+    }
+}
+
+impl fmt::Display for TicketRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TicketRef::Id(id) => id.fmt(f),
+            TicketRef::Key(key) => key.fmt(f),
+        }
+    }
+}
+
+impl FromStr for TicketRef {
+    type Err = ParseTicketKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<TicketId>() {
+            Ok(id) => Ok(Self::Id(id)),
+            Err(_) => s.parse().map(Self::Key),
+        }
+    }
+}
+
+/// Decides which id each new ticket gets.
+pub trait IdStrategy: Send + Sync {
+    /// The id to assign to the next ticket.
+    fn next_id(&mut self) -> TicketId;
+
+    /// The human-readable key of `id`, for strategies that assign one.
+    fn key(&self, _id: TicketId) -> Option<TicketKey> {
+        None
+    }
+
+    /// The id `key` was generated from, if it was generated by this strategy.
+    fn resolve(&self, _key: &TicketKey) -> Option<TicketId> {
+        None
+    }
+
+    fn clone_box(&self) -> Box<dyn IdStrategy>;
+}
+
+impl Clone for Box<dyn IdStrategy> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// `0`, `1`, `2`, ...
+#[derive(Clone, Debug, Default)]
+pub struct Sequential {
+    next: u64,
+}
+
+impl IdStrategy for Sequential {
+    fn next_id(&mut self) -> TicketId {
+        let id = TicketId(self.next);
+        self.next += 1;
+        id
+    }
+
+    fn clone_box(&self) -> Box<dyn IdStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+/// `offset`, `offset + step`, `offset + 2 * step`, ...
+///
+/// Generators with the same `step` and different offsets (below `step`) never collide,
+/// and the generator an id comes from can be found with `id.value() % step`.
+#[derive(Clone, Debug)]
+pub struct Strided {
+    next: u64,
+    step: u64,
+}
+
+impl Strided {
+    /// # Panics
+    ///
+    /// Panics if `step` is zero.
+    pub fn new(offset: u64, step: u64) -> Self {
+        assert!(step > 0, "The step must be greater than zero");
+        Self { next: offset, step }
+    }
+}
+
+impl IdStrategy for Strided {
+    fn next_id(&mut self) -> TicketId {
+        let id = TicketId(self.next);
+        self.next += self.step;
+        id
+    }
+
+    fn clone_box(&self) -> Box<dyn IdStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+/// Ids that sort by creation time and don't collide across generators
+/// configured with different node numbers.
+///
+/// Each id packs, from the most significant bit down:
+/// milliseconds since the Unix epoch (42 bits), the node number (10 bits)
+/// and a per-millisecond sequence number (12 bits).
+#[derive(Clone, Debug)]
+pub struct TimeOrdered {
+    node: u64,
+    last_millis: u64,
+    sequence: u64,
+}
+
+impl TimeOrdered {
+    pub const MAX_NODE: u16 = (1 << 10) - 1;
+    const SEQUENCE_BITS: u32 = 12;
+    const NODE_BITS: u32 = 10;
+
+    /// # Panics
+    ///
+    /// Panics if `node` is greater than [`TimeOrdered::MAX_NODE`].
+    pub fn new(node: u16) -> Self {
+        assert!(
+            node <= Self::MAX_NODE,
+            "The node number cannot be greater than {}",
+            Self::MAX_NODE
+        );
+        Self {
+            node: node.into(),
+            last_millis: 0,
+            sequence: 0,
+        }
+    }
+}
+
+impl IdStrategy for TimeOrdered {
+    fn next_id(&mut self) -> TicketId {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("The system clock is set before the Unix epoch")
+            .as_millis() as u64;
+        if now > self.last_millis {
+            self.last_millis = now;
+            self.sequence = 0;
+        } else {
+            // The clock didn't move (or went backwards): keep counting within
+            // the last millisecond we used, borrowing the next one when it's full.
+            self.sequence += 1;
+            if self.sequence == 1 << Self::SEQUENCE_BITS {
+                self.last_millis += 1;
+                self.sequence = 0;
+            }
+        }
+        TicketId(
+            self.last_millis << (Self::NODE_BITS + Self::SEQUENCE_BITS)
+                | self.node << Self::SEQUENCE_BITS
+                | self.sequence,
+        )
+    }
+
+    fn clone_box(&self) -> Box<dyn IdStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+/// `PROJ-1`, `PROJ-2`, ...: the numeric id is the number after the project key.
+#[derive(Clone, Debug)]
+pub struct ProjectScoped {
+    project: ProjectKey,
+    next: u64,
+}
+
+impl ProjectScoped {
+    pub fn new(project: ProjectKey) -> Self {
+        Self { project, next: 1 }
+    }
+
+    pub fn project(&self) -> &ProjectKey {
+        &self.project
+    }
+}
+
+impl IdStrategy for ProjectScoped {
+    fn next_id(&mut self) -> TicketId {
+        let id = TicketId(self.next);
+        self.next += 1;
+        id
+    }
+
+    fn key(&self, id: TicketId) -> Option<TicketKey> {
+        Some(TicketKey {
+            project: self.project.clone(),
+            number: id.0,
+        })
+    }
+
+    fn resolve(&self, key: &TicketKey) -> Option<TicketId> {
+        (key.project == self.project).then_some(TicketId(key.number))
+    }
+
+    fn clone_box(&self) -> Box<dyn IdStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticket_id_round_trips() {
+        let id = TicketId(42);
+        assert_eq!(id.to_string(), "42");
+        assert_eq!("42".parse::<TicketId>().unwrap(), id);
+    }
+
+    #[test]
+    fn invalid_ticket_id() {
+        let err = "PROJ-42".parse::<TicketId>().unwrap_err();
+        assert_eq!(err.to_string(), "`PROJ-42` is not a valid ticket id");
+        // They would print differently from how they were written.
+        for input in ["+42", "042", "00", ""] {
+            assert!(input.parse::<TicketId>().is_err(), "{input:?}");
+        }
+        assert_eq!("0".parse::<TicketId>().unwrap(), TicketId(0));
+    }
+
+    #[test]
+    fn ticket_key_round_trips() {
+        let key: TicketKey = "PROJ-42".parse().unwrap();
+        assert_eq!(key.project.as_str(), "PROJ");
+        assert_eq!(key.number, 42);
+        assert_eq!(key.to_string(), "PROJ-42");
+    }
+
+    #[test]
+    fn invalid_ticket_keys() {
+        assert_eq!(
+            "PROJ42".parse::<TicketKey>().unwrap_err(),
+            ParseTicketKeyError::MissingSeparator("PROJ42".into())
+        );
+        assert_eq!(
+            "proj-42".parse::<TicketKey>().unwrap_err(),
+            ParseTicketKeyError::InvalidProject(ProjectKeyError::InvalidStart)
+        );
+        assert_eq!(
+            "-42".parse::<TicketKey>().unwrap_err(),
+            ParseTicketKeyError::InvalidProject(ProjectKeyError::Empty)
+        );
+        assert_eq!(
+            "PROJ-x".parse::<TicketKey>().unwrap_err(),
+            ParseTicketKeyError::InvalidNumber("x".into())
+        );
+        assert_eq!(
+            "PROJ-042".parse::<TicketKey>().unwrap_err(),
+            ParseTicketKeyError::InvalidNumber("042".into())
+        );
+        assert_eq!(
+            "PROJ-+42".parse::<TicketKey>().unwrap_err(),
+            ParseTicketKeyError::InvalidNumber("+42".into())
+        );
+    }
+
+    #[test]
+    fn ticket_ref_accepts_ids_and_keys() {
+        assert_eq!(
+            "7".parse::<TicketRef>().unwrap(),
+            TicketRef::Id(TicketId(7))
+        );
+        assert_eq!(
+            "OPS-7".parse::<TicketRef>().unwrap(),
+            TicketRef::Key(TicketKey {
+                project: "OPS".try_into().unwrap(),
+                number: 7
+            })
+        );
+    }
+
+    #[test]
+    fn strided_generators_do_not_collide() {
+        let mut a = Strided::new(0, 2);
+        let mut b = Strided::new(1, 2);
+        let ids: Vec<_> = (0..3).flat_map(|_| [a.next_id(), b.next_id()]).collect();
+        let ids: Vec<_> = ids.into_iter().map(TicketId::value).collect();
+        assert_eq!(ids, [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn time_ordered_ids_increase() {
+        let mut strategy = TimeOrdered::new(3);
+        let ids: Vec<_> = (0..10_000).map(|_| strategy.next_id()).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn time_ordered_nodes_do_not_collide() {
+        let mut a = TimeOrdered::new(1);
+        let mut b = TimeOrdered::new(2);
+        for _ in 0..1_000 {
+            assert_ne!(a.next_id(), b.next_id());
+        }
+    }
+
+    #[test]
+    fn project_scoped_keys_resolve() {
+        let mut strategy = ProjectScoped::new("PROJ".try_into().unwrap());
+        let id = strategy.next_id();
+        let key = strategy.key(id).unwrap();
+        assert_eq!(key.to_string(), "PROJ-1");
+        assert_eq!(strategy.resolve(&key), Some(id));
+        assert_eq!(strategy.resolve(&"OTHER-1".parse().unwrap()), None);
+    }
+}
@@ -1,97 +1,121 @@
-use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
 
-// TODO: Implement the patching functionality.
-use crate::data::{Ticket, TicketDraft, TicketPatch};
-use crate::store::{TicketId, TicketStore};
+use crate::backpressure::Backpressure;
+use crate::id::Strided;
+use crate::limits::{Limiter, Limits};
+use crate::replication::DEFAULT_REPLICATION_LOG;
+use crate::store::TicketStore;
+use crate::supervision::Supervision;
 
+pub mod backpressure;
+pub mod batch;
+pub mod cancel;
+mod client;
+pub mod csv_io;
 pub mod data;
+pub mod events;
+pub mod id;
+pub mod limits;
+pub mod metrics;
+pub mod pool;
+pub mod project;
+pub mod replication;
+mod repository;
+mod server;
+pub mod socket;
 pub mod store;
+pub mod supervision;
+pub mod trace;
+mod wire;
 
-#[derive(Clone)]
-pub struct TicketStoreClient {
-    sender: SyncSender<Command>,
-}
+pub use client::{ClientError, TicketStoreClient, ValidationError, SUBSCRIPTION_BUFFER};
+pub use server::{
+    server, Command, Responder, ServerHandle, ShardedServerHandle, ShutdownReport, DEFAULT_WORKERS,
+};
 
-impl TicketStoreClient {
-    pub fn insert(&self, draft: TicketDraft) -> Result<TicketId, OverloadedError> {
-        let (response_sender, response_receiver) = sync_channel(1);
-        self.sender
-            .try_send(Command::Insert {
-                draft,
-                response_channel: response_sender,
-            })
-            .map_err(|_| OverloadedError)?;
-        Ok(response_receiver.recv().unwrap())
-    }
+/// How to set up a server started with [`launch_with`].
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// How many commands can be queued before the server is considered overloaded.
+    pub capacity: usize,
+    /// The policy used by the returned client when the queue is full.
+    /// Each client can pick a different one with [`TicketStoreClient::with_backpressure`].
+    pub backpressure: Backpressure,
+    /// What the server does when a command makes it panic.
+    pub supervision: Supervision,
+    /// How many worker threads run the server's read-only commands,
+    /// such as searches and exports. Zero is treated as one.
+    pub workers: usize,
+    /// The rate limits and quotas of each client. Unlimited by default.
+    pub limits: Limits,
+    /// How many read replicas to keep, see [`replication`]. None by default.
+    pub replicas: usize,
+    /// How many writes to keep around for replicas that fall behind.
+    pub replication_log: usize,
+}
 
-    pub fn get(&self, id: TicketId) -> Result<Option<Ticket>, OverloadedError> {
-        let (response_sender, response_receiver) = sync_channel(1);
-        self.sender
-            .try_send(Command::Get {
-                id,
-                response_channel: response_sender,
-            })
-            .map_err(|_| OverloadedError)?;
-        Ok(response_receiver.recv().unwrap())
+impl Config {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            backpressure: Backpressure::default(),
+            supervision: Supervision::default(),
+            workers: DEFAULT_WORKERS,
+            limits: Limits::default(),
+            replicas: 0,
+            replication_log: DEFAULT_REPLICATION_LOG,
+        }
     }
-
-    pub fn update(&self, ticket_patch: TicketPatch) -> Result<(), OverloadedError> {}
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error("The store is overloaded")]
-pub struct OverloadedError;
+pub fn launch(capacity: usize) -> (TicketStoreClient, ServerHandle) {
+    launch_with(Config::new(capacity))
+}
 
-pub fn launch(capacity: usize) -> TicketStoreClient {
-    let (sender, receiver) = sync_channel(capacity);
-    std::thread::spawn(move || server(receiver));
-    TicketStoreClient { sender }
+pub fn launch_with(config: Config) -> (TicketStoreClient, ServerHandle) {
+    let backpressure = config.backpressure;
+    let server = ServerHandle::spawn(TicketStore::new(), config);
+    let client = TicketStoreClient::new(vec![server.endpoint()]).with_backpressure(backpressure);
+    (client, server)
 }
 
-enum Command {
-    Insert {
-        draft: TicketDraft,
-        response_channel: SyncSender<TicketId>,
-    },
-    Get {
-        id: TicketId,
-        response_channel: SyncSender<Option<Ticket>>,
-    },
-    Update {
-        patch: TicketPatch,
-        response_channel: SyncSender<()>,
-    },
+/// Spread tickets across `n_shards` server threads, each with its own queue
+/// holding up to `capacity` commands.
+///
+/// The returned client routes every command to the shard that owns the ticket it targets.
+///
+/// # Panics
+///
+/// Panics if `n_shards` is zero.
+pub fn launch_sharded(
+    n_shards: usize,
+    capacity: usize,
+) -> (TicketStoreClient, ShardedServerHandle) {
+    launch_sharded_with(n_shards, Config::new(capacity))
 }
 
-pub fn server(receiver: Receiver<Command>) {
-    let mut store = TicketStore::new();
-    loop {
-        match receiver.recv() {
-            Ok(Command::Insert {
-                draft,
-                response_channel,
-            }) => {
-                let id = store.add_ticket(draft);
-                let _ = response_channel.send(id);
-            }
-            Ok(Command::Get {
-                id,
-                response_channel,
-            }) => {
-                let ticket = store.get(id);
-                let _ = response_channel.send(ticket.cloned());
-            }
-            Ok(Command::Update {
-                patch,
-                response_channel,
-            }) => {
-                todo!()
-            }
-            Err(_) => {
-                // There are no more senders, so we can safely break
-                // and shut down the server.
-                break;
-            }
-        }
-    }
+/// Like [`launch_sharded`], with every shard set up according to `config`.
+///
+/// The shards share the same [`limits`]: a client's rate and quota cover all of them.
+///
+/// # Panics
+///
+/// Panics if `n_shards` is zero.
+pub fn launch_sharded_with(
+    n_shards: usize,
+    config: Config,
+) -> (TicketStoreClient, ShardedServerHandle) {
+    assert!(n_shards > 0, "There must be at least one shard");
+    let backpressure = config.backpressure;
+    let limiter = Arc::new(Mutex::new(Limiter::new(config.limits.clone())));
+    let shards: Vec<_> = (0..n_shards as u64)
+        .map(|shard| {
+            let ids = Strided::new(shard, n_shards as u64);
+            let store = TicketStore::with_id_strategy(ids);
+            ServerHandle::spawn_limited(store, config.clone(), Arc::clone(&limiter))
+        })
+        .collect();
+    let client = TicketStoreClient::new(shards.iter().map(ServerHandle::endpoint).collect())
+        .with_backpressure(backpressure);
+    (client, ShardedServerHandle::new(shards))
 }
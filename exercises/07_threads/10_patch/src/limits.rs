@@ -0,0 +1,428 @@
+//! Per-client rate limits and quotas.
+//!
+//! Every command carries the [`ClientId`] of the client that sent it, see
+//! [`TicketStoreClient::with_identity`](crate::TicketStoreClient::with_identity).
+//! Each client gets its own token bucket, refilled at a steady rate, and its own
+//! quota of tickets created per time window. Commands over the limit are rejected with
+//! [`ClientError::RateLimited`](crate::ClientError::RateLimited) without being executed.
+//!
+//! The rate limit is checked by the client before a command is queued, so a client over
+//! its limit can't take queue slots away from the others; the server checks it again for
+//! the commands that weren't, e.g. those of a client that doesn't share its limits.
+//! The quota is checked by the server as it runs each command: the tickets the command
+//! may create are reserved right away, and given back if it doesn't create them.
+//!
+//! With a sharded server, the shards share the same limits: a client's rate and quota
+//! cover all of them, not each one.
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Who sent a command. Clones of a client share its identity.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClientId(Arc<str>);
+
+impl ClientId {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into().into())
+    }
+
+    /// The identity of clients that didn't pick one: they all share the same limits.
+    pub fn anonymous() -> Self {
+        Self::new("anonymous")
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for ClientId {
+    fn default() -> Self {
+        Self::anonymous()
+    }
+}
+
+impl fmt::Display for ClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for ClientId {
+    fn from(id: &str) -> Self {
+        Self::new(id)
+    }
+}
+
+impl From<String> for ClientId {
+    fn from(id: String) -> Self {
+        Self::new(id)
+    }
+}
+
+/// A token bucket: up to `burst` commands at once, then `per_second` commands per second.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+/// At most `tickets` tickets created per `window`, e.g. 1000 per hour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quota {
+    pub tickets: u64,
+    /// The window starts with the first ticket created after the previous one ended.
+    pub window: Duration,
+}
+
+impl Quota {
+    pub fn per_hour(tickets: u64) -> Self {
+        Self {
+            tickets,
+            window: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// The limits that apply to one client. `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClientLimits {
+    pub rate: Option<RateLimit>,
+    pub quota: Option<Quota>,
+}
+
+/// The limits of every client: the same defaults for everyone,
+/// unless a client has limits of its own.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Limits {
+    pub default: ClientLimits,
+    overrides: HashMap<ClientId, ClientLimits>,
+}
+
+impl Limits {
+    /// Apply `limits` to every client.
+    pub fn new(limits: ClientLimits) -> Self {
+        Self {
+            default: limits,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Apply `limits` to `client` instead of the defaults.
+    pub fn with_override(mut self, client: impl Into<ClientId>, limits: ClientLimits) -> Self {
+        self.overrides.insert(client.into(), limits);
+        self
+    }
+
+    pub fn of(&self, client: &ClientId) -> ClientLimits {
+        self.overrides.get(client).copied().unwrap_or(self.default)
+    }
+}
+
+/// What a client has done so far.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientUsage {
+    pub client: ClientId,
+    /// Commands run by the server.
+    pub commands: u64,
+    /// Commands rejected for going over the limits.
+    pub rate_limited: u64,
+    pub tickets_created: u64,
+    /// How many more tickets the client can create in the current window,
+    /// `None` if it has no quota.
+    pub quota_remaining: Option<u64>,
+}
+
+/// Keeps track of every client's usage. Shared, behind a lock, by the clients,
+/// the shards and their handles.
+#[derive(Default)]
+pub(crate) struct Limiter {
+    limits: Limits,
+    clients: HashMap<ClientId, ClientState>,
+}
+
+struct ClientState {
+    tokens: f64,
+    refilled_at: Instant,
+    window_start: Option<Instant>,
+    created_in_window: u64,
+    usage: ClientUsage,
+}
+
+impl Limiter {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Take a token from the bucket of `client`, before queueing one of its commands.
+    /// Returns how long to wait if there is none left.
+    pub(crate) fn throttle(&mut self, client: &ClientId, now: Instant) -> Result<(), Duration> {
+        let limits = self.limits.of(client);
+        let state = self.state(client, now);
+        let outcome = state.throttle(limits, now);
+        if outcome.is_err() {
+            state.usage.rate_limited += 1;
+        }
+        outcome
+    }
+
+    /// Put back the token taken by [`throttle`](Self::throttle) for a command
+    /// that never made it into the queue.
+    pub(crate) fn refund(&mut self, client: &ClientId) {
+        let limits = self.limits.of(client);
+        if let (Some(rate), Some(state)) = (limits.rate, self.clients.get_mut(client)) {
+            state.tokens = (state.tokens + 1.).min(f64::from(rate.burst));
+        }
+    }
+
+    /// Let a command from `client` run, unless it would create `tickets` tickets
+    /// past its quota. Returns how long to wait otherwise.
+    ///
+    /// The tickets are reserved until the command [settles](Self::settle) them,
+    /// so that concurrent commands, e.g. on other shards, can't overshoot the quota.
+    pub(crate) fn admit(
+        &mut self,
+        client: &ClientId,
+        tickets: u64,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let limits = self.limits.of(client);
+        let state = self.state(client, now);
+        let outcome = state.admit(limits, tickets, now);
+        match outcome {
+            Ok(()) => state.usage.commands += 1,
+            Err(_) => state.usage.rate_limited += 1,
+        }
+        outcome
+    }
+
+    /// Record that a command from `client`, admitted with `reserved` tickets,
+    /// actually created `created` of them: the others go back to its quota.
+    pub(crate) fn settle(&mut self, client: &ClientId, reserved: u64, created: u64, now: Instant) {
+        let state = self.state(client, now);
+        let unused = reserved.saturating_sub(created);
+        state.created_in_window = state.created_in_window.saturating_sub(unused);
+        state.usage.tickets_created += created;
+    }
+
+    /// The usage of every client seen so far, by client id.
+    pub(crate) fn usage(&self, now: Instant) -> Vec<ClientUsage> {
+        let mut usage: Vec<_> = self
+            .clients
+            .iter()
+            .map(|(client, state)| {
+                let quota = self.limits.of(client).quota;
+                ClientUsage {
+                    quota_remaining: quota.map(|quota| state.quota_remaining(quota, now)),
+                    ..state.usage.clone()
+                }
+            })
+            .collect();
+        usage.sort_by(|a, b| a.client.cmp(&b.client));
+        usage
+    }
+
+    fn state(&mut self, client: &ClientId, now: Instant) -> &mut ClientState {
+        let limits = self.limits.of(client);
+        self.clients
+            .entry(client.clone())
+            .or_insert_with(|| ClientState {
+                tokens: limits.rate.map_or(0., |rate| f64::from(rate.burst)),
+                refilled_at: now,
+                window_start: None,
+                created_in_window: 0,
+                usage: ClientUsage {
+                    client: client.clone(),
+                    commands: 0,
+                    rate_limited: 0,
+                    tickets_created: 0,
+                    quota_remaining: None,
+                },
+            })
+    }
+}
+
+impl ClientState {
+    fn admit(&mut self, limits: ClientLimits, tickets: u64, now: Instant) -> Result<(), Duration> {
+        if let Some(quota) = limits.quota {
+            self.roll_window(quota, now);
+            if tickets > 0 && self.created_in_window + tickets > quota.tickets {
+                // The window starts with the first ticket, so it's running.
+                let window_start = self.window_start.unwrap_or(now);
+                return Err((window_start + quota.window).saturating_duration_since(now));
+            }
+        }
+        if tickets > 0 {
+            self.window_start.get_or_insert(now);
+            self.created_in_window += tickets;
+        }
+        Ok(())
+    }
+
+    fn throttle(&mut self, limits: ClientLimits, now: Instant) -> Result<(), Duration> {
+        if let Some(rate) = limits.rate {
+            let elapsed = now.saturating_duration_since(self.refilled_at);
+            self.tokens =
+                (self.tokens + elapsed.as_secs_f64() * rate.per_second).min(f64::from(rate.burst));
+            self.refilled_at = now;
+            if self.tokens < 1. {
+                let missing = 1. - self.tokens;
+                return Err(
+                    Duration::try_from_secs_f64(missing / rate.per_second).unwrap_or(Duration::MAX)
+                );
+            }
+            self.tokens -= 1.;
+        }
+        Ok(())
+    }
+
+    fn roll_window(&mut self, quota: Quota, now: Instant) {
+        if self
+            .window_start
+            .is_some_and(|start| now >= start + quota.window)
+        {
+            self.window_start = None;
+            self.created_in_window = 0;
+        }
+    }
+
+    fn quota_remaining(&self, quota: Quota, now: Instant) -> u64 {
+        let expired = self
+            .window_start
+            .is_some_and(|start| now >= start + quota.window);
+        if expired {
+            quota.tickets
+        } else {
+            quota.tickets.saturating_sub(self.created_in_window)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(limits: ClientLimits) -> (Limiter, ClientId) {
+        (Limiter::new(Limits::new(limits)), ClientId::new("alice"))
+    }
+
+    #[test]
+    fn the_bucket_refills_over_time() {
+        let (mut limiter, alice) = limiter(ClientLimits {
+            rate: Some(RateLimit {
+                burst: 2,
+                per_second: 10.,
+            }),
+            quota: None,
+        });
+        // What happens to a read: throttled by the client, admitted by the server.
+        let mut send = |now| {
+            limiter
+                .throttle(&alice, now)
+                .and_then(|()| limiter.admit(&alice, 0, now))
+        };
+        let start = Instant::now();
+        assert!(send(start).is_ok());
+        assert!(send(start).is_ok());
+        let retry_after = send(start).unwrap_err();
+        assert_eq!(retry_after, Duration::from_millis(100));
+
+        assert!(send(start + retry_after).is_ok());
+        assert!(send(start + retry_after).is_err());
+        let usage = &limiter.usage(start)[0];
+        assert_eq!((usage.commands, usage.rate_limited), (3, 2));
+    }
+
+    #[test]
+    fn the_quota_resets_after_the_window() {
+        let (mut limiter, alice) = limiter(ClientLimits {
+            rate: None,
+            quota: Some(Quota::per_hour(3)),
+        });
+        let start = Instant::now();
+        assert!(limiter.admit(&alice, 2, start).is_ok());
+        limiter.settle(&alice, 2, 2, start);
+
+        let later = start + Duration::from_secs(600);
+        // Reads don't create tickets, so they are never over quota.
+        assert!(limiter.admit(&alice, 0, later).is_ok());
+        assert_eq!(
+            limiter.admit(&alice, 2, later),
+            Err(Duration::from_secs(3000))
+        );
+        assert!(limiter.admit(&alice, 1, later).is_ok());
+        limiter.settle(&alice, 1, 1, later);
+        assert_eq!(limiter.usage(later)[0].quota_remaining, Some(0));
+
+        let next_window = start + Duration::from_secs(3600);
+        assert_eq!(limiter.usage(next_window)[0].quota_remaining, Some(3));
+        assert!(limiter.admit(&alice, 3, next_window).is_ok());
+    }
+
+    #[test]
+    fn admitted_tickets_are_reserved_until_settled() {
+        let (mut limiter, alice) = limiter(ClientLimits {
+            rate: None,
+            quota: Some(Quota::per_hour(3)),
+        });
+        let now = Instant::now();
+        // Two batches in flight at once, e.g. on different shards.
+        assert!(limiter.admit(&alice, 2, now).is_ok());
+        assert!(limiter.admit(&alice, 2, now).is_err());
+        assert!(limiter.admit(&alice, 1, now).is_ok());
+
+        // The first batch failed, the second one went through.
+        limiter.settle(&alice, 2, 0, now);
+        limiter.settle(&alice, 1, 1, now);
+        let usage = &limiter.usage(now)[0];
+        assert_eq!((usage.tickets_created, usage.quota_remaining), (1, Some(2)));
+        assert!(limiter.admit(&alice, 2, now).is_ok());
+    }
+
+    #[test]
+    fn refunded_tokens_can_be_spent_again() {
+        let (mut limiter, alice) = limiter(ClientLimits {
+            rate: Some(RateLimit {
+                burst: 1,
+                per_second: 1.,
+            }),
+            quota: None,
+        });
+        let now = Instant::now();
+        assert!(limiter.throttle(&alice, now).is_ok());
+        limiter.refund(&alice);
+        assert!(limiter.throttle(&alice, now).is_ok());
+        assert!(limiter.throttle(&alice, now).is_err());
+        // The bucket never holds more than its burst.
+        limiter.refund(&alice);
+        limiter.refund(&alice);
+        assert!(limiter.throttle(&alice, now).is_ok());
+        assert!(limiter.throttle(&alice, now).is_err());
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let strict = ClientLimits {
+            rate: Some(RateLimit {
+                burst: 1,
+                per_second: 1.,
+            }),
+            quota: None,
+        };
+        let mut limiter =
+            Limiter::new(Limits::new(strict).with_override("admin", ClientLimits::default()));
+        let now = Instant::now();
+        let admin = ClientId::new("admin");
+        for _ in 0..10 {
+            assert!(limiter.throttle(&admin, now).is_ok());
+        }
+        let alice = ClientId::new("alice");
+        assert!(limiter.throttle(&alice, now).is_ok());
+        assert!(limiter.throttle(&alice, now).is_err());
+    }
+}
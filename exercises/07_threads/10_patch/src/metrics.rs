@@ -0,0 +1,425 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicIsize, AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::server::Command;
+
+/// The kinds of [`Command`] that are tracked separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CommandKind {
+    Insert,
+    Get,
+    Update,
+    Batch,
+    Subscribe,
+    Search,
+    Export,
+}
+
+impl CommandKind {
+    const ALL: [CommandKind; 7] = [
+        CommandKind::Insert,
+        CommandKind::Get,
+        CommandKind::Update,
+        CommandKind::Batch,
+        CommandKind::Subscribe,
+        CommandKind::Search,
+        CommandKind::Export,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommandKind::Insert => "insert",
+            CommandKind::Get => "get",
+            CommandKind::Update => "update",
+            CommandKind::Batch => "batch",
+            CommandKind::Subscribe => "subscribe",
+            CommandKind::Search => "search",
+            CommandKind::Export => "export",
+        }
+    }
+
+    /// `None` for the commands that are internal to the server.
+    pub(crate) fn of(command: &Command) -> Option<CommandKind> {
+        match command {
+            Command::Insert { .. } => Some(CommandKind::Insert),
+            Command::Get { .. } => Some(CommandKind::Get),
+            Command::Update { .. } => Some(CommandKind::Update),
+            Command::Batch { .. } => Some(CommandKind::Batch),
+            Command::Subscribe { .. } => Some(CommandKind::Subscribe),
+            Command::Search { .. } => Some(CommandKind::Search),
+            Command::Export { .. } => Some(CommandKind::Export),
+            Command::Shutdown => None,
+        }
+    }
+}
+
+impl fmt::Display for CommandKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The upper bounds of the latency histogram buckets.
+/// Anything slower ends up in an extra, unbounded bucket.
+pub const LATENCY_BUCKETS: [Duration; 11] = [
+    Duration::from_micros(10),
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+];
+
+#[derive(Default)]
+struct Histogram {
+    /// One counter per entry of `LATENCY_BUCKETS`, plus the unbounded bucket.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    fn record(&self, latency: Duration) {
+        let bucket = LATENCY_BUCKETS.partition_point(|bound| *bound < latency);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.sum_nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LatencyHistogram {
+        LatencyHistogram {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+            sum: Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct CommandMetrics {
+    handled: AtomicU64,
+    failed: AtomicU64,
+    latency: Histogram,
+}
+
+/// Live counters, shared by a server, its handle and its clients.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    capacity: usize,
+    commands: [CommandMetrics; CommandKind::ALL.len()],
+    /// Signed: the server can dequeue a command before its sender gets to count it.
+    queued: AtomicIsize,
+    overloaded: AtomicU64,
+    timeouts: AtomicU64,
+    restarts: AtomicU64,
+    rate_limited: AtomicU64,
+    expired: AtomicU64,
+    cancelled: AtomicU64,
+}
+
+impl Metrics {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    fn command(&self, kind: CommandKind) -> &CommandMetrics {
+        &self.commands[kind as usize]
+    }
+
+    pub(crate) fn enqueued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dequeued(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Called by the server once it has processed a command.
+    pub(crate) fn handled(&self, kind: CommandKind, succeeded: bool) {
+        let command = self.command(kind);
+        command.handled.fetch_add(1, Ordering::Relaxed);
+        if !succeeded {
+            command.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Called by the client once it got a reply, with the time since it started sending.
+    pub(crate) fn answered(&self, kind: CommandKind, latency: Duration) {
+        self.command(kind).latency.record(latency);
+    }
+
+    pub(crate) fn overloaded(&self) {
+        self.overloaded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn timed_out(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn restarted(&self) {
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn expired(&self) {
+        self.expired.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn cancelled(&self) {
+        self.cancelled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> ServerStats {
+        ServerStats {
+            capacity: self.capacity,
+            queued: self.queued.load(Ordering::Relaxed).max(0) as usize,
+            commands: CommandKind::ALL
+                .into_iter()
+                .map(|kind| {
+                    let command = self.command(kind);
+                    let stats = CommandStats {
+                        handled: command.handled.load(Ordering::Relaxed),
+                        failed: command.failed.load(Ordering::Relaxed),
+                        latency: command.latency.snapshot(),
+                    };
+                    (kind, stats)
+                })
+                .collect(),
+            overloaded: self.overloaded.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            restarts: self.restarts.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            expired: self.expired.load(Ordering::Relaxed),
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A point-in-time copy of a server's metrics.
+///
+/// Its `Display` implementation renders the Prometheus text exposition format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerStats {
+    /// How many commands the queue can hold.
+    pub capacity: usize,
+    /// How many commands are waiting in the queue.
+    pub queued: usize,
+    pub commands: BTreeMap<CommandKind, CommandStats>,
+    /// Commands rejected because the queue stayed full, see
+    /// [`ClientError::Overloaded`](crate::ClientError::Overloaded).
+    pub overloaded: u64,
+    /// Commands that didn't get a reply in time, see
+    /// [`ClientError::Timeout`](crate::ClientError::Timeout).
+    pub timeouts: u64,
+    /// How many times the server recovered from a panic, see
+    /// [`Supervision`](crate::supervision::Supervision).
+    pub restarts: u64,
+    /// Commands rejected because their client went over its limits, see
+    /// [`ClientError::RateLimited`](crate::ClientError::RateLimited).
+    pub rate_limited: u64,
+    /// Commands skipped because their deadline passed while they were queued, see
+    /// [`cancel`](crate::cancel).
+    pub expired: u64,
+    /// Commands skipped because they were cancelled while they were queued, see
+    /// [`ClientError::Cancelled`](crate::ClientError::Cancelled).
+    pub cancelled: u64,
+}
+
+impl ServerStats {
+    pub fn command(&self, kind: CommandKind) -> &CommandStats {
+        &self.commands[&kind]
+    }
+
+    /// How full the queue is, between 0 and 1.
+    pub fn occupancy(&self) -> f64 {
+        if self.capacity == 0 {
+            0.
+        } else {
+            self.queued as f64 / self.capacity as f64
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandStats {
+    /// Commands processed by the server, successfully or not.
+    pub handled: u64,
+    /// Commands the server rejected, e.g. updates to a missing ticket.
+    pub failed: u64,
+    /// Round trip time as seen by clients: queueing, processing and replying.
+    pub latency: LatencyHistogram,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatencyHistogram {
+    /// How many samples fell in each of the [`LATENCY_BUCKETS`], plus a last,
+    /// unbounded bucket. These are not cumulative.
+    pub buckets: Vec<u64>,
+    pub sum: Duration,
+}
+
+impl LatencyHistogram {
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count())
+            .ok()
+            .filter(|count| *count > 0)?;
+        Some(self.sum / count)
+    }
+
+    /// An upper bound for the `q`-quantile (e.g. `0.99`), `None` if there are no samples
+    /// or if it falls in the unbounded bucket.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let target = (q.clamp(0., 1.) * self.count() as f64).ceil().max(1.) as u64;
+        let mut seen = 0;
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            seen += bucket;
+            if seen >= target {
+                return Some(bound);
+            }
+        }
+        None
+    }
+}
+
+const PREFIX: &str = "ticket_server";
+
+impl fmt::Display for ServerStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "# HELP {PREFIX}_commands_total Commands processed by the server."
+        )?;
+        writeln!(f, "# TYPE {PREFIX}_commands_total counter")?;
+        for (kind, stats) in &self.commands {
+            writeln!(
+                f,
+                "{PREFIX}_commands_total{{command=\"{kind}\"}} {}",
+                stats.handled
+            )?;
+        }
+        writeln!(
+            f,
+            "# HELP {PREFIX}_command_errors_total Commands rejected by the server."
+        )?;
+        writeln!(f, "# TYPE {PREFIX}_command_errors_total counter")?;
+        for (kind, stats) in &self.commands {
+            writeln!(
+                f,
+                "{PREFIX}_command_errors_total{{command=\"{kind}\"}} {}",
+                stats.failed
+            )?;
+        }
+
+        let name = format!("{PREFIX}_command_duration_seconds");
+        writeln!(
+            f,
+            "# HELP {name} Round trip time of commands, as seen by clients."
+        )?;
+        writeln!(f, "# TYPE {name} histogram")?;
+        for (kind, stats) in &self.commands {
+            let mut cumulative = 0;
+            for (bucket, bound) in stats.latency.buckets.iter().zip(LATENCY_BUCKETS) {
+                cumulative += bucket;
+                let le = bound.as_secs_f64();
+                writeln!(
+                    f,
+                    "{name}_bucket{{command=\"{kind}\",le=\"{le}\"}} {cumulative}"
+                )?;
+            }
+            let count = stats.latency.count();
+            writeln!(f, "{name}_bucket{{command=\"{kind}\",le=\"+Inf\"}} {count}")?;
+            let sum = stats.latency.sum.as_secs_f64();
+            writeln!(f, "{name}_sum{{command=\"{kind}\"}} {sum}")?;
+            writeln!(f, "{name}_count{{command=\"{kind}\"}} {count}")?;
+        }
+
+        writeln!(
+            f,
+            "# HELP {PREFIX}_queue_depth Commands waiting in the queue."
+        )?;
+        writeln!(f, "# TYPE {PREFIX}_queue_depth gauge")?;
+        writeln!(f, "{PREFIX}_queue_depth {}", self.queued)?;
+        writeln!(
+            f,
+            "# HELP {PREFIX}_queue_capacity How many commands the queue can hold."
+        )?;
+        writeln!(f, "# TYPE {PREFIX}_queue_capacity gauge")?;
+        writeln!(f, "{PREFIX}_queue_capacity {}", self.capacity)?;
+        writeln!(
+            f,
+            "# HELP {PREFIX}_overloaded_total Commands rejected because the queue was full."
+        )?;
+        writeln!(f, "# TYPE {PREFIX}_overloaded_total counter")?;
+        writeln!(f, "{PREFIX}_overloaded_total {}", self.overloaded)?;
+        writeln!(
+            f,
+            "# HELP {PREFIX}_timeouts_total Commands that didn't get a reply in time."
+        )?;
+        writeln!(f, "# TYPE {PREFIX}_timeouts_total counter")?;
+        writeln!(f, "{PREFIX}_timeouts_total {}", self.timeouts)?;
+        writeln!(
+            f,
+            "# HELP {PREFIX}_restarts_total Times the server recovered from a panic."
+        )?;
+        writeln!(f, "# TYPE {PREFIX}_restarts_total counter")?;
+        writeln!(f, "{PREFIX}_restarts_total {}", self.restarts)?;
+        writeln!(
+            f,
+            "# HELP {PREFIX}_rate_limited_total Commands rejected because their client went over its limits."
+        )?;
+        writeln!(f, "# TYPE {PREFIX}_rate_limited_total counter")?;
+        writeln!(f, "{PREFIX}_rate_limited_total {}", self.rate_limited)?;
+        writeln!(
+            f,
+            "# HELP {PREFIX}_expired_total Commands skipped because their deadline passed while queued."
+        )?;
+        writeln!(f, "# TYPE {PREFIX}_expired_total counter")?;
+        writeln!(f, "{PREFIX}_expired_total {}", self.expired)?;
+        writeln!(
+            f,
+            "# HELP {PREFIX}_cancelled_total Commands skipped because they were cancelled while queued."
+        )?;
+        writeln!(f, "# TYPE {PREFIX}_cancelled_total counter")?;
+        writeln!(f, "{PREFIX}_cancelled_total {}", self.cancelled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latencies_land_in_the_first_bucket_that_fits() {
+        let histogram = Histogram::default();
+        histogram.record(Duration::from_micros(10));
+        histogram.record(Duration::from_micros(11));
+        histogram.record(Duration::from_secs(2));
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.buckets[0], 1);
+        assert_eq!(snapshot.buckets[1], 1);
+        assert_eq!(snapshot.buckets[LATENCY_BUCKETS.len()], 1);
+        assert_eq!(snapshot.count(), 3);
+        assert_eq!(snapshot.quantile(0.5), Some(Duration::from_micros(50)));
+        assert_eq!(snapshot.quantile(1.), None);
+    }
+}
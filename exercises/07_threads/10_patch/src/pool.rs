@@ -0,0 +1,216 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::supervision::panic_message;
+
+pub(crate) type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed number of worker threads, fed by a bounded queue of jobs.
+///
+/// A job that panics doesn't take its worker down: the panic is reported
+/// through the job's [`JobHandle`] and the worker moves on to the next job.
+///
+/// Dropping the pool is the same as calling [`shutdown`](Self::shutdown).
+pub struct ThreadPool {
+    sender: Option<SyncSender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum SpawnError {
+    #[error("The job queue is full")]
+    Full,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum JobError {
+    #[error("The job panicked: {0}")]
+    Panicked(String),
+    #[error("The job was dropped before it could run")]
+    Cancelled,
+}
+
+impl ThreadPool {
+    /// Start `workers` threads, sharing a queue that holds up to `capacity` pending jobs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    pub fn new(workers: usize, capacity: usize) -> Self {
+        assert!(workers > 0, "A pool needs at least one worker");
+        let (sender, receiver) = sync_channel::<Job>(capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..workers)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || work(&receiver))
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// Queue `job`, waiting for room in the queue if it's full.
+    pub fn spawn<T>(&self, job: impl FnOnce() -> T + Send + 'static) -> JobHandle<T>
+    where
+        T: Send + 'static,
+    {
+        let (job, handle) = wrap(job);
+        // Workers only stop once the sender is gone, which can't happen while we hold `&self`.
+        self.sender().send(job).expect("The workers are running");
+        handle
+    }
+
+    /// Queue `job`, or give up if the queue is full.
+    pub fn try_spawn<T>(
+        &self,
+        job: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<JobHandle<T>, SpawnError>
+    where
+        T: Send + 'static,
+    {
+        let (job, handle) = wrap(job);
+        self.try_execute(job).map_err(|_| SpawnError::Full)?;
+        Ok(handle)
+    }
+
+    /// Queue `job` as is, or hand it back if the queue is full.
+    pub(crate) fn try_execute(&self, job: Job) -> Result<(), Job> {
+        match self.sender().try_send(job) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(job)) => Err(job),
+            Err(TrySendError::Disconnected(_)) => unreachable!("The workers are running"),
+        }
+    }
+
+    /// Stop accepting jobs, wait for the queued ones to run and for every worker to exit.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn sender(&self) -> &SyncSender<Job> {
+        self.sender.as_ref().expect("The pool is running")
+    }
+
+    fn stop(&mut self) {
+        // Workers exit once the queue is empty and there is no sender left.
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            // Jobs can't panic their way out of `work`, so neither can the worker.
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn work(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        // The lock is released as soon as we have a job, before running it.
+        let job = receiver.lock().unwrap().recv();
+        match job {
+            Ok(job) => job(),
+            Err(_) => return,
+        }
+    }
+}
+
+fn wrap<T>(job: impl FnOnce() -> T + Send + 'static) -> (Job, JobHandle<T>)
+where
+    T: Send + 'static,
+{
+    let (sender, receiver) = sync_channel(1);
+    let job = Box::new(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(job))
+            .map_err(|panic| JobError::Panicked(panic_message(&*panic)));
+        // Nobody may be waiting for the result anymore.
+        let _ = sender.send(result);
+    });
+    (job, JobHandle { receiver })
+}
+
+/// The result of a job queued on a [`ThreadPool`].
+pub struct JobHandle<T> {
+    receiver: Receiver<Result<T, JobError>>,
+}
+
+impl<T> JobHandle<T> {
+    /// Wait for the job to finish.
+    pub fn join(self) -> Result<T, JobError> {
+        self.receiver.recv().unwrap_or(Err(JobError::Cancelled))
+    }
+
+    /// The job's result, or `self` back if it hasn't finished yet.
+    pub fn try_join(self) -> Result<Result<T, JobError>, Self> {
+        match self.receiver.try_recv() {
+            Ok(result) => Ok(result),
+            Err(TryRecvError::Empty) => Err(self),
+            Err(TryRecvError::Disconnected) => Ok(Err(JobError::Cancelled)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Barrier;
+
+    #[test]
+    fn jobs_return_their_result() {
+        let pool = ThreadPool::new(2, 4);
+        let handles: Vec<_> = (0..10).map(|i| pool.spawn(move || i * i)).collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, (0..10).map(|i| i * i).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn a_panicking_job_does_not_kill_its_worker() {
+        let pool = ThreadPool::new(1, 1);
+        let failed = pool.spawn(|| -> u32 { panic!("Oops") });
+        assert_eq!(failed.join(), Err(JobError::Panicked("Oops".into())));
+        assert_eq!(pool.spawn(|| 42).join(), Ok(42));
+    }
+
+    #[test]
+    fn the_queue_is_bounded() {
+        let pool = ThreadPool::new(1, 1);
+        let barrier = Arc::new(Barrier::new(2));
+        // Keep the only worker busy, then fill the queue.
+        let busy = pool.spawn({
+            let barrier = Arc::clone(&barrier);
+            move || {
+                barrier.wait();
+                barrier.wait();
+            }
+        });
+        barrier.wait();
+        let queued = pool.try_spawn(|| ()).unwrap();
+        assert!(matches!(pool.try_spawn(|| ()), Err(SpawnError::Full)));
+
+        let busy = busy.try_join().expect_err("The job is still running");
+        barrier.wait();
+        busy.join().unwrap();
+        queued.join().unwrap();
+    }
+
+    #[test]
+    fn shutdown_runs_the_queued_jobs() {
+        let pool = ThreadPool::new(2, 100);
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..50 {
+            let done = Arc::clone(&done);
+            pool.spawn(move || done.fetch_add(1, Ordering::SeqCst));
+        }
+        pool.shutdown();
+        assert_eq!(done.load(Ordering::SeqCst), 50);
+    }
+}
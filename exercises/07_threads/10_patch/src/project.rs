@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::id::{ProjectKey, ProjectScoped, TicketKey};
+use crate::store::TicketStore;
+
+/// A named group of tickets, numbered independently from every other project.
+#[derive(Clone)]
+pub struct Project {
+    key: ProjectKey,
+    name: String,
+    tickets: TicketStore,
+}
+
+impl Project {
+    pub fn new(key: ProjectKey, name: String) -> Self {
+        Self {
+            tickets: TicketStore::with_id_strategy(ProjectScoped::new(key.clone())),
+            key,
+            name,
+        }
+    }
+
+    pub fn key(&self) -> &ProjectKey {
+        &self.key
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn tickets(&self) -> &TicketStore {
+        &self.tickets
+    }
+
+    pub fn tickets_mut(&mut self) -> &mut TicketStore {
+        &mut self.tickets
+    }
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum ProjectError {
+    #[error("A project with key `{0}` already exists")]
+    DuplicateProject(ProjectKey),
+    #[error("There is no project with key `{0}`")]
+    UnknownProject(ProjectKey),
+    #[error("There is no ticket with key `{0}`")]
+    UnknownTicket(TicketKey),
+}
+
+/// Many projects, addressed by fully qualified ticket keys (e.g. `PROJ-42`).
+///
+/// When a ticket moves to another project it gets a new key:
+/// the old key keeps working as a redirect to the new one.
+#[derive(Clone, Default)]
+pub struct ProjectStore {
+    projects: BTreeMap<ProjectKey, Project>,
+    redirects: BTreeMap<TicketKey, TicketKey>,
+}
+
+impl ProjectStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create_project(
+        &mut self,
+        key: ProjectKey,
+        name: String,
+    ) -> Result<&mut Project, ProjectError> {
+        if self.projects.contains_key(&key) {
+            return Err(ProjectError::DuplicateProject(key));
+        }
+        Ok(self
+            .projects
+            .entry(key.clone())
+            .or_insert(Project::new(key, name)))
+    }
+
+    pub fn project(&self, key: &ProjectKey) -> Option<&Project> {
+        self.projects.get(key)
+    }
+
+    pub fn project_mut(&mut self, key: &ProjectKey) -> Option<&mut Project> {
+        self.projects.get_mut(key)
+    }
+
+    pub fn projects(&self) -> impl Iterator<Item = &Project> {
+        self.projects.values()
+    }
+
+    pub fn add_ticket(
+        &mut self,
+        project: &ProjectKey,
+        draft: TicketDraft,
+    ) -> Result<TicketKey, ProjectError> {
+        let project = self
+            .projects
+            .get_mut(project)
+            .ok_or_else(|| ProjectError::UnknownProject(project.clone()))?;
+        let id = project.tickets.add_ticket(draft);
+        Ok(project
+            .tickets
+            .key(id)
+            .expect("Project stores always assign ticket keys"))
+    }
+
+    /// The current key of the ticket known as `key`, following redirects
+    /// left behind by moves.
+    pub fn resolve(&self, key: &TicketKey) -> Option<TicketKey> {
+        let mut key = key;
+        while let Some(next) = self.redirects.get(key) {
+            key = next;
+        }
+        self.get_exact(key).map(|_| key.clone())
+    }
+
+    pub fn get(&self, key: &TicketKey) -> Option<&Ticket> {
+        self.get_exact(&self.resolve(key)?)
+    }
+
+    pub fn get_mut(&mut self, key: &TicketKey) -> Option<&mut Ticket> {
+        let key = self.resolve(key)?;
+        let project = self.projects.get_mut(&key.project)?;
+        let id = project.tickets.resolve(&key.into())?;
+        project.tickets.get_mut(id)
+    }
+
+    /// Move a ticket to another project, returning its new key.
+    ///
+    /// The ticket keeps its title, description and status.
+    /// Its old key becomes a redirect to the new one.
+    pub fn move_ticket(
+        &mut self,
+        key: &TicketKey,
+        to: &ProjectKey,
+    ) -> Result<TicketKey, ProjectError> {
+        let from = self
+            .resolve(key)
+            .ok_or_else(|| ProjectError::UnknownTicket(key.clone()))?;
+        if &from.project == to {
+            return Ok(from);
+        }
+        if !self.projects.contains_key(to) {
+            return Err(ProjectError::UnknownProject(to.clone()));
+        }
+
+        let source = &mut self.projects.get_mut(&from.project).unwrap().tickets;
+        let id = source.resolve(&from.clone().into()).unwrap();
+        let ticket = source.remove(id).unwrap();
+
+        let destination = &mut self.projects.get_mut(to).unwrap().tickets;
+        let id = destination.add_ticket(TicketDraft {
+            title: ticket.title,
+            description: ticket.description,
+        });
+        destination.update(TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(ticket.status),
+        });
+        let new_key = destination.key(id).unwrap();
+
+        self.redirects.insert(from, new_key.clone());
+        Ok(new_key)
+    }
+
+    fn get_exact(&self, key: &TicketKey) -> Option<&Ticket> {
+        self.projects
+            .get(&key.project)?
+            .tickets
+            .lookup(&key.clone().into())
+    }
+}
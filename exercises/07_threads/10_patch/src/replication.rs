@@ -0,0 +1,336 @@
+//! Read replicas: follower threads that keep their own copy of the store.
+//!
+//! With [`Config::replicas`](crate::Config::replicas) set, the server appends every
+//! write it applies to a replication log, as the tickets the write left behind.
+//! Each follower thread copies them into its own [`TicketStore`], in order.
+//!
+//! Clients keep reading from the server unless they opt into replica reads with
+//! [`TicketStoreClient::with_read_from`](crate::TicketStoreClient::with_read_from).
+//! A replica only serves a read if:
+//!
+//! - it's no more than `max_staleness` behind: every write it's missing was applied
+//!   by the server less than `max_staleness` ago;
+//! - it has applied the writes made by the client so far, and those of the
+//!   [`WriteToken`] it was given, if any (read-your-writes).
+//!
+//! Otherwise the read goes to the server.
+//!
+//! The log keeps the last [`Config::replication_log`](crate::Config::replication_log)
+//! writes. A follower that falls further behind catches up by copying the whole store
+//! as of the oldest write still in the log.
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::data::Ticket;
+use crate::store::TicketStore;
+
+/// How many writes the replication log keeps by default.
+pub const DEFAULT_REPLICATION_LOG: usize = 1024;
+
+/// Where a client sends its reads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadFrom {
+    /// The server: reads see every write applied so far.
+    #[default]
+    Primary,
+    /// Any replica that's fresh enough, see the [module docs](self).
+    /// Falls back to the server if none is.
+    Replica { max_staleness: Duration },
+}
+
+/// The writes a client has seen, as positions in the replication log of each shard.
+///
+/// Get one from [`TicketStoreClient::last_write`](crate::TicketStoreClient::last_write),
+/// and hand it to another client with
+/// [`TicketStoreClient::with_read_after`](crate::TicketStoreClient::with_read_after)
+/// so that its replica reads include those writes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WriteToken {
+    pub(crate) positions: Vec<u64>,
+}
+
+/// What one replica is up to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReplicaStats {
+    /// How many writes the replica has applied.
+    pub applied: u64,
+    /// How many writes the server applied that the replica hasn't yet.
+    pub behind: u64,
+    /// How many times the replica fell so far behind that it copied the whole store.
+    pub snapshots: u64,
+    /// How many reads the replica served.
+    pub reads: u64,
+}
+
+/// The replication log of a server, and the replicas that follow it.
+pub(crate) struct Replication {
+    log: Mutex<Log>,
+    /// Signalled whenever a write is appended, or the log is closed.
+    appended: Condvar,
+    replicas: Vec<Replica>,
+    /// Which replica gets the next read.
+    next_read: AtomicUsize,
+}
+
+struct Log {
+    /// The most recent writes, oldest first.
+    entries: VecDeque<Entry>,
+    capacity: usize,
+    /// The store as of `base_position`, just before the oldest write in `entries`.
+    base: TicketStore,
+    base_position: u64,
+    /// The position of the last write.
+    head: u64,
+    closed: bool,
+}
+
+#[derive(Clone)]
+struct Entry {
+    position: u64,
+    applied_at: Instant,
+    /// The tickets changed by the write, as they were right after it.
+    tickets: Vec<Ticket>,
+}
+
+struct Replica {
+    store: RwLock<TicketStore>,
+    /// Only updated while holding the `store` lock, so that the two always agree.
+    applied: AtomicU64,
+    snapshots: AtomicU64,
+    reads: AtomicU64,
+}
+
+/// What a follower needs to do to catch up.
+struct CatchUp {
+    /// Start over from this store, if the follower fell behind the log.
+    snapshot: Option<(TicketStore, u64)>,
+    entries: Vec<Entry>,
+}
+
+impl Replication {
+    /// `replicas` replicas of `store`, keeping the last `log_capacity` writes around.
+    pub(crate) fn new(store: &TicketStore, replicas: usize, log_capacity: usize) -> Self {
+        Self {
+            log: Mutex::new(Log {
+                entries: VecDeque::new(),
+                capacity: log_capacity.max(1),
+                base: store.clone(),
+                base_position: 0,
+                head: 0,
+                closed: false,
+            }),
+            appended: Condvar::new(),
+            replicas: (0..replicas)
+                .map(|_| Replica {
+                    store: RwLock::new(store.clone()),
+                    applied: AtomicU64::new(0),
+                    snapshots: AtomicU64::new(0),
+                    reads: AtomicU64::new(0),
+                })
+                .collect(),
+            next_read: AtomicUsize::new(0),
+        }
+    }
+
+    /// Start a follower thread for each replica.
+    pub(crate) fn follow(self: &Arc<Self>) -> Vec<JoinHandle<()>> {
+        (0..self.replicas.len())
+            .map(|replica| {
+                let replication = Arc::clone(self);
+                thread::spawn(move || replication.run_follower(replica))
+            })
+            .collect()
+    }
+
+    /// Record a write that changed `tickets`. Never waits for the followers.
+    pub(crate) fn append(&self, tickets: Vec<Ticket>) {
+        let mut log = self.log.lock().unwrap();
+        log.head += 1;
+        let entry = Entry {
+            position: log.head,
+            applied_at: Instant::now(),
+            tickets,
+        };
+        log.entries.push_back(entry);
+        if log.entries.len() > log.capacity {
+            let oldest = log.entries.pop_front().unwrap();
+            log.base_position = oldest.position;
+            for ticket in oldest.tickets {
+                log.base.put(ticket);
+            }
+        }
+        self.appended.notify_all();
+    }
+
+    /// The position of the last write.
+    pub(crate) fn head(&self) -> u64 {
+        self.log.lock().unwrap().head
+    }
+
+    /// Stop the followers once they have applied every write.
+    pub(crate) fn close(&self) {
+        self.log.lock().unwrap().closed = true;
+        self.appended.notify_all();
+    }
+
+    /// Run `read` against a replica that has applied every write up to `after`, and is
+    /// no more than `max_staleness` behind. `None` if there isn't one.
+    pub(crate) fn read<T>(
+        &self,
+        after: u64,
+        max_staleness: Duration,
+        read: impl FnOnce(&TicketStore) -> T,
+    ) -> Option<T> {
+        let n_replicas = self.replicas.len();
+        let first = self.next_read.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+        for i in 0..n_replicas {
+            let replica = &self.replicas[(first + i) % n_replicas];
+            let store = replica.store.read().unwrap();
+            let applied = replica.applied.load(Ordering::Acquire);
+            if applied < after || self.staleness(applied, now) > max_staleness {
+                continue;
+            }
+            replica.reads.fetch_add(1, Ordering::Relaxed);
+            return Some(read(&store));
+        }
+        None
+    }
+
+    pub(crate) fn stats(&self) -> Vec<ReplicaStats> {
+        let head = self.head();
+        self.replicas
+            .iter()
+            .map(|replica| {
+                let applied = replica.applied.load(Ordering::Acquire);
+                ReplicaStats {
+                    applied,
+                    behind: head.saturating_sub(applied),
+                    snapshots: replica.snapshots.load(Ordering::Relaxed),
+                    reads: replica.reads.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+
+    /// How long ago the server applied the oldest write after `applied`.
+    fn staleness(&self, applied: u64, now: Instant) -> Duration {
+        let log = self.log.lock().unwrap();
+        if applied >= log.head {
+            return Duration::ZERO;
+        }
+        match log
+            .entries
+            .iter()
+            .find(|entry| entry.position == applied + 1)
+        {
+            Some(entry) => now.saturating_duration_since(entry.applied_at),
+            // So far behind that the write isn't in the log anymore.
+            None => Duration::MAX,
+        }
+    }
+
+    fn run_follower(&self, replica: usize) {
+        let replica = &self.replicas[replica];
+        while let Some(catch_up) = self.wait_for_writes(replica.applied.load(Ordering::Acquire)) {
+            let mut store = replica.store.write().unwrap();
+            let mut applied = replica.applied.load(Ordering::Acquire);
+            if let Some((snapshot, position)) = catch_up.snapshot {
+                *store = snapshot;
+                applied = position;
+                replica.snapshots.fetch_add(1, Ordering::Relaxed);
+            }
+            for entry in catch_up.entries {
+                for ticket in entry.tickets {
+                    store.put(ticket);
+                }
+                applied = entry.position;
+            }
+            replica.applied.store(applied, Ordering::Release);
+        }
+    }
+
+    /// Block until there are writes after `applied`.
+    /// `None` once the log is closed and there is nothing left to apply.
+    fn wait_for_writes(&self, applied: u64) -> Option<CatchUp> {
+        let log = self
+            .appended
+            .wait_while(self.log.lock().unwrap(), |log| {
+                log.head == applied && !log.closed
+            })
+            .unwrap();
+        if log.head == applied {
+            return None;
+        }
+        if applied < log.base_position {
+            return Some(CatchUp {
+                snapshot: Some((log.base.clone(), log.base_position)),
+                entries: log.entries.iter().cloned().collect(),
+            });
+        }
+        Some(CatchUp {
+            snapshot: None,
+            entries: log
+                .entries
+                .iter()
+                .filter(|entry| entry.position > applied)
+                .cloned()
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TicketDraft;
+    use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+    fn write(store: &mut TicketStore, replication: &Replication) {
+        let id = store.add_ticket(TicketDraft {
+            title: ticket_title(),
+            description: ticket_description(),
+        });
+        replication.append(vec![store.get(id).unwrap().clone()]);
+    }
+
+    #[test]
+    fn followers_that_fall_behind_the_log_copy_the_store() {
+        let mut store = TicketStore::new();
+        let replication = Arc::new(Replication::new(&store, 1, 2));
+        // Nobody is following yet: the first writes fall off the log.
+        for _ in 0..5 {
+            write(&mut store, &replication);
+        }
+        assert_eq!(replication.stats()[0].behind, 5);
+
+        let followers = replication.follow();
+        write(&mut store, &replication);
+        replication.close();
+        for follower in followers {
+            follower.join().unwrap();
+        }
+
+        let stats = replication.stats()[0];
+        assert_eq!((stats.applied, stats.behind, stats.snapshots), (6, 0, 1));
+        let copy = replication.read(6, Duration::ZERO, |replica| replica.len());
+        assert_eq!(copy, Some(store.len()));
+    }
+
+    #[test]
+    fn stale_replicas_are_skipped() {
+        let mut store = TicketStore::new();
+        let replication = Replication::new(&store, 2, 16);
+        write(&mut store, &replication);
+        thread::sleep(Duration::from_millis(20));
+
+        // Nobody is following: the replicas are one write, and 20ms, behind.
+        assert_eq!(replication.read(0, Duration::from_millis(5), |_| ()), None);
+        assert_eq!(replication.read(1, Duration::from_secs(60), |_| ()), None);
+        let stale = replication.read(0, Duration::from_secs(60), |replica| replica.len());
+        assert_eq!(stale, Some(0));
+    }
+}
@@ -0,0 +1,49 @@
+use crate::data::{Status, Ticket, TicketDraft, TicketPatch};
+use crate::store::{TicketId, TicketStore};
+use ticket_fields::{TicketDescription, TicketTitle};
+use ticket_repository::{TicketChanges, TicketNotFound, TicketRecord, TicketRepository};
+
+impl TicketRepository for TicketStore {
+    type Id = TicketId;
+    type Status = Status;
+
+    fn insert(&mut self, title: TicketTitle, description: TicketDescription) -> TicketId {
+        self.add_ticket(TicketDraft { title, description })
+    }
+
+    fn get(&self, id: TicketId) -> Option<TicketRecord<TicketId, Status>> {
+        TicketStore::get(self, id).map(record)
+    }
+
+    fn update(
+        &mut self,
+        id: TicketId,
+        changes: TicketChanges<Status>,
+    ) -> Result<(), TicketNotFound> {
+        let patch = TicketPatch {
+            id,
+            title: changes.title,
+            description: changes.description,
+            status: changes.status,
+        };
+        TicketStore::update(self, patch).ok_or(TicketNotFound)?;
+        Ok(())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = TicketRecord<TicketId, Status>> + '_ {
+        TicketStore::iter(self).map(record)
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+}
+
+fn record(ticket: &Ticket) -> TicketRecord<TicketId, Status> {
+    TicketRecord {
+        id: ticket.id,
+        title: ticket.title.clone(),
+        description: ticket.description.clone(),
+        status: ticket.status,
+    }
+}
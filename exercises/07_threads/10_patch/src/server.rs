@@ -0,0 +1,1047 @@
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use tracing::{info_span, Span};
+
+use crate::batch::{Operation, OperationOutcome};
+use crate::cancel::Abandoned;
+use crate::client::{ClientError, ValidationError};
+use crate::csv_io::{write_csv, Column};
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::events::{Subscriber, TicketEvent};
+use crate::limits::{ClientId, ClientUsage, Limiter};
+use crate::metrics::{CommandKind, Metrics, ServerStats};
+use crate::pool::ThreadPool;
+use crate::replication::{ReplicaStats, Replication};
+use crate::store::{TicketId, TicketStore};
+use crate::supervision::{panic_message, Supervision, Suspect};
+use crate::trace::{CorrelationId, Trace};
+use crate::Config;
+
+/// The channel the server uses to reply to a command.
+pub type Responder<T> = SyncSender<Result<T, ClientError>>;
+
+pub enum Command {
+    Insert {
+        draft: TicketDraft,
+        trace: Trace,
+        response_channel: Responder<TicketId>,
+    },
+    Get {
+        id: TicketId,
+        trace: Trace,
+        response_channel: Responder<Option<Ticket>>,
+    },
+    Update {
+        patch: TicketPatch,
+        /// If set, the patch is only applied if the ticket is still equal to this.
+        expected: Option<Ticket>,
+        trace: Trace,
+        response_channel: Responder<()>,
+    },
+    Batch {
+        operations: Vec<Operation>,
+        trace: Trace,
+        response_channel: Responder<Vec<OperationOutcome>>,
+    },
+    Subscribe {
+        subscriber: Subscriber,
+        trace: Trace,
+        response_channel: Responder<()>,
+    },
+    /// See [`TicketStore::search`]. Runs on the server's worker pool.
+    Search {
+        query: String,
+        trace: Trace,
+        response_channel: Responder<Vec<Ticket>>,
+    },
+    /// See [`export_csv`](crate::csv_io::export_csv). Runs on the server's worker pool.
+    Export {
+        columns: Vec<Column>,
+        /// Whether to start with a header row.
+        header: bool,
+        trace: Trace,
+        response_channel: Responder<Vec<u8>>,
+    },
+    /// Wakes up the server so that it notices it's been asked to shut down.
+    /// Sent by [`ServerHandle`].
+    Shutdown,
+}
+
+impl Command {
+    /// How many tickets the command would create if it succeeded.
+    fn tickets(&self) -> u64 {
+        match self {
+            Command::Insert { .. } => 1,
+            Command::Batch { operations, .. } => operations
+                .iter()
+                .filter(|operation| matches!(operation, Operation::Insert(_)))
+                .count() as u64,
+            _ => 0,
+        }
+    }
+
+    /// `None` for the commands that are internal to the server.
+    pub fn trace(&self) -> Option<&Trace> {
+        match self {
+            Command::Insert { trace, .. }
+            | Command::Get { trace, .. }
+            | Command::Update { trace, .. }
+            | Command::Batch { trace, .. }
+            | Command::Subscribe { trace, .. }
+            | Command::Search { trace, .. }
+            | Command::Export { trace, .. } => Some(trace),
+            Command::Shutdown => None,
+        }
+    }
+
+    /// The span covering everything the server does for this command.
+    fn span(&self) -> Span {
+        let Some(trace) = self.trace() else {
+            return Span::none();
+        };
+        info_span!(
+            "ticket_server.command",
+            command = CommandKind::of(self).map(|kind| kind.as_str()),
+            correlation_id = %trace.correlation_id,
+        )
+    }
+}
+
+/// State shared between the server, its handle and its clients.
+#[derive(Default)]
+pub(crate) struct ServerState {
+    /// Set once shutdown has been requested, with the deadline for draining the queue.
+    stop: OnceLock<Option<Instant>>,
+    pub(crate) metrics: Arc<Metrics>,
+    /// Shared by every shard of a sharded server.
+    limiter: Arc<Mutex<Limiter>>,
+    /// `None` if the server has no replicas.
+    pub(crate) replication: Option<Arc<Replication>>,
+}
+
+impl ServerState {
+    pub(crate) fn new(
+        capacity: usize,
+        limiter: Arc<Mutex<Limiter>>,
+        replication: Option<Arc<Replication>>,
+    ) -> Self {
+        Self {
+            stop: OnceLock::new(),
+            metrics: Arc::new(Metrics::new(capacity)),
+            limiter,
+            replication,
+        }
+    }
+
+    pub(crate) fn is_stopping(&self) -> bool {
+        self.stop.get().is_some()
+    }
+
+    /// Check the rate limit of `client` before queueing one of its commands,
+    /// see [`limits`](crate::limits).
+    pub(crate) fn throttle(&self, client: &ClientId) -> Result<(), Duration> {
+        self.limiter
+            .lock()
+            .unwrap()
+            .throttle(client, Instant::now())
+    }
+
+    /// Give back the token taken by [`throttle`](Self::throttle)
+    /// for a command that couldn't be queued.
+    pub(crate) fn refund(&self, client: &ClientId) {
+        self.limiter.lock().unwrap().refund(client);
+    }
+}
+
+/// Where clients send the commands for one server.
+#[derive(Clone)]
+pub(crate) struct Endpoint {
+    pub(crate) sender: SyncSender<Command>,
+    pub(crate) state: Arc<ServerState>,
+    /// Whether `state` is the server's own, so that its rate limits can be checked
+    /// before queueing. Otherwise the server checks them itself.
+    pub(crate) throttles: bool,
+}
+
+/// What the server left behind when it stopped.
+pub struct ShutdownReport {
+    pub store: TicketStore,
+    /// Commands that were still queued when the shutdown deadline expired.
+    /// Their callers get [`ClientError::ServerGone`].
+    pub dropped_commands: usize,
+}
+
+/// Controls the lifetime of a server started with [`launch`](crate::launch).
+///
+/// Dropping the handle leaves the server running until every client is gone.
+pub struct ServerHandle {
+    sender: SyncSender<Command>,
+    state: Arc<ServerState>,
+    thread: JoinHandle<ShutdownReport>,
+    followers: Vec<JoinHandle<()>>,
+}
+
+impl ServerHandle {
+    /// Serve `store` from a new thread, plus one thread per replica.
+    pub(crate) fn spawn(store: TicketStore, config: Config) -> Self {
+        let limiter = Arc::new(Mutex::new(Limiter::new(config.limits.clone())));
+        Self::spawn_limited(store, config, limiter)
+    }
+
+    /// Like [`spawn`](Self::spawn), but enforce the limits of `limiter`,
+    /// which other shards may share, instead of those in `config`.
+    pub(crate) fn spawn_limited(
+        store: TicketStore,
+        config: Config,
+        limiter: Arc<Mutex<Limiter>>,
+    ) -> Self {
+        let (sender, receiver) = sync_channel(config.capacity);
+        let replication = (config.replicas > 0).then(|| {
+            Arc::new(Replication::new(
+                &store,
+                config.replicas,
+                config.replication_log,
+            ))
+        });
+        let followers = replication.as_ref().map(Replication::follow);
+        let state = Arc::new(ServerState::new(config.capacity, limiter, replication));
+        // A pool can't be empty: settle it here rather than let the server thread panic.
+        let workers = config.workers.max(1);
+        let thread = {
+            let state = Arc::clone(&state);
+            std::thread::spawn(move || {
+                let pool = ThreadPool::new(workers, config.capacity);
+                let server = Server::new(
+                    store,
+                    Arc::clone(&state.metrics),
+                    Arc::clone(&state.limiter),
+                    state.replication.clone(),
+                    config.supervision,
+                    pool,
+                );
+                serve(server, receiver, &state)
+            })
+        };
+        Self {
+            sender,
+            state,
+            thread,
+            followers: followers.unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn endpoint(&self) -> Endpoint {
+        Endpoint {
+            sender: self.sender.clone(),
+            state: Arc::clone(&self.state),
+            throttles: true,
+        }
+    }
+
+    /// The server's metrics, as of now.
+    pub fn stats(&self) -> ServerStats {
+        self.state.metrics.snapshot()
+    }
+
+    /// What each client has done so far, by client id, see [`limits`](crate::limits).
+    pub fn usage(&self) -> Vec<ClientUsage> {
+        self.state.limiter.lock().unwrap().usage(Instant::now())
+    }
+
+    /// What each replica is up to, see [`replication`](crate::replication).
+    /// Empty if the server has no replicas.
+    pub fn replicas(&self) -> Vec<ReplicaStats> {
+        self.state
+            .replication
+            .as_ref()
+            .map(|replication| replication.stats())
+            .unwrap_or_default()
+    }
+
+    /// Stop accepting new commands, process everything already queued
+    /// and return the final state of the store.
+    pub fn shutdown(self) -> TicketStore {
+        self.stop(None).store
+    }
+
+    /// Like [`shutdown`](Self::shutdown), but stop processing queued commands
+    /// once `timeout` has elapsed. Commands left in the queue are dropped.
+    pub fn shutdown_timeout(self, timeout: Duration) -> ShutdownReport {
+        self.stop(Instant::now().checked_add(timeout))
+    }
+
+    fn stop(self, deadline: Option<Instant>) -> ShutdownReport {
+        self.request_stop(deadline);
+        self.join()
+    }
+
+    /// Ask the server to stop, without waiting for it.
+    fn request_stop(&self, deadline: Option<Instant>) {
+        let _ = self.state.stop.set(deadline);
+        // If the queue is full the server is busy and will notice the request
+        // after its current command, no need to wake it up.
+        if self.sender.try_send(Command::Shutdown).is_ok() {
+            self.state.metrics.enqueued();
+        }
+    }
+
+    /// Wait for the server to stop, once asked to with [`request_stop`](Self::request_stop).
+    fn join(self) -> ShutdownReport {
+        drop(self.sender);
+        let report = self.thread.join();
+        // The replicas keep serving reads, they just stop following.
+        if let Some(replication) = &self.state.replication {
+            replication.close();
+        }
+        for follower in self.followers {
+            let _ = follower.join();
+        }
+        match report {
+            Ok(report) => report,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+/// Controls the lifetime of the shards started with [`launch_sharded`](crate::launch_sharded).
+pub struct ShardedServerHandle {
+    shards: Vec<ServerHandle>,
+}
+
+impl ShardedServerHandle {
+    pub(crate) fn new(shards: Vec<ServerHandle>) -> Self {
+        Self { shards }
+    }
+
+    /// The metrics of each shard, in shard order.
+    pub fn stats(&self) -> Vec<ServerStats> {
+        self.shards.iter().map(ServerHandle::stats).collect()
+    }
+
+    /// What each client has done so far, by client id, across all shards:
+    /// they share the same limits, see [`limits`](crate::limits).
+    pub fn usage(&self) -> Vec<ClientUsage> {
+        self.shards[0].usage()
+    }
+
+    /// Shut down every shard, see [`ServerHandle::shutdown`].
+    /// Returns the final store of each shard, in shard order.
+    pub fn shutdown(self) -> Vec<TicketStore> {
+        self.stop(None)
+            .into_iter()
+            .map(|report| report.store)
+            .collect()
+    }
+
+    /// Shut down every shard, see [`ServerHandle::shutdown_timeout`].
+    /// All shards share the same deadline.
+    pub fn shutdown_timeout(self, timeout: Duration) -> Vec<ShutdownReport> {
+        self.stop(Instant::now().checked_add(timeout))
+    }
+
+    fn stop(self, deadline: Option<Instant>) -> Vec<ShutdownReport> {
+        // Ask every shard to stop before waiting on any of them,
+        // so that they all drain their queues in parallel.
+        for shard in &self.shards {
+            shard.request_stop(deadline);
+        }
+        self.shards.into_iter().map(ServerHandle::join).collect()
+    }
+}
+
+pub fn server(receiver: Receiver<Command>) -> TicketStore {
+    let state = ServerState::default();
+    let pool = ThreadPool::new(DEFAULT_WORKERS, DEFAULT_WORKERS);
+    let server = Server::new(
+        TicketStore::new(),
+        Arc::clone(&state.metrics),
+        Arc::clone(&state.limiter),
+        None,
+        Supervision::default(),
+        pool,
+    );
+    serve(server, receiver, &state).store
+}
+
+/// How many worker threads a server uses by default for its read-only commands.
+pub const DEFAULT_WORKERS: usize = 2;
+
+fn serve(mut server: Server, receiver: Receiver<Command>, state: &ServerState) -> ShutdownReport {
+    // `recv` fails when there are no more senders, so we can safely
+    // shut down the server.
+    while let Ok(command) = receiver.recv() {
+        server.process(command);
+        if let Some(deadline) = state.stop.get() {
+            return server.drain(&receiver, *deadline);
+        }
+    }
+    ShutdownReport {
+        store: server.store,
+        dropped_commands: 0,
+    }
+}
+
+/// Everything owned by the server thread.
+struct Server {
+    store: TicketStore,
+    subscribers: Vec<Subscriber>,
+    /// The events of the command being handled, held back until it replies:
+    /// if it panics before that, they are dropped along with its changes.
+    unpublished: Vec<TicketEvent>,
+    /// The client of the command being handled, and how many tickets it reserved
+    /// against its quota when it was admitted, until they are settled.
+    reservation: Option<(ClientId, u64)>,
+    metrics: Arc<Metrics>,
+    limiter: Arc<Mutex<Limiter>>,
+    replication: Option<Arc<Replication>>,
+    supervision: Supervision,
+    restarts: u32,
+    /// Dropped last: in-flight read-only commands get to finish before the server exits.
+    pool: ThreadPool,
+}
+
+impl Server {
+    fn new(
+        store: TicketStore,
+        metrics: Arc<Metrics>,
+        limiter: Arc<Mutex<Limiter>>,
+        replication: Option<Arc<Replication>>,
+        supervision: Supervision,
+        pool: ThreadPool,
+    ) -> Self {
+        Self {
+            store,
+            subscribers: Vec::new(),
+            unpublished: Vec::new(),
+            reservation: None,
+            metrics,
+            limiter,
+            replication,
+            supervision,
+            restarts: 0,
+            pool,
+        }
+    }
+
+    /// Handle a command that was just taken off the queue,
+    /// recovering from panics as configured by [`Supervision`].
+    fn process(&mut self, command: Command) {
+        let span = command.span();
+        let _command = span.enter();
+        let dequeue = info_span!(
+            "dequeue",
+            queued_us = command.trace().map(|trace| trace.age().as_micros() as u64),
+        )
+        .entered();
+        self.metrics.dequeued();
+        if let Some(trace) = command.trace() {
+            match trace.abandoned() {
+                Some(Abandoned::Cancelled) => {
+                    self.metrics.cancelled();
+                    return self.reject(command, ClientError::Cancelled);
+                }
+                Some(Abandoned::Expired) => {
+                    self.metrics.expired();
+                    return self.reject(command, ClientError::Timeout);
+                }
+                None => {}
+            }
+            let tickets = command.tickets();
+            let admitted = {
+                let mut limiter = self.limiter.lock().unwrap();
+                let now = Instant::now();
+                // Commands from clients that couldn't check the rate limit themselves.
+                let throttled = if trace.throttled {
+                    Ok(())
+                } else {
+                    limiter.throttle(&trace.client, now)
+                };
+                throttled.and_then(|()| limiter.admit(&trace.client, tickets, now))
+            };
+            if let Err(retry_after) = admitted {
+                self.metrics.rate_limited();
+                return self.reject(command, ClientError::RateLimited { retry_after });
+            }
+            self.reservation = Some((trace.client.clone(), tickets));
+        }
+        let Some(suspect) = Suspect::of(&command) else {
+            dequeue.exit();
+            info_span!("execute").in_scope(|| self.handle(command));
+            // Whatever the command didn't create goes back to the quota.
+            return self.settle(0);
+        };
+        let correlation_id = command.trace().map(|trace| trace.correlation_id.clone());
+        // Commands that can change the store get a snapshot to roll back to.
+        // Cheap: the copy shares its tickets with the store until they change.
+        let mutates = matches!(
+            command,
+            Command::Insert { .. } | Command::Update { .. } | Command::Batch { .. }
+        );
+        let snapshot = mutates.then(|| self.store.clone());
+        dequeue.exit();
+        let outcome = info_span!("execute")
+            .in_scope(|| panic::catch_unwind(AssertUnwindSafe(|| self.handle(command))));
+        self.settle(0);
+        let Err(panic) = outcome else {
+            return;
+        };
+
+        if let Some(snapshot) = snapshot {
+            self.store = snapshot;
+        }
+        // Neither subscribers nor replicas get to see the changes that were rolled back.
+        self.unpublished.clear();
+        let crash = suspect.convict(&*panic, self.restarts);
+        if self.restarts >= self.supervision.max_restarts {
+            tracing::error!(
+                correlation_id = correlation_id.as_ref().map(CorrelationId::as_str),
+                restarts = self.restarts,
+                "Ticket server: {crash}, giving up"
+            );
+            self.supervision.escalate(&crash);
+            panic::resume_unwind(panic);
+        }
+        self.restarts += 1;
+        self.metrics.restarted();
+        tracing::warn!(
+            correlation_id = correlation_id.as_ref().map(CorrelationId::as_str),
+            "Ticket server: {crash}, restarting from the last consistent state"
+        );
+    }
+
+    fn drain(mut self, receiver: &Receiver<Command>, deadline: Option<Instant>) -> ShutdownReport {
+        let mut dropped_commands = 0;
+        while let Ok(command) = receiver.try_recv() {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline)
+                && !matches!(command, Command::Shutdown)
+            {
+                if let Some(trace) = command.trace() {
+                    tracing::warn!(
+                        correlation_id = %trace.correlation_id,
+                        "Dropped a command queued past the shutdown deadline"
+                    );
+                }
+                self.metrics.dequeued();
+                dropped_commands += 1;
+            } else {
+                self.process(command);
+            }
+        }
+        ShutdownReport {
+            store: self.store,
+            dropped_commands,
+        }
+    }
+
+    /// Events are published, and new tickets counted against the client's quota,
+    /// before the reply is sent: once a call returns, its effects are visible.
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Insert {
+                draft,
+                response_channel,
+                ..
+            } => {
+                let id = self.store.add_ticket(draft);
+                let ticket = self.store.get(id).unwrap().clone();
+                self.publish(&[TicketEvent::Created(ticket)]);
+                self.settle(1);
+                self.reply(CommandKind::Insert, response_channel, Ok(id));
+            }
+            Command::Get {
+                id,
+                response_channel,
+                ..
+            } => {
+                let ticket = self.store.get(id).cloned();
+                self.reply(CommandKind::Get, response_channel, Ok(ticket));
+            }
+            Command::Update {
+                patch,
+                expected,
+                response_channel,
+                ..
+            } => {
+                let before = self.store.get(patch.id).cloned();
+                let outcome = update(&mut self.store, patch, expected);
+                if let (Ok(()), Some(before)) = (&outcome, before) {
+                    let after = self.store.get(before.id).unwrap();
+                    self.publish(&TicketEvent::diff(&before, after));
+                }
+                self.reply(CommandKind::Update, response_channel, outcome);
+            }
+            Command::Batch {
+                operations,
+                response_channel,
+                ..
+            } => {
+                let mut before = BTreeMap::new();
+                for operation in &operations {
+                    if let Operation::Update(patch) = operation {
+                        if let Some(ticket) = self.store.get(patch.id) {
+                            before.entry(patch.id).or_insert_with(|| ticket.clone());
+                        }
+                    }
+                }
+                let outcome = self.store.apply_batch(operations);
+                if let Ok(outcomes) = &outcome {
+                    self.publish(&batch_events(&self.store, before, outcomes));
+                    let inserted = outcomes
+                        .iter()
+                        .filter(|outcome| matches!(outcome, OperationOutcome::Inserted(_)))
+                        .count();
+                    self.settle(inserted as u64);
+                }
+                let outcome = outcome.map_err(|e| ValidationError::from(e).into());
+                self.reply(CommandKind::Batch, response_channel, outcome);
+            }
+            Command::Subscribe {
+                subscriber,
+                response_channel,
+                ..
+            } => {
+                self.subscribers.push(subscriber);
+                self.reply(CommandKind::Subscribe, response_channel, Ok(()));
+            }
+            Command::Search {
+                query,
+                trace,
+                response_channel,
+            } => self.offload(CommandKind::Search, trace, response_channel, move |store| {
+                store.search(&query).cloned().collect()
+            }),
+            Command::Export {
+                columns,
+                header,
+                trace,
+                response_channel,
+            } => self.offload(CommandKind::Export, trace, response_channel, move |store| {
+                let mut csv = Vec::new();
+                write_csv(store, &columns, header, &mut csv).expect("Writing to memory can't fail");
+                csv
+            }),
+            Command::Shutdown => {}
+        }
+    }
+
+    /// Record that the command being handled created `tickets` tickets,
+    /// giving the rest of its reservation back to its client's quota.
+    /// Does nothing once the reservation is settled.
+    fn settle(&mut self, tickets: u64) {
+        if let Some((client, reserved)) = self.reservation.take() {
+            self.limiter
+                .lock()
+                .unwrap()
+                .settle(&client, reserved, tickets, Instant::now());
+        }
+    }
+
+    /// Answer `command` with `error`, without running it.
+    fn reject(&mut self, command: Command, error: ClientError) {
+        match command {
+            Command::Insert {
+                response_channel, ..
+            } => self.reply(CommandKind::Insert, response_channel, Err(error)),
+            Command::Get {
+                response_channel, ..
+            } => self.reply(CommandKind::Get, response_channel, Err(error)),
+            Command::Update {
+                response_channel, ..
+            } => self.reply(CommandKind::Update, response_channel, Err(error)),
+            Command::Batch {
+                response_channel, ..
+            } => self.reply(CommandKind::Batch, response_channel, Err(error)),
+            Command::Subscribe {
+                response_channel, ..
+            } => self.reply(CommandKind::Subscribe, response_channel, Err(error)),
+            Command::Search {
+                response_channel, ..
+            } => self.reply(CommandKind::Search, response_channel, Err(error)),
+            Command::Export {
+                response_channel, ..
+            } => self.reply(CommandKind::Export, response_channel, Err(error)),
+            Command::Shutdown => {}
+        }
+    }
+
+    /// Publish the command's events, now that it has run to completion, then reply.
+    fn reply<T>(
+        &mut self,
+        kind: CommandKind,
+        response_channel: Responder<T>,
+        outcome: Result<T, ClientError>,
+    ) {
+        let events = std::mem::take(&mut self.unpublished);
+        self.flush(&events);
+        reply(&self.metrics, kind, response_channel, outcome);
+    }
+
+    /// Run a read-only command on the worker pool, against a snapshot of the store,
+    /// so that the server can move on to the next command right away.
+    /// Taking the snapshot doesn't copy any ticket, see [`TicketStore`].
+    /// If the pool is busy, the command runs on the server thread instead.
+    fn offload<T: Send + 'static>(
+        &self,
+        kind: CommandKind,
+        trace: Trace,
+        response_channel: Responder<T>,
+        query: impl FnOnce(&TicketStore) -> T + Send + 'static,
+    ) {
+        let store = self.store.clone();
+        let metrics = Arc::clone(&self.metrics);
+        let span = info_span!("worker");
+        let job = Box::new(move || {
+            let _worker = span.enter();
+            let outcome =
+                panic::catch_unwind(AssertUnwindSafe(|| query(&store))).map_err(|panic| {
+                    tracing::error!(
+                        command = kind.as_str(),
+                        "Ticket server: command panicked on a worker: {}",
+                        panic_message(&*panic)
+                    );
+                    ClientError::Crashed
+                });
+            reply(&metrics, kind, response_channel, outcome);
+            // Kept until now so that the caller can still be told it was cancelled.
+            drop(trace);
+        });
+        if let Err(job) = self.pool.try_execute(job) {
+            job();
+        }
+    }
+
+    /// Tell subscribers and replicas about a write once the command replies, see
+    /// [`reply`](Self::reply). Every ticket the write changed is the subject of at least
+    /// one event.
+    fn publish(&mut self, events: &[TicketEvent]) {
+        self.unpublished.extend_from_slice(events);
+    }
+
+    fn flush(&mut self, events: &[TicketEvent]) {
+        if let (Some(replication), false) = (&self.replication, events.is_empty()) {
+            let mut ids: Vec<_> = events.iter().map(TicketEvent::id).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            let tickets = ids
+                .into_iter()
+                .map(|id| self.store.get(id).unwrap().clone())
+                .collect();
+            replication.append(tickets);
+        }
+        for event in events {
+            let ticket = self
+                .store
+                .get(event.id())
+                .expect("Events are about existing tickets");
+            self.subscribers
+                .retain_mut(|subscriber| subscriber.notify(event, ticket));
+        }
+    }
+}
+
+/// Commands are counted before replying, so that callers see their own commands
+/// in the stats.
+fn reply<T>(
+    metrics: &Metrics,
+    kind: CommandKind,
+    response_channel: Responder<T>,
+    outcome: Result<T, ClientError>,
+) {
+    metrics.handled(kind, outcome.is_ok());
+    let _reply = info_span!("reply", ok = outcome.is_ok()).entered();
+    let _ = response_channel.send(outcome);
+}
+
+fn update(
+    store: &mut TicketStore,
+    patch: TicketPatch,
+    expected: Option<Ticket>,
+) -> Result<(), ClientError> {
+    let id = patch.id;
+    if patch.title.is_none() && patch.description.is_none() && patch.status.is_none() {
+        return Err(ValidationError::EmptyPatch(id).into());
+    }
+    let current = store.get(id).ok_or(ClientError::NotFound(id))?;
+    if expected.is_some_and(|expected| &expected != current) {
+        return Err(ClientError::Conflict(id));
+    }
+    store.update(patch);
+    Ok(())
+}
+
+/// The events for a successful batch, in order.
+/// A ticket updated several times by the same batch gets a single set of events
+/// describing all of its changes.
+fn batch_events(
+    store: &TicketStore,
+    mut before: BTreeMap<TicketId, Ticket>,
+    outcomes: &[OperationOutcome],
+) -> Vec<TicketEvent> {
+    let mut events = Vec::new();
+    for outcome in outcomes {
+        match *outcome {
+            OperationOutcome::Inserted(id) => {
+                events.push(TicketEvent::Created(store.get(id).unwrap().clone()));
+            }
+            OperationOutcome::Updated(id) => {
+                // Tickets created by the batch itself are fully described by `Created`,
+                // and the first update of a ticket already covers the later ones.
+                if let Some(previous) = before.remove(&id) {
+                    events.extend(TicketEvent::diff(&previous, store.get(id).unwrap()));
+                }
+            }
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::{IdStrategy, Sequential};
+    use crate::limits::ClientId;
+    use crate::trace::CorrelationId;
+    use crate::TicketStoreClient;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::sync_channel;
+    use std::sync::Mutex;
+    use ticket_fields::test_helpers::{ticket_description, ticket_draft, ticket_title};
+    use tracing::field::{Field, Visit};
+    use tracing::{Event, Level};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    /// Hands out sequential ids, but panics while `armed` is set.
+    #[derive(Clone)]
+    struct Tripwire {
+        armed: Arc<AtomicBool>,
+        ids: Sequential,
+    }
+
+    impl IdStrategy for Tripwire {
+        fn next_id(&mut self) -> TicketId {
+            assert!(!self.armed.load(Ordering::SeqCst), "Tripwire hit");
+            self.ids.next_id()
+        }
+
+        fn clone_box(&self) -> Box<dyn IdStrategy> {
+            Box::new(self.clone())
+        }
+    }
+
+    fn supervised(supervision: Supervision) -> (TicketStoreClient, ServerHandle, Arc<AtomicBool>) {
+        let armed = Arc::new(AtomicBool::new(false));
+        let store = TicketStore::with_id_strategy(Tripwire {
+            armed: Arc::clone(&armed),
+            ids: Sequential::default(),
+        });
+        let mut config = Config::new(5);
+        config.supervision = supervision;
+        let server = ServerHandle::spawn(store, config);
+        let client = TicketStoreClient::new(vec![server.endpoint()]);
+        (client, server, armed)
+    }
+
+    #[test]
+    fn the_server_survives_a_panicking_command() {
+        let (client, server, armed) = supervised(Supervision::default());
+        let id = client.insert(ticket_draft!()).unwrap();
+
+        armed.store(true, Ordering::SeqCst);
+        assert_eq!(client.insert(ticket_draft!()), Err(ClientError::Crashed));
+        armed.store(false, Ordering::SeqCst);
+
+        assert!(client.get(id).unwrap().is_some());
+        let next = client.insert(ticket_draft!()).unwrap();
+        assert_eq!(next.value(), id.value() + 1);
+        assert_eq!(server.stats().restarts, 1);
+        assert_eq!(server.shutdown().len(), 2);
+    }
+
+    /// Records the level and correlation id of every event, if it has one.
+    #[derive(Clone, Default)]
+    struct Events(Arc<Mutex<Vec<(Level, String)>>>);
+
+    struct CorrelationVisitor(Option<String>);
+
+    impl Visit for CorrelationVisitor {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "correlation_id" {
+                self.0 = Some(value.to_string());
+            }
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    impl<S: tracing::Subscriber> Layer<S> for Events {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let mut visitor = CorrelationVisitor(None);
+            event.record(&mut visitor);
+            let level = *event.metadata().level();
+            let correlation_id = visitor.0.unwrap_or_default();
+            self.0.lock().unwrap().push((level, correlation_id));
+        }
+    }
+
+    #[test]
+    fn crashes_are_logged_with_the_correlation_id() {
+        let events = Events::default();
+        let store = TicketStore::with_id_strategy(Tripwire {
+            armed: Arc::new(AtomicBool::new(true)),
+            ids: Sequential::default(),
+        });
+        let mut server = Server::new(
+            store,
+            Arc::default(),
+            Arc::default(),
+            None,
+            Supervision::default(),
+            ThreadPool::new(1, 0),
+        );
+        let (response_channel, response) = sync_channel(1);
+        let command = Command::Insert {
+            draft: ticket_draft!(),
+            trace: Trace::new(ClientId::anonymous(), CorrelationId::new("request-7")),
+            response_channel,
+        };
+        let subscriber = tracing_subscriber::registry().with(events.clone());
+        tracing::subscriber::with_default(subscriber, || server.process(command));
+
+        assert_eq!(response.recv().unwrap(), Err(ClientError::Crashed));
+        let events = events.0.lock().unwrap();
+        assert_eq!(*events, [(Level::WARN, "request-7".to_string())]);
+    }
+
+    #[test]
+    fn replicas_never_see_the_writes_of_a_crashed_command() {
+        let armed = Arc::new(AtomicBool::new(false));
+        let store = TicketStore::with_id_strategy(Tripwire {
+            armed: Arc::clone(&armed),
+            ids: Sequential::default(),
+        });
+        let replication = Arc::new(Replication::new(&store, 1, 16));
+        let mut server = Server::new(
+            store,
+            Arc::default(),
+            Arc::default(),
+            Some(Arc::clone(&replication)),
+            Supervision::default(),
+            ThreadPool::new(1, 0),
+        );
+        let insert = || {
+            let (response_channel, response) = sync_channel(1);
+            let command = Command::Insert {
+                draft: ticket_draft!(),
+                trace: Trace::new(ClientId::anonymous(), CorrelationId::generate()),
+                response_channel,
+            };
+            (command, response)
+        };
+
+        // A write published halfway through a command that then crashes.
+        let id = server.store.add_ticket(ticket_draft!());
+        server.publish(&[TicketEvent::Created(server.store.get(id).unwrap().clone())]);
+        let (command, response) = insert();
+        armed.store(true, Ordering::SeqCst);
+        server.process(command);
+        assert_eq!(response.recv().unwrap(), Err(ClientError::Crashed));
+        assert_eq!(replication.head(), 0);
+
+        armed.store(false, Ordering::SeqCst);
+        let (command, response) = insert();
+        server.process(command);
+        assert!(response.recv().unwrap().is_ok());
+        assert_eq!(replication.head(), 1);
+    }
+
+    #[test]
+    fn the_server_escalates_after_too_many_restarts() {
+        let crashes = Arc::new(Mutex::new(Vec::new()));
+        let supervision = Supervision::new(1).on_escalation({
+            let crashes = Arc::clone(&crashes);
+            move |crash: &crate::supervision::Crash| crashes.lock().unwrap().push(crash.clone())
+        });
+        let (client, server, armed) = supervised(supervision);
+
+        armed.store(true, Ordering::SeqCst);
+        assert_eq!(client.insert(ticket_draft!()), Err(ClientError::Crashed));
+        assert!(crashes.lock().unwrap().is_empty());
+        assert_eq!(client.insert(ticket_draft!()), Err(ClientError::Crashed));
+        // The server thread is gone.
+        let joined = std::panic::catch_unwind(AssertUnwindSafe(|| server.shutdown()));
+        assert!(joined.is_err());
+
+        let crashes = crashes.lock().unwrap();
+        assert_eq!(crashes.len(), 1);
+        assert_eq!(crashes[0].command, CommandKind::Insert);
+        assert_eq!(crashes[0].message, "Tripwire hit");
+        assert_eq!(crashes[0].restarts, 1);
+    }
+
+    #[test]
+    fn commands_queued_past_the_deadline_are_dropped() {
+        let (sender, receiver) = sync_channel(5);
+        let mut responses = Vec::new();
+        for _ in 0..3 {
+            let (response_channel, response) = sync_channel(1);
+            let draft = TicketDraft {
+                title: ticket_title(),
+                description: ticket_description(),
+            };
+            sender
+                .send(Command::Insert {
+                    draft,
+                    trace: Trace::new(ClientId::anonymous(), CorrelationId::generate()),
+                    response_channel,
+                })
+                .unwrap();
+            responses.push(response);
+        }
+        sender.send(Command::Shutdown).unwrap();
+
+        let report = Server::new(
+            TicketStore::new(),
+            Arc::default(),
+            Arc::default(),
+            None,
+            Supervision::default(),
+            ThreadPool::new(1, 0),
+        )
+        .drain(&receiver, Some(Instant::now()));
+        assert_eq!(report.dropped_commands, 3);
+        assert!(report.store.is_empty());
+        for response in responses {
+            assert!(response.recv().is_err());
+        }
+    }
+
+    #[test]
+    fn the_server_enforces_the_rate_of_clients_that_dont_share_its_limits() {
+        let mut config = Config::new(4);
+        config.limits = crate::limits::Limits::new(crate::limits::ClientLimits {
+            rate: Some(crate::limits::RateLimit {
+                burst: 1,
+                per_second: 0.01,
+            }),
+            quota: None,
+        });
+        let server = ServerHandle::spawn(TicketStore::new(), config);
+        let client = TicketStoreClient::from_sender(server.sender.clone());
+
+        let id = client.insert(ticket_draft!()).unwrap();
+        let error = client.get(id).unwrap_err();
+        assert!(
+            matches!(error, ClientError::RateLimited { .. }),
+            "{error:?}"
+        );
+        let usage = server.usage();
+        assert_eq!((usage[0].commands, usage[0].rate_limited), (1, 1));
+        drop(client);
+        server.shutdown();
+    }
+}
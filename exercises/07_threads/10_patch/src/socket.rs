@@ -0,0 +1,689 @@
+//! Serve a ticket store to other processes over a Unix domain socket.
+//!
+//! A [`SocketServer`] accepts connections on a socket file and forwards the calls it
+//! receives to the store through a [`TicketStoreClient`], so remote calls go through the
+//! same queue, backpressure policy, rate limits and tracing as local ones.
+//! A [`SocketClient`] has the same methods as a [`TicketStoreClient`], except for those
+//! that pick where reads go, see [`SocketClient`].
+//!
+//! ```no_run
+//! use patch::launch;
+//! use patch::socket::{SocketClient, SocketServer};
+//! # use ticket_fields::test_helpers::{ticket_description, ticket_title};
+//! # let draft = patch::data::TicketDraft { title: ticket_title(), description: ticket_description() };
+//!
+//! // In the long-running process:
+//! let (client, _server) = launch(64);
+//! let _socket = SocketServer::bind("/run/tickets.sock", client)?;
+//!
+//! // In the CLI:
+//! let client = SocketClient::connect("/run/tickets.sock")?.with_identity("cli");
+//! let id = client.insert(draft)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Calls and their responses are sent as length-prefixed binary frames, tagged with
+//! [`PROTOCOL_VERSION`]: a client and a server with different versions refuse to talk
+//! to each other with [`ProtocolError::UnsupportedVersion`].
+//!
+//! Clients pick their own [`identity`](SocketClient::with_identity): use the permissions
+//! of the socket file to control who can connect.
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::backpressure::Backpressure;
+use crate::batch::{Operation, OperationOutcome};
+use crate::cancel::CancelHandle;
+use crate::client::{ClientError, TicketStoreClient, SUBSCRIPTION_BUFFER};
+use crate::csv_io::Column;
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::events::{EventFilter, Notification, Subscription, SubscriptionError};
+use crate::limits::ClientId;
+use crate::store::TicketId;
+use crate::trace::CorrelationId;
+use crate::wire::{encode_frame, read_frame, Call, FrameError, Request, Response};
+
+pub use crate::wire::{ProtocolError, MAX_FRAME_LEN, MAX_REQUEST_LEN, PROTOCOL_VERSION};
+
+/// How often a connection streaming events checks whether the server is shutting down.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(50);
+
+/// The most events a remote subscription buffers on the server's side:
+/// asking for more gets this many.
+pub const MAX_SUBSCRIPTION_BUFFER: usize = 64 * 1024;
+
+/// The most connections a [`SocketServer`] keeps open at once, subscriptions included.
+/// The connections past that are answered with [`ClientError::Overloaded`] and closed.
+pub const MAX_CONNECTIONS: usize = 64;
+
+/// Accepts connections on a socket file, each served by a thread of its own,
+/// up to [`MAX_CONNECTIONS`] at once.
+///
+/// Shutting it down, or dropping it, closes every connection and removes the socket file.
+/// The store itself keeps running.
+pub struct SocketServer {
+    path: PathBuf,
+    shared: Arc<Shared>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    stopping: AtomicBool,
+    next_connection: AtomicU64,
+    /// The open connections, so that shutting down can close them.
+    connections: Mutex<HashMap<u64, Connection>>,
+}
+
+struct Connection {
+    stream: UnixStream,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SocketServer {
+    /// Listen on `path`, forwarding calls to the store `client` talks to.
+    ///
+    /// Fails if a file already exists at `path`, e.g. left behind by a server that crashed.
+    pub fn bind(path: impl AsRef<Path>, client: TicketStoreClient) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let listener = UnixListener::bind(&path)?;
+        let shared = Arc::new(Shared {
+            stopping: AtomicBool::new(false),
+            next_connection: AtomicU64::new(0),
+            connections: Mutex::new(HashMap::new()),
+        });
+        let acceptor = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || accept(listener, client, shared))
+        };
+        Ok(Self {
+            path,
+            shared,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stop accepting connections, close the open ones, and wait for their threads to exit.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        let Some(acceptor) = self.acceptor.take() else {
+            return;
+        };
+        self.shared.stopping.store(true, Ordering::SeqCst);
+        // Wake the acceptor up, so that it notices.
+        let _ = UnixStream::connect(&self.path);
+        let _ = acceptor.join();
+        let connections: Vec<_> = self
+            .shared
+            .connections
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, connection)| connection)
+            .collect();
+        for mut connection in connections {
+            let _ = connection.stream.shutdown(Shutdown::Both);
+            if let Some(thread) = connection.thread.take() {
+                let _ = thread.join();
+            }
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Drop for SocketServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn accept(listener: UnixListener, client: TicketStoreClient, shared: Arc<Shared>) {
+    for stream in listener.incoming() {
+        if shared.stopping.load(Ordering::SeqCst) {
+            return;
+        }
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        let mut connections = shared.connections.lock().unwrap();
+        // Forget the connections that were closed by their client.
+        connections.retain(|_, connection| {
+            !connection
+                .thread
+                .as_ref()
+                .is_some_and(JoinHandle::is_finished)
+        });
+        if connections.len() >= MAX_CONNECTIONS {
+            drop(connections);
+            let overloaded = ClientError::Overloaded(Backpressure::FailFast);
+            let _ = respond(&mut stream, Response::Failed(overloaded));
+            let _ = stream.shutdown(Shutdown::Both);
+            continue;
+        }
+        let Ok(handle) = stream.try_clone() else {
+            continue;
+        };
+        let id = shared.next_connection.fetch_add(1, Ordering::Relaxed);
+        let thread = {
+            let client = client.clone();
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                serve(&mut stream, client, &shared);
+                // `Shared` holds a clone of the stream: closing ours doesn't hang up.
+                let _ = stream.shutdown(Shutdown::Both);
+            })
+        };
+        connections.insert(
+            id,
+            Connection {
+                stream: handle,
+                thread: Some(thread),
+            },
+        );
+    }
+}
+
+/// Answer the calls sent on `stream`, one at a time, until the client hangs up.
+fn serve(stream: &mut UnixStream, client: TicketStoreClient, shared: &Shared) {
+    loop {
+        let request: Request = match read_frame(stream) {
+            Ok(request) => request,
+            // We can't tell where the next frame starts: give up on the connection.
+            Err(FrameError::Protocol(error)) => {
+                let _ = respond(stream, Response::Failed(error.into()));
+                return;
+            }
+            Err(FrameError::Io(_)) => return,
+        };
+        let mut client = client.clone().with_identity(request.client);
+        if let Some(correlation_id) = request.correlation_id {
+            client = client.with_correlation_id(correlation_id);
+        }
+        if let Some(timeout) = request.timeout {
+            client = client.with_timeout(timeout);
+        }
+        if let Some(backpressure) = request.backpressure {
+            client = client.with_backpressure(backpressure);
+        }
+        let response = match request.call {
+            Call::Insert(draft) => client.insert(draft).map(Response::Inserted),
+            Call::Get(id) => client.get(id).map(Response::Ticket),
+            Call::Update { patch, expected } => match expected {
+                None => client.update(patch),
+                Some(expected) => client.update_if_unchanged(expected, patch),
+            }
+            .map(|()| Response::Updated),
+            Call::Batch(operations) => client.batch(operations).map(Response::Batch),
+            Call::Search(query) => client.search(&query).map(Response::Tickets),
+            Call::Export(columns) => client.export_csv(&columns).map(Response::Csv),
+            Call::Subscribe { filter, buffer } => {
+                return stream_events(stream, &client, filter, buffer, shared);
+            }
+        };
+        let response = response.unwrap_or_else(Response::Failed);
+        if respond(stream, response).is_err() {
+            return;
+        }
+    }
+}
+
+/// Forward the events of a new subscription on `stream`, until either end goes away.
+fn stream_events(
+    stream: &mut UnixStream,
+    client: &TicketStoreClient,
+    filter: EventFilter,
+    buffer: u32,
+    shared: &Shared,
+) {
+    // The buffer is allocated upfront: don't let the peer pick any size it likes.
+    let buffer = (buffer as usize).clamp(1, MAX_SUBSCRIPTION_BUFFER);
+    let subscription = match client.subscribe_with_buffer(filter, buffer) {
+        Ok(subscription) => subscription,
+        Err(error) => {
+            let _ = respond(stream, Response::Failed(error));
+            return;
+        }
+    };
+    if respond(stream, Response::Subscribed).is_err() {
+        return;
+    }
+    while !shared.stopping.load(Ordering::SeqCst) {
+        let response = match subscription.recv_timeout(SHUTDOWN_POLL) {
+            Ok(None) => continue,
+            Ok(Some(event)) => Response::Event(event),
+            Err(SubscriptionError::Lagged(missed)) => Response::Lagged(missed),
+            Err(SubscriptionError::Closed) => return,
+        };
+        if respond(stream, response).is_err() {
+            return;
+        }
+    }
+}
+
+fn respond(stream: &mut UnixStream, response: Response) -> io::Result<()> {
+    // The only response that can't be encoded is one that's too large.
+    let frame = encode_frame(&response)
+        .or_else(|error| encode_frame(&Response::Failed(error.into())))
+        .expect("An error response always fits in a frame");
+    stream.write_all(&frame)
+}
+
+/// Talks to a [`SocketServer`], possibly in another process.
+///
+/// Calls are sent one at a time on a single connection, shared by the clones of the client.
+/// If the connection breaks, the call fails with [`ClientError::ServerGone`]
+/// and the next one reconnects.
+///
+/// Where reads go is up to the server process: they are served wherever its own client
+/// reads from, see [`TicketStoreClient::with_read_from`]. That client sees every write
+/// made through the socket server, so there is no `last_write` or `with_read_after`
+/// to pass writes around: remote reads already follow all of them.
+#[derive(Clone)]
+pub struct SocketClient {
+    path: Arc<Path>,
+    connection: Arc<Mutex<Option<UnixStream>>>,
+    correlation_id: Option<CorrelationId>,
+    identity: ClientId,
+    timeout: Option<Duration>,
+    /// `None` to use the server's own policy.
+    backpressure: Option<Backpressure>,
+    cancel: Option<CancelHandle>,
+}
+
+impl SocketClient {
+    /// Connect to the server listening on `path`.
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+        let stream = UnixStream::connect(path.as_ref())?;
+        Ok(Self {
+            path: path.as_ref().into(),
+            connection: Arc::new(Mutex::new(Some(stream))),
+            correlation_id: None,
+            identity: ClientId::anonymous(),
+            timeout: None,
+            backpressure: None,
+            cancel: None,
+        })
+    }
+
+    /// See [`TicketStoreClient::with_identity`].
+    pub fn with_identity(mut self, identity: impl Into<ClientId>) -> Self {
+        self.identity = identity.into();
+        self
+    }
+
+    pub fn identity(&self) -> &ClientId {
+        &self.identity
+    }
+
+    /// See [`TicketStoreClient::with_correlation_id`].
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<CorrelationId>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    pub fn correlation_id(&self) -> Option<&CorrelationId> {
+        self.correlation_id.as_ref()
+    }
+
+    /// See [`TicketStoreClient::with_timeout`]. The client also stops waiting
+    /// for the response once `timeout` has elapsed.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Use `backpressure` when the server's queue is full,
+    /// instead of the policy of the server's own client.
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.backpressure = Some(backpressure);
+        self
+    }
+
+    /// The policy set with [`with_backpressure`](Self::with_backpressure), if any.
+    pub fn backpressure(&self) -> Option<Backpressure> {
+        self.backpressure
+    }
+
+    /// See [`TicketStoreClient::with_cancel`].
+    ///
+    /// Cancelling a pending call closes its connection: the server process isn't told,
+    /// so it still runs the command, even if it hasn't started yet.
+    /// Use a [timeout](Self::with_timeout) to have the server skip commands
+    /// that wait in its queue for too long.
+    pub fn with_cancel(mut self, cancel: CancelHandle) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    pub fn cancel_handle(&self) -> Option<&CancelHandle> {
+        self.cancel.as_ref()
+    }
+
+    pub fn insert(&self, draft: TicketDraft) -> Result<TicketId, ClientError> {
+        match self.call(Call::Insert(draft))? {
+            Response::Inserted(id) => Ok(id),
+            _ => Err(ProtocolError::UnexpectedResponse.into()),
+        }
+    }
+
+    /// See [`TicketStoreClient::insert_timeout`].
+    pub fn insert_timeout(
+        &self,
+        draft: TicketDraft,
+        timeout: Duration,
+    ) -> Result<TicketId, ClientError> {
+        self.clone().with_timeout(timeout).insert(draft)
+    }
+
+    pub fn get(&self, id: TicketId) -> Result<Option<Ticket>, ClientError> {
+        match self.call(Call::Get(id))? {
+            Response::Ticket(ticket) => Ok(ticket),
+            _ => Err(ProtocolError::UnexpectedResponse.into()),
+        }
+    }
+
+    /// See [`TicketStoreClient::get_timeout`].
+    pub fn get_timeout(
+        &self,
+        id: TicketId,
+        timeout: Duration,
+    ) -> Result<Option<Ticket>, ClientError> {
+        self.clone().with_timeout(timeout).get(id)
+    }
+
+    pub fn update(&self, ticket_patch: TicketPatch) -> Result<(), ClientError> {
+        match self.call(Call::Update {
+            patch: ticket_patch,
+            expected: None,
+        })? {
+            Response::Updated => Ok(()),
+            _ => Err(ProtocolError::UnexpectedResponse.into()),
+        }
+    }
+
+    /// See [`TicketStoreClient::update_if_unchanged`].
+    pub fn update_if_unchanged(
+        &self,
+        expected: Ticket,
+        ticket_patch: TicketPatch,
+    ) -> Result<(), ClientError> {
+        match self.call(Call::Update {
+            patch: ticket_patch,
+            expected: Some(expected),
+        })? {
+            Response::Updated => Ok(()),
+            _ => Err(ProtocolError::UnexpectedResponse.into()),
+        }
+    }
+
+    /// See [`TicketStoreClient::batch`].
+    pub fn batch(&self, operations: Vec<Operation>) -> Result<Vec<OperationOutcome>, ClientError> {
+        match self.call(Call::Batch(operations))? {
+            Response::Batch(outcomes) => Ok(outcomes),
+            _ => Err(ProtocolError::UnexpectedResponse.into()),
+        }
+    }
+
+    /// See [`TicketStoreClient::search`].
+    pub fn search(&self, query: &str) -> Result<Vec<Ticket>, ClientError> {
+        match self.call(Call::Search(query.to_string()))? {
+            Response::Tickets(tickets) => Ok(tickets),
+            _ => Err(ProtocolError::UnexpectedResponse.into()),
+        }
+    }
+
+    /// See [`TicketStoreClient::export_csv`].
+    ///
+    /// [`Column::Computed`] columns can't be sent to the server: they are rejected
+    /// with [`ProtocolError::UnsupportedColumn`].
+    pub fn export_csv(&self, columns: &[Column]) -> Result<Vec<u8>, ClientError> {
+        if let Some(Column::Computed { header, .. }) = columns
+            .iter()
+            .find(|column| matches!(column, Column::Computed { .. }))
+        {
+            return Err(ProtocolError::UnsupportedColumn(header.clone()).into());
+        }
+        match self.call(Call::Export(columns.to_vec()))? {
+            Response::Csv(csv) => Ok(csv),
+            _ => Err(ProtocolError::UnexpectedResponse.into()),
+        }
+    }
+
+    /// See [`TicketStoreClient::subscribe`].
+    pub fn subscribe(&self, filter: EventFilter) -> Result<Subscription, ClientError> {
+        self.subscribe_with_buffer(filter, SUBSCRIPTION_BUFFER)
+    }
+
+    /// See [`TicketStoreClient::subscribe_with_buffer`].
+    ///
+    /// The events are streamed on a connection of their own. Dropping the subscription
+    /// closes it the next time an event comes in. The server buffers at most
+    /// [`MAX_SUBSCRIPTION_BUFFER`] of them, however large `buffer` is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is zero.
+    pub fn subscribe_with_buffer(
+        &self,
+        filter: EventFilter,
+        buffer: usize,
+    ) -> Result<Subscription, ClientError> {
+        assert!(buffer > 0, "The subscription buffer can't be empty");
+        let mut stream = UnixStream::connect(&self.path).map_err(|_| ClientError::ServerGone)?;
+        let call = Call::Subscribe {
+            filter,
+            buffer: buffer.try_into().unwrap_or(u32::MAX),
+        };
+        match exchange(&mut stream, &self.request(call)) {
+            Ok(Response::Subscribed) => {}
+            Ok(Response::Failed(error)) => return Err(error),
+            Ok(_) => return Err(ProtocolError::UnexpectedResponse.into()),
+            Err(error) => return Err(error.into()),
+        }
+        let (sender, receiver) = sync_channel(buffer);
+        thread::spawn(move || forward_events(stream, sender));
+        Ok(Subscription::new(receiver))
+    }
+
+    fn request(&self, call: Call) -> Request {
+        Request {
+            client: self.identity.clone(),
+            correlation_id: self.correlation_id.clone(),
+            timeout: self.timeout,
+            backpressure: self.backpressure,
+            call,
+        }
+    }
+
+    /// Send `call` and wait for the response, for at most the client's timeout if set,
+    /// and until it's cancelled.
+    fn call(&self, call: Call) -> Result<Response, ClientError> {
+        let request = self.request(call);
+        let mut connection = self.connection.lock().unwrap();
+        let stream = match &mut *connection {
+            Some(stream) => stream,
+            None => connection
+                .insert(UnixStream::connect(&self.path).map_err(|_| ClientError::ServerGone)?),
+        };
+        // Stop waiting for the response as soon as the call is cancelled.
+        let waiter = match &self.cancel {
+            None => None,
+            Some(cancel) => {
+                let hang_up = stream.try_clone().map_err(|_| ClientError::ServerGone)?;
+                let waiter = cancel.on_cancel(move || {
+                    let _ = hang_up.shutdown(Shutdown::Both);
+                });
+                Some(waiter.ok_or(ClientError::Cancelled)?)
+            }
+        };
+        // A zero timeout means "no timeout" to the socket.
+        let read_timeout = self
+            .timeout
+            .map(|timeout| timeout.max(Duration::from_millis(1)));
+        let outcome = stream
+            .set_read_timeout(read_timeout)
+            .map_err(FrameError::Io)
+            .and_then(|()| exchange(stream, &request));
+        drop(waiter);
+        if self.cancel.as_ref().is_some_and(CancelHandle::is_cancelled) {
+            // The connection may have been closed under our feet, even if the response
+            // made it: the clones of the client need a new one.
+            *connection = None;
+            if outcome.is_err() {
+                return Err(ClientError::Cancelled);
+            }
+        }
+        match outcome {
+            Ok(Response::Failed(error)) => Err(error),
+            Ok(response) => Ok(response),
+            Err(error) => {
+                // The response may still come in later, and be mistaken for the answer
+                // to the next call: start afresh with a new connection.
+                *connection = None;
+                Err(error.into())
+            }
+        }
+    }
+}
+
+/// Send `request` on `stream` and read the response.
+fn exchange(stream: &mut UnixStream, request: &Request) -> Result<Response, FrameError> {
+    let frame = encode_frame(request)?;
+    stream.write_all(&frame)?;
+    read_frame(stream)
+}
+
+/// Pass the events streamed by the server on to the subscription,
+/// until either the server or the subscription goes away.
+fn forward_events(mut stream: UnixStream, sender: SyncSender<Notification>) {
+    loop {
+        let notification = match read_frame(&mut stream) {
+            Ok(Response::Event(event)) => Notification::Event(event),
+            Ok(Response::Lagged(missed)) => Notification::Lagged(missed),
+            _ => return,
+        };
+        if sender.send(notification).is_err() {
+            return;
+        }
+    }
+}
+
+impl From<FrameError> for ClientError {
+    fn from(error: FrameError) -> Self {
+        match error {
+            FrameError::Io(error)
+                if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                ClientError::Timeout
+            }
+            FrameError::Io(_) => ClientError::ServerGone,
+            FrameError::Protocol(error) => ClientError::Protocol(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::TicketEvent;
+    use crate::launch;
+    use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+    #[test]
+    fn huge_subscription_buffers_are_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let (client, server) = launch(16);
+        let socket = SocketServer::bind(dir.path().join("tickets.sock"), client.clone()).unwrap();
+        let mut stream = UnixStream::connect(socket.path()).unwrap();
+        let request = Request {
+            client: ClientId::anonymous(),
+            correlation_id: None,
+            timeout: None,
+            backpressure: None,
+            call: Call::Subscribe {
+                filter: EventFilter::All,
+                buffer: u32::MAX,
+            },
+        };
+        // The server must not try to allocate that many events upfront.
+        let response = exchange(&mut stream, &request).unwrap();
+        assert!(matches!(response, Response::Subscribed), "{response:?}");
+
+        let id = client
+            .insert(TicketDraft {
+                title: ticket_title(),
+                description: ticket_description(),
+            })
+            .unwrap();
+        let event = read_frame(&mut stream).unwrap();
+        assert!(
+            matches!(&event, Response::Event(TicketEvent::Created(ticket)) if ticket.id == id),
+            "{event:?}"
+        );
+        socket.shutdown();
+        server.shutdown();
+    }
+
+    #[test]
+    fn connections_past_the_limit_are_turned_away() {
+        let dir = tempfile::tempdir().unwrap();
+        let (client, server) = launch(16);
+        let socket = SocketServer::bind(dir.path().join("tickets.sock"), client).unwrap();
+        let mut open: Vec<_> = (0..MAX_CONNECTIONS)
+            .map(|_| UnixStream::connect(socket.path()).unwrap())
+            .collect();
+        // Each of them is served by the time it answers.
+        for stream in &mut open {
+            let request = Request {
+                client: ClientId::anonymous(),
+                correlation_id: None,
+                timeout: None,
+                backpressure: None,
+                call: Call::Search(String::new()),
+            };
+            assert!(matches!(
+                exchange(stream, &request),
+                Ok(Response::Tickets(_))
+            ));
+        }
+
+        let mut extra = UnixStream::connect(socket.path()).unwrap();
+        let response = read_frame::<Response>(&mut extra).unwrap();
+        assert_eq!(
+            response,
+            Response::Failed(ClientError::Overloaded(Backpressure::FailFast))
+        );
+
+        // Once a connection closes, its slot is up for grabs.
+        drop(open.pop());
+        let remote = SocketClient::connect(socket.path()).unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while remote.search("").is_err() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "The slot was never freed"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        socket.shutdown();
+        server.shutdown();
+    }
+}
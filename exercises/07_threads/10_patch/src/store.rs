@@ -1,26 +1,31 @@
-use crate::data::{Status, Ticket, TicketDraft};
-use std::collections::BTreeMap;
+use crate::data::{Status, Ticket, TicketDraft, TicketPatch};
+use crate::id::{IdStrategy, Sequential, TicketKey, TicketRef};
+use par_reduce::ParReduce;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct TicketId(u64);
+pub use crate::id::TicketId;
 
+/// Cloning a store is cheap: the tickets live in a persistent map, whose copies share
+/// everything but the tickets changed after the clone.
 #[derive(Clone)]
 pub struct TicketStore {
-    tickets: BTreeMap<TicketId, Ticket>,
-    counter: u64,
+    tickets: im::OrdMap<TicketId, Ticket>,
+    ids: Box<dyn IdStrategy>,
 }
 
 impl TicketStore {
     pub fn new() -> Self {
+        Self::with_id_strategy(Sequential::default())
+    }
+
+    pub fn with_id_strategy(ids: impl IdStrategy + 'static) -> Self {
         Self {
-            tickets: BTreeMap::new(),
-            counter: 0,
+            tickets: im::OrdMap::new(),
+            ids: Box::new(ids),
         }
     }
 
     pub fn add_ticket(&mut self, ticket: TicketDraft) -> TicketId {
-        let id = TicketId(self.counter);
-        self.counter += 1;
+        let id = self.ids.next_id();
         let ticket = Ticket {
             id,
             title: ticket.title,
@@ -35,7 +40,134 @@ impl TicketStore {
         self.tickets.get(&id)
     }
 
+    /// The human-readable key of a ticket, if the store's id strategy assigns one.
+    pub fn key(&self, id: TicketId) -> Option<TicketKey> {
+        if !self.tickets.contains_key(&id) {
+            return None;
+        }
+        self.ids.key(id)
+    }
+
+    /// Find the id of the ticket `reference` points to, if it exists.
+    pub fn resolve(&self, reference: &TicketRef) -> Option<TicketId> {
+        let id = match reference {
+            TicketRef::Id(id) => *id,
+            TicketRef::Key(key) => self.ids.resolve(key)?,
+        };
+        self.tickets.contains_key(&id).then_some(id)
+    }
+
+    /// Look a ticket up by either its numeric id or its key.
+    pub fn lookup(&self, reference: &TicketRef) -> Option<&Ticket> {
+        self.get(self.resolve(reference)?)
+    }
+
     pub fn get_mut(&mut self, id: TicketId) -> Option<&mut Ticket> {
         self.tickets.get_mut(&id)
     }
+
+    /// Apply `patch` to the ticket it targets.
+    /// Returns `None` if there is no ticket with the patched id.
+    pub fn update(&mut self, patch: TicketPatch) -> Option<&Ticket> {
+        let ticket = self.tickets.get_mut(&patch.id)?;
+        if let Some(title) = patch.title {
+            ticket.title = title;
+        }
+        if let Some(description) = patch.description {
+            ticket.description = description;
+        }
+        if let Some(status) = patch.status {
+            ticket.status = status;
+        }
+        Some(ticket)
+    }
+
+    /// Run `f` against a working copy of the store.
+    ///
+    /// Changes are committed only if `f` returns `Ok`: on `Err` the store is left untouched,
+    /// including its id sequence.
+    pub fn transaction<T, E>(
+        &mut self,
+        f: impl FnOnce(&mut TicketStore) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut working_copy = self.clone();
+        let outcome = f(&mut working_copy)?;
+        *self = working_copy;
+        Ok(outcome)
+    }
+
+    /// Store `ticket` as is, replacing the ticket with the same id if there is one.
+    /// Used by replicas, which copy the tickets written by their primary.
+    pub(crate) fn put(&mut self, ticket: Ticket) {
+        self.tickets.insert(ticket.id, ticket);
+    }
+
+    pub fn remove(&mut self, id: TicketId) -> Option<Ticket> {
+        self.tickets.remove(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Ticket> {
+        self.tickets.values()
+    }
+
+    /// How many tickets are in each status. Large stores are counted in parallel.
+    pub fn status_counts(&self) -> StatusCounts {
+        let tickets: Vec<&Ticket> = self.iter().collect();
+        ParReduce::new().fold(
+            &tickets,
+            StatusCounts::default,
+            |counts, ticket| counts.with(ticket.status),
+            StatusCounts::merge,
+        )
+    }
+
+    /// The tickets whose title or description contains `query`, ignoring case.
+    pub fn search<'a>(&'a self, query: &str) -> impl Iterator<Item = &'a Ticket> + 'a {
+        let query = query.to_lowercase();
+        self.iter().filter(move |ticket| {
+            ticket.title.as_str().to_lowercase().contains(&query)
+                || ticket.description.as_str().to_lowercase().contains(&query)
+        })
+    }
+}
+
+/// How many tickets are in each status, see [`TicketStore::status_counts`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatusCounts {
+    pub to_do: usize,
+    pub in_progress: usize,
+    pub done: usize,
+}
+
+impl StatusCounts {
+    fn with(mut self, status: Status) -> Self {
+        match status {
+            Status::ToDo => self.to_do += 1,
+            Status::InProgress => self.in_progress += 1,
+            Status::Done => self.done += 1,
+        }
+        self
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self {
+            to_do: self.to_do + other.to_do,
+            in_progress: self.in_progress + other.in_progress,
+            done: self.done + other.done,
+        }
+    }
+}
+
+impl Default for TicketStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -0,0 +1,161 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::client::ClientError;
+use crate::metrics::CommandKind;
+use crate::server::{Command, Responder};
+use crate::store::TicketId;
+
+type EscalationHook = Arc<dyn Fn(&Crash) + Send + Sync>;
+
+/// How many times a server restarts by default before giving up.
+pub const DEFAULT_MAX_RESTARTS: u32 = 3;
+
+/// What the server does when processing a command panics.
+///
+/// The store is rolled back to how it was before the command, the caller gets
+/// [`ClientError::Crashed`] and the server moves on to the next command.
+/// After `max_restarts` crashes (over the whole lifetime of the server) it gives up:
+/// the escalation hook is called and the server thread dies with the panic.
+#[derive(Clone)]
+pub struct Supervision {
+    pub max_restarts: u32,
+    escalation: Option<EscalationHook>,
+}
+
+impl Supervision {
+    pub fn new(max_restarts: u32) -> Self {
+        Self {
+            max_restarts,
+            escalation: None,
+        }
+    }
+
+    /// Call `hook` with the crash that made the server give up, right before it dies.
+    pub fn on_escalation(mut self, hook: impl Fn(&Crash) + Send + Sync + 'static) -> Self {
+        self.escalation = Some(Arc::new(hook));
+        self
+    }
+
+    pub(crate) fn escalate(&self, crash: &Crash) {
+        if let Some(hook) = &self.escalation {
+            hook(crash);
+        }
+    }
+}
+
+impl Default for Supervision {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RESTARTS)
+    }
+}
+
+impl fmt::Debug for Supervision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Supervision")
+            .field("max_restarts", &self.max_restarts)
+            .field("escalation", &self.escalation.is_some())
+            .finish()
+    }
+}
+
+/// A command that made the server panic.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crash {
+    pub command: CommandKind,
+    /// The ticket the command was about, if any.
+    pub ticket: Option<TicketId>,
+    /// The panic message.
+    pub message: String,
+    /// How many times the server had already restarted before this crash.
+    pub restarts: u32,
+}
+
+impl fmt::Display for Crash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` command", self.command)?;
+        if let Some(ticket) = self.ticket {
+            write!(f, " for ticket {ticket}")?;
+        }
+        write!(f, " panicked: {}", self.message)
+    }
+}
+
+/// What the server needs to know about a command to report a crash,
+/// captured before the command is handed over to the handler.
+pub(crate) struct Suspect {
+    command: CommandKind,
+    ticket: Option<TicketId>,
+    report: Box<dyn FnOnce(ClientError) + Send>,
+}
+
+impl Suspect {
+    pub(crate) fn of(command: &Command) -> Option<Suspect> {
+        let (ticket, report) = match command {
+            Command::Insert {
+                response_channel, ..
+            } => (None, reporter(response_channel)),
+            Command::Get {
+                id,
+                response_channel,
+                ..
+            } => (Some(*id), reporter(response_channel)),
+            Command::Update {
+                patch,
+                response_channel,
+                ..
+            } => (Some(patch.id), reporter(response_channel)),
+            Command::Batch {
+                response_channel, ..
+            } => (None, reporter(response_channel)),
+            Command::Search {
+                response_channel, ..
+            } => (None, reporter(response_channel)),
+            Command::Export {
+                response_channel, ..
+            } => (None, reporter(response_channel)),
+            Command::Subscribe {
+                response_channel, ..
+            } => (None, reporter(response_channel)),
+            Command::Shutdown => return None,
+        };
+        Some(Suspect {
+            command: CommandKind::of(command)?,
+            ticket,
+            report,
+        })
+    }
+
+    /// Tell the caller its command crashed the server.
+    pub(crate) fn convict(self, panic: &(dyn Any + Send), restarts: u32) -> Crash {
+        (self.report)(ClientError::Crashed);
+        Crash {
+            command: self.command,
+            ticket: self.ticket,
+            message: panic_message(panic),
+            restarts,
+        }
+    }
+}
+
+fn reporter<T: Send + 'static>(
+    response_channel: &Responder<T>,
+) -> Box<dyn FnOnce(ClientError) + Send> {
+    let response_channel = response_channel.clone();
+    Box::new(move |error| {
+        // The handler may have replied before panicking, in which case the caller
+        // already has its answer.
+        let _ = response_channel.try_send(Err(error));
+    })
+}
+
+pub(crate) fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
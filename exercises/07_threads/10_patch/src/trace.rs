@@ -0,0 +1,116 @@
+//! Following a command from the client, through the queue, to the server and back.
+//!
+//! Every command carries a [`Trace`]: the [`CorrelationId`] of the call that sent it
+//! and the time it was queued. Both ends emit [`tracing`] spans tagged with the
+//! correlation id:
+//!
+//! - `ticket_store.call`, on the caller's thread, for the whole call, with an `enqueue`
+//!   span for the time spent getting the command into the queue;
+//! - `ticket_server.command`, on the server, with a `dequeue` span (recording how long
+//!   the command sat in the queue, in `queued_us`), an `execute` span, and a `reply` span.
+//!   Commands that run on the worker pool get an extra `worker` span.
+//!
+//! Comparing the `queued_us` of slow calls with the duration of their `execute` span tells
+//! whether they were waiting for the server or for the store.
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::cancel::{Abandoned, CancelHandle, CancelReply};
+use crate::limits::ClientId;
+
+/// Identifies a call across threads, e.g. the id of the HTTP request that triggered it.
+///
+/// Pass your own with [`TicketStoreClient::with_correlation_id`](crate::TicketStoreClient::with_correlation_id),
+/// otherwise every call gets a new one from [`CorrelationId::generate`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CorrelationId(Arc<str>);
+
+impl CorrelationId {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into().into())
+    }
+
+    /// An id that is unique within this process.
+    pub fn generate() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self::new(format!("call-{}", NEXT.fetch_add(1, Ordering::Relaxed)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for CorrelationId {
+    fn from(id: &str) -> Self {
+        Self::new(id)
+    }
+}
+
+impl From<String> for CorrelationId {
+    fn from(id: String) -> Self {
+        Self::new(id)
+    }
+}
+
+/// Where a command comes from, when it was queued, and when to give up on it.
+#[derive(Clone, Debug)]
+pub struct Trace {
+    /// The client that sent the command, whose limits apply to it.
+    pub client: ClientId,
+    pub correlation_id: CorrelationId,
+    /// When the client started queueing the command.
+    /// Includes the time spent waiting for room in a full queue.
+    pub enqueued_at: Instant,
+    /// The server skips the command if it's still queued by then, see [`cancel`](crate::cancel).
+    pub deadline: Option<Instant>,
+    pub cancel: Option<CancelHandle>,
+    /// Whether the client already took a token for the command from the server's
+    /// rate limiter, see [`limits`](crate::limits). Otherwise the server does.
+    pub(crate) throttled: bool,
+    /// Wakes the caller up if the command gets cancelled. Must not be dropped before
+    /// the command's response channel, see [`CancelReply`].
+    pub(crate) cancel_reply: Option<CancelReply>,
+}
+
+impl Trace {
+    /// A command queued right now.
+    pub fn new(client: ClientId, correlation_id: CorrelationId) -> Self {
+        Self {
+            client,
+            correlation_id,
+            enqueued_at: Instant::now(),
+            deadline: None,
+            cancel: None,
+            throttled: false,
+            cancel_reply: None,
+        }
+    }
+
+    /// How long ago the command was queued.
+    pub fn age(&self) -> Duration {
+        self.enqueued_at.elapsed()
+    }
+
+    /// Whether the caller has given up on the command: it's no use running it anymore.
+    pub fn abandoned(&self) -> Option<Abandoned> {
+        if self.cancel.as_ref().is_some_and(CancelHandle::is_cancelled) {
+            Some(Abandoned::Cancelled)
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Some(Abandoned::Expired)
+        } else {
+            None
+        }
+    }
+}
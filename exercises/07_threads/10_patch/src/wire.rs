@@ -0,0 +1,1044 @@
+//! The binary encoding of the messages exchanged over a [`socket`](crate::socket).
+//!
+//! Every message travels in a frame: the length of the frame, as a big-endian `u32`,
+//! followed by that many bytes. The first byte is the [`PROTOCOL_VERSION`] of the peer
+//! that wrote the frame, the rest is the message.
+//!
+//! Integers are big-endian. Enums start with a one-byte tag. Strings and sequences start
+//! with their length as a `u32`, optional values with a `0` (none) or `1` (some) byte.
+use std::io::{self, ErrorKind, Read};
+use std::time::Duration;
+
+use ticket_fields::{TicketDescription, TicketTitle};
+
+use crate::backpressure::Backpressure;
+use crate::batch::{BatchError, Operation, OperationError, OperationOutcome};
+use crate::client::{ClientError, ValidationError};
+use crate::csv_io::Column;
+use crate::data::{Status, Ticket, TicketDraft, TicketPatch};
+use crate::events::{EventFilter, TicketEvent};
+use crate::limits::ClientId;
+use crate::store::TicketId;
+use crate::trace::CorrelationId;
+
+/// Bumped whenever the encoding changes. Peers only talk to peers with the same version.
+pub const PROTOCOL_VERSION: u8 = 3;
+
+/// The largest frame a peer accepts, in bytes.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// The largest request a server accepts, in bytes. Requests come from peers the server
+/// has no reason to trust, so they are kept much smaller than responses:
+/// a batch creating a thousand tickets still fits.
+pub const MAX_REQUEST_LEN: usize = 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum ProtocolError {
+    #[error(
+        "The peer speaks version {0} of the protocol, but we speak version {}",
+        PROTOCOL_VERSION
+    )]
+    UnsupportedVersion(u8),
+    #[error("The frame is {0} bytes long, more than the peer accepts")]
+    FrameTooLarge(u64),
+    #[error("The message is malformed: {0}")]
+    Malformed(String),
+    #[error("The `{0}` column is computed locally, it can't be sent to another process")]
+    UnsupportedColumn(String),
+    #[error("The peer answered with a message that doesn't match the request")]
+    UnexpectedResponse,
+}
+
+/// What a client asks for, on behalf of whom, and how.
+#[derive(Debug)]
+pub(crate) struct Request {
+    pub(crate) client: ClientId,
+    /// `None` to let the server generate one.
+    pub(crate) correlation_id: Option<CorrelationId>,
+    /// See [`TicketStoreClient::with_timeout`](crate::TicketStoreClient::with_timeout).
+    pub(crate) timeout: Option<Duration>,
+    /// `None` to use the server's own policy.
+    pub(crate) backpressure: Option<Backpressure>,
+    pub(crate) call: Call,
+}
+
+/// One per method of [`TicketStoreClient`](crate::TicketStoreClient).
+#[derive(Debug)]
+pub(crate) enum Call {
+    Insert(TicketDraft),
+    Get(TicketId),
+    Update {
+        patch: TicketPatch,
+        expected: Option<Ticket>,
+    },
+    Batch(Vec<Operation>),
+    Search(String),
+    Export(Vec<Column>),
+    /// Turns the connection into a stream of [`Response::Event`]s and [`Response::Lagged`]s.
+    Subscribe {
+        filter: EventFilter,
+        buffer: u32,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Response {
+    Inserted(TicketId),
+    Ticket(Option<Ticket>),
+    Updated,
+    Batch(Vec<OperationOutcome>),
+    Tickets(Vec<Ticket>),
+    Csv(Vec<u8>),
+    Subscribed,
+    Event(TicketEvent),
+    Lagged(u64),
+    Failed(ClientError),
+}
+
+#[derive(Debug)]
+pub(crate) enum FrameError {
+    Io(io::Error),
+    Protocol(ProtocolError),
+}
+
+impl From<io::Error> for FrameError {
+    fn from(error: io::Error) -> Self {
+        FrameError::Io(error)
+    }
+}
+
+impl From<ProtocolError> for FrameError {
+    fn from(error: ProtocolError) -> Self {
+        FrameError::Protocol(error)
+    }
+}
+
+/// `message`, framed and ready to be written.
+pub(crate) fn encode_frame<T: Wire>(message: &T) -> Result<Vec<u8>, ProtocolError> {
+    let mut frame = vec![0; 4];
+    frame.push(PROTOCOL_VERSION);
+    message.encode(&mut frame);
+    let len = frame.len() - 4;
+    if len > T::MAX_LEN {
+        return Err(ProtocolError::FrameTooLarge(len as u64));
+    }
+    frame[..4].copy_from_slice(&(len as u32).to_be_bytes());
+    Ok(frame)
+}
+
+/// Block until a whole frame has been read, then decode it.
+///
+/// The frame is read as it comes in rather than allocated upfront:
+/// a peer can't make us reserve more memory than it actually sends.
+pub(crate) fn read_frame<T: Wire>(reader: &mut impl Read) -> Result<T, FrameError> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len as usize > T::MAX_LEN {
+        return Err(ProtocolError::FrameTooLarge(len.into()).into());
+    }
+    let mut frame = Vec::new();
+    reader.take(len.into()).read_to_end(&mut frame)?;
+    if frame.len() < len as usize {
+        return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+    }
+    Ok(decode_frame(&frame)?)
+}
+
+/// Decode a frame, without its length.
+pub(crate) fn decode_frame<T: Wire>(frame: &[u8]) -> Result<T, ProtocolError> {
+    let mut input = Input(frame);
+    let version = input.u8()?;
+    if version != PROTOCOL_VERSION {
+        return Err(ProtocolError::UnsupportedVersion(version));
+    }
+    let message = T::decode(&mut input)?;
+    if !input.0.is_empty() {
+        return Err(malformed(format!("{} trailing bytes", input.0.len())));
+    }
+    Ok(message)
+}
+
+pub(crate) trait Wire: Sized {
+    /// The largest frame that may hold a message of this type, in bytes.
+    const MAX_LEN: usize = MAX_FRAME_LEN;
+
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError>;
+}
+
+/// The part of a frame that hasn't been decoded yet.
+pub(crate) struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ProtocolError> {
+        if self.0.len() < n {
+            return Err(malformed("the message is truncated"));
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ProtocolError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ProtocolError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn prefix_len(&mut self) -> Result<usize, ProtocolError> {
+        let len = self.u32()? as usize;
+        // Every element takes at least a byte: don't trust lengths we can't back up.
+        if len > self.0.len() {
+            return Err(malformed("the message is truncated"));
+        }
+        Ok(len)
+    }
+}
+
+fn malformed(reason: impl Into<String>) -> ProtocolError {
+    ProtocolError::Malformed(reason.into())
+}
+
+fn unknown_tag(what: &str, tag: u8) -> ProtocolError {
+    malformed(format!("unknown {what} tag {tag}"))
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_len(out: &mut Vec<u8>, len: usize) {
+    // Frames are capped well below `u32::MAX`, so are their contents.
+    put_u32(out, len as u32);
+}
+
+impl Wire for u8 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        input.u8()
+    }
+}
+
+impl Wire for u32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_u32(out, *self);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        input.u32()
+    }
+}
+
+impl Wire for u64 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_u64(out, *self);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        input.u64()
+    }
+}
+
+impl Wire for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_len(out, self.len());
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        let len = input.prefix_len()?;
+        let bytes = input.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| malformed("a string isn't valid UTF-8"))
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_len(out, self.len());
+        for item in self {
+            item.encode(out);
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        let len = input.prefix_len()?;
+        (0..len).map(|_| T::decode(input)).collect()
+    }
+}
+
+impl<T: Wire> Wire for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Ok(None),
+            1 => T::decode(input).map(Some),
+            tag => Err(unknown_tag("option", tag)),
+        }
+    }
+}
+
+impl<T: Wire, E: Wire> Wire for Result<T, E> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Ok(value) => {
+                out.push(0);
+                value.encode(out);
+            }
+            Err(error) => {
+                out.push(1);
+                error.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => T::decode(input).map(Ok),
+            1 => E::decode(input).map(Err),
+            tag => Err(unknown_tag("result", tag)),
+        }
+    }
+}
+
+impl Wire for Duration {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_u64(out, self.as_secs());
+        put_u32(out, self.subsec_nanos());
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        let secs = input.u64()?;
+        let nanos = input.u32()?;
+        if nanos >= 1_000_000_000 {
+            return Err(malformed(
+                "a duration has more than a second worth of nanoseconds",
+            ));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+impl Wire for TicketId {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_u64(out, self.value());
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        input.u64().map(TicketId::from_value)
+    }
+}
+
+impl Wire for ClientId {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_str().to_string().encode(out);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        String::decode(input).map(ClientId::new)
+    }
+}
+
+impl Wire for CorrelationId {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_str().to_string().encode(out);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        String::decode(input).map(CorrelationId::new)
+    }
+}
+
+impl Wire for TicketTitle {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_str().to_string().encode(out);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        TicketTitle::try_from(String::decode(input)?).map_err(|e| malformed(e.to_string()))
+    }
+}
+
+impl Wire for TicketDescription {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_str().to_string().encode(out);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        TicketDescription::try_from(String::decode(input)?).map_err(|e| malformed(e.to_string()))
+    }
+}
+
+impl Wire for Status {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(match self {
+            Status::ToDo => 0,
+            Status::InProgress => 1,
+            Status::Done => 2,
+        });
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Ok(Status::ToDo),
+            1 => Ok(Status::InProgress),
+            2 => Ok(Status::Done),
+            tag => Err(unknown_tag("status", tag)),
+        }
+    }
+}
+
+impl Wire for Ticket {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.id.encode(out);
+        self.title.encode(out);
+        self.description.encode(out);
+        self.status.encode(out);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        Ok(Ticket {
+            id: Wire::decode(input)?,
+            title: Wire::decode(input)?,
+            description: Wire::decode(input)?,
+            status: Wire::decode(input)?,
+        })
+    }
+}
+
+impl Wire for TicketDraft {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.title.encode(out);
+        self.description.encode(out);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        Ok(TicketDraft {
+            title: Wire::decode(input)?,
+            description: Wire::decode(input)?,
+        })
+    }
+}
+
+impl Wire for TicketPatch {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.id.encode(out);
+        self.title.encode(out);
+        self.description.encode(out);
+        self.status.encode(out);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        Ok(TicketPatch {
+            id: Wire::decode(input)?,
+            title: Wire::decode(input)?,
+            description: Wire::decode(input)?,
+            status: Wire::decode(input)?,
+        })
+    }
+}
+
+impl Wire for Operation {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Operation::Insert(draft) => {
+                out.push(0);
+                draft.encode(out);
+            }
+            Operation::Update(patch) => {
+                out.push(1);
+                patch.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Wire::decode(input).map(Operation::Insert),
+            1 => Wire::decode(input).map(Operation::Update),
+            tag => Err(unknown_tag("operation", tag)),
+        }
+    }
+}
+
+impl Wire for OperationOutcome {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let (tag, id) = match self {
+            OperationOutcome::Inserted(id) => (0, id),
+            OperationOutcome::Updated(id) => (1, id),
+        };
+        out.push(tag);
+        id.encode(out);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Wire::decode(input).map(OperationOutcome::Inserted),
+            1 => Wire::decode(input).map(OperationOutcome::Updated),
+            tag => Err(unknown_tag("operation outcome", tag)),
+        }
+    }
+}
+
+impl Wire for OperationError {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            OperationError::NotFound(id) => {
+                out.push(0);
+                id.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Wire::decode(input).map(OperationError::NotFound),
+            tag => Err(unknown_tag("operation error", tag)),
+        }
+    }
+}
+
+impl Wire for Backpressure {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Backpressure::FailFast => out.push(0),
+            Backpressure::Block => out.push(1),
+            Backpressure::BlockFor(timeout) => {
+                out.push(2);
+                timeout.encode(out);
+            }
+            Backpressure::Retry {
+                max_attempts,
+                initial_backoff,
+                max_backoff,
+            } => {
+                out.push(3);
+                max_attempts.encode(out);
+                initial_backoff.encode(out);
+                max_backoff.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Ok(Backpressure::FailFast),
+            1 => Ok(Backpressure::Block),
+            2 => Wire::decode(input).map(Backpressure::BlockFor),
+            3 => Ok(Backpressure::Retry {
+                max_attempts: Wire::decode(input)?,
+                initial_backoff: Wire::decode(input)?,
+                max_backoff: Wire::decode(input)?,
+            }),
+            tag => Err(unknown_tag("backpressure", tag)),
+        }
+    }
+}
+
+impl Wire for ValidationError {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            ValidationError::EmptyPatch(id) => {
+                out.push(0);
+                id.encode(out);
+            }
+            ValidationError::Batch(error) => {
+                out.push(1);
+                error.results.encode(out);
+            }
+            ValidationError::CrossShardBatch => out.push(2),
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Wire::decode(input).map(ValidationError::EmptyPatch),
+            1 => Ok(ValidationError::Batch(BatchError {
+                results: Wire::decode(input)?,
+            })),
+            2 => Ok(ValidationError::CrossShardBatch),
+            tag => Err(unknown_tag("validation error", tag)),
+        }
+    }
+}
+
+impl Wire for ProtocolError {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            ProtocolError::UnsupportedVersion(version) => {
+                out.push(0);
+                version.encode(out);
+            }
+            ProtocolError::FrameTooLarge(len) => {
+                out.push(1);
+                len.encode(out);
+            }
+            ProtocolError::Malformed(reason) => {
+                out.push(2);
+                reason.encode(out);
+            }
+            ProtocolError::UnsupportedColumn(header) => {
+                out.push(3);
+                header.encode(out);
+            }
+            ProtocolError::UnexpectedResponse => out.push(4),
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Wire::decode(input).map(ProtocolError::UnsupportedVersion),
+            1 => Wire::decode(input).map(ProtocolError::FrameTooLarge),
+            2 => Wire::decode(input).map(ProtocolError::Malformed),
+            3 => Wire::decode(input).map(ProtocolError::UnsupportedColumn),
+            4 => Ok(ProtocolError::UnexpectedResponse),
+            tag => Err(unknown_tag("protocol error", tag)),
+        }
+    }
+}
+
+impl Wire for ClientError {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            ClientError::Overloaded(backpressure) => {
+                out.push(0);
+                backpressure.encode(out);
+            }
+            ClientError::Timeout => out.push(1),
+            ClientError::NotFound(id) => {
+                out.push(2);
+                id.encode(out);
+            }
+            ClientError::ServerGone => out.push(3),
+            ClientError::Validation(error) => {
+                out.push(4);
+                error.encode(out);
+            }
+            ClientError::Conflict(id) => {
+                out.push(5);
+                id.encode(out);
+            }
+            ClientError::Crashed => out.push(6),
+            ClientError::RateLimited { retry_after } => {
+                out.push(7);
+                retry_after.encode(out);
+            }
+            ClientError::Protocol(error) => {
+                out.push(8);
+                error.encode(out);
+            }
+            ClientError::Cancelled => out.push(9),
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Wire::decode(input).map(ClientError::Overloaded),
+            1 => Ok(ClientError::Timeout),
+            2 => Wire::decode(input).map(ClientError::NotFound),
+            3 => Ok(ClientError::ServerGone),
+            4 => Wire::decode(input).map(ClientError::Validation),
+            5 => Wire::decode(input).map(ClientError::Conflict),
+            6 => Ok(ClientError::Crashed),
+            7 => Ok(ClientError::RateLimited {
+                retry_after: Wire::decode(input)?,
+            }),
+            8 => Wire::decode(input).map(ClientError::Protocol),
+            9 => Ok(ClientError::Cancelled),
+            tag => Err(unknown_tag("client error", tag)),
+        }
+    }
+}
+
+impl Wire for Column {
+    /// # Panics
+    ///
+    /// Panics on [`Column::Computed`]: callers check for them beforehand,
+    /// see [`ProtocolError::UnsupportedColumn`].
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(match self {
+            Column::Id => 0,
+            Column::Key => 1,
+            Column::Title => 2,
+            Column::Description => 3,
+            Column::Status => 4,
+            Column::Computed { header, .. } => {
+                panic!("The computed column `{header}` can't be encoded")
+            }
+        });
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Ok(Column::Id),
+            1 => Ok(Column::Key),
+            2 => Ok(Column::Title),
+            3 => Ok(Column::Description),
+            4 => Ok(Column::Status),
+            tag => Err(unknown_tag("column", tag)),
+        }
+    }
+}
+
+impl Wire for EventFilter {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            EventFilter::All => out.push(0),
+            EventFilter::Ticket(id) => {
+                out.push(1);
+                id.encode(out);
+            }
+            EventFilter::Status(status) => {
+                out.push(2);
+                status.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Ok(EventFilter::All),
+            1 => Wire::decode(input).map(EventFilter::Ticket),
+            2 => Wire::decode(input).map(EventFilter::Status),
+            tag => Err(unknown_tag("event filter", tag)),
+        }
+    }
+}
+
+impl Wire for TicketEvent {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            TicketEvent::Created(ticket) => {
+                out.push(0);
+                ticket.encode(out);
+            }
+            TicketEvent::Updated {
+                id,
+                title,
+                description,
+            } => {
+                out.push(1);
+                id.encode(out);
+                title.encode(out);
+                description.encode(out);
+            }
+            TicketEvent::StatusChanged { id, from, to } => {
+                out.push(2);
+                id.encode(out);
+                from.encode(out);
+                to.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Wire::decode(input).map(TicketEvent::Created),
+            1 => Ok(TicketEvent::Updated {
+                id: Wire::decode(input)?,
+                title: Wire::decode(input)?,
+                description: Wire::decode(input)?,
+            }),
+            2 => Ok(TicketEvent::StatusChanged {
+                id: Wire::decode(input)?,
+                from: Wire::decode(input)?,
+                to: Wire::decode(input)?,
+            }),
+            tag => Err(unknown_tag("event", tag)),
+        }
+    }
+}
+
+impl Wire for Request {
+    const MAX_LEN: usize = MAX_REQUEST_LEN;
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.client.encode(out);
+        self.correlation_id.encode(out);
+        self.timeout.encode(out);
+        self.backpressure.encode(out);
+        self.call.encode(out);
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        Ok(Request {
+            client: Wire::decode(input)?,
+            correlation_id: Wire::decode(input)?,
+            timeout: Wire::decode(input)?,
+            backpressure: Wire::decode(input)?,
+            call: Wire::decode(input)?,
+        })
+    }
+}
+
+impl Wire for Call {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Call::Insert(draft) => {
+                out.push(0);
+                draft.encode(out);
+            }
+            Call::Get(id) => {
+                out.push(1);
+                id.encode(out);
+            }
+            Call::Update { patch, expected } => {
+                out.push(2);
+                patch.encode(out);
+                expected.encode(out);
+            }
+            Call::Batch(operations) => {
+                out.push(3);
+                operations.encode(out);
+            }
+            Call::Search(query) => {
+                out.push(4);
+                query.encode(out);
+            }
+            Call::Export(columns) => {
+                out.push(5);
+                columns.encode(out);
+            }
+            Call::Subscribe { filter, buffer } => {
+                out.push(6);
+                filter.encode(out);
+                buffer.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Wire::decode(input).map(Call::Insert),
+            1 => Wire::decode(input).map(Call::Get),
+            2 => Ok(Call::Update {
+                patch: Wire::decode(input)?,
+                expected: Wire::decode(input)?,
+            }),
+            3 => Wire::decode(input).map(Call::Batch),
+            4 => Wire::decode(input).map(Call::Search),
+            5 => Wire::decode(input).map(Call::Export),
+            6 => Ok(Call::Subscribe {
+                filter: Wire::decode(input)?,
+                buffer: Wire::decode(input)?,
+            }),
+            tag => Err(unknown_tag("call", tag)),
+        }
+    }
+}
+
+impl Wire for Response {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Response::Inserted(id) => {
+                out.push(0);
+                id.encode(out);
+            }
+            Response::Ticket(ticket) => {
+                out.push(1);
+                ticket.encode(out);
+            }
+            Response::Updated => out.push(2),
+            Response::Batch(outcomes) => {
+                out.push(3);
+                outcomes.encode(out);
+            }
+            Response::Tickets(tickets) => {
+                out.push(4);
+                tickets.encode(out);
+            }
+            Response::Csv(csv) => {
+                out.push(5);
+                csv.encode(out);
+            }
+            Response::Subscribed => out.push(6),
+            Response::Event(event) => {
+                out.push(7);
+                event.encode(out);
+            }
+            Response::Lagged(missed) => {
+                out.push(8);
+                missed.encode(out);
+            }
+            Response::Failed(error) => {
+                out.push(9);
+                error.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Input<'_>) -> Result<Self, ProtocolError> {
+        match input.u8()? {
+            0 => Wire::decode(input).map(Response::Inserted),
+            1 => Wire::decode(input).map(Response::Ticket),
+            2 => Ok(Response::Updated),
+            3 => Wire::decode(input).map(Response::Batch),
+            4 => Wire::decode(input).map(Response::Tickets),
+            5 => Wire::decode(input).map(Response::Csv),
+            6 => Ok(Response::Subscribed),
+            7 => Wire::decode(input).map(Response::Event),
+            8 => Wire::decode(input).map(Response::Lagged),
+            9 => Wire::decode(input).map(Response::Failed),
+            tag => Err(unknown_tag("response", tag)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+    fn ticket() -> Ticket {
+        Ticket {
+            id: TicketId::from_value(7),
+            title: ticket_title(),
+            description: ticket_description(),
+            status: Status::InProgress,
+        }
+    }
+
+    fn round_trip(response: Response) {
+        let frame = encode_frame(&response).unwrap();
+        let len = u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize;
+        assert_eq!(len, frame.len() - 4);
+        assert_eq!(frame[4], PROTOCOL_VERSION);
+        let decoded: Response = read_frame(&mut frame.as_slice()).unwrap();
+        assert_eq!(decoded, response);
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        round_trip(Response::Ticket(Some(ticket())));
+        round_trip(Response::Ticket(None));
+        round_trip(Response::Csv(b"id,title\n".to_vec()));
+        round_trip(Response::Event(TicketEvent::Updated {
+            id: TicketId::from_value(3),
+            title: None,
+            description: Some(ticket_description()),
+        }));
+        round_trip(Response::Failed(ClientError::Validation(
+            ValidationError::Batch(BatchError {
+                results: vec![
+                    Ok(OperationOutcome::Inserted(TicketId::from_value(1))),
+                    Err(OperationError::NotFound(TicketId::from_value(2))),
+                ],
+            }),
+        )));
+        round_trip(Response::Failed(ClientError::RateLimited {
+            retry_after: Duration::from_millis(1500),
+        }));
+        round_trip(Response::Failed(ClientError::Cancelled));
+    }
+
+    #[test]
+    fn requests_carry_their_options() {
+        let request = Request {
+            client: ClientId::new("cli"),
+            correlation_id: Some(CorrelationId::new("request-7")),
+            timeout: Some(Duration::from_millis(250)),
+            backpressure: Some(Backpressure::BlockFor(Duration::from_secs(1))),
+            call: Call::Get(TicketId::from_value(7)),
+        };
+        let frame = encode_frame(&request).unwrap();
+        let decoded: Request = read_frame(&mut frame.as_slice()).unwrap();
+        assert_eq!(decoded.client, request.client);
+        assert_eq!(decoded.correlation_id, request.correlation_id);
+        assert_eq!(decoded.timeout, request.timeout);
+        assert_eq!(decoded.backpressure, request.backpressure);
+        assert!(matches!(decoded.call, Call::Get(id) if id == TicketId::from_value(7)));
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut frame = encode_frame(&Response::Updated).unwrap();
+        frame[4] = PROTOCOL_VERSION + 1;
+        let error = decode_frame::<Response>(&frame[4..]).unwrap_err();
+        assert_eq!(
+            error,
+            ProtocolError::UnsupportedVersion(PROTOCOL_VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn truncated_and_oversized_frames_are_rejected() {
+        let frame = encode_frame(&Response::Ticket(Some(ticket()))).unwrap();
+        let error = decode_frame::<Response>(&frame[4..frame.len() - 1]).unwrap_err();
+        assert!(matches!(error, ProtocolError::Malformed(_)), "{error:?}");
+
+        let mut huge = (MAX_FRAME_LEN as u32 + 1).to_be_bytes().to_vec();
+        huge.push(PROTOCOL_VERSION);
+        let Err(FrameError::Protocol(error)) = read_frame::<Response>(&mut huge.as_slice()) else {
+            panic!("An oversized frame was accepted");
+        };
+        assert!(matches!(error, ProtocolError::FrameTooLarge(_)));
+    }
+
+    #[test]
+    fn frames_shorter_than_their_length_are_rejected() {
+        // Claims the largest frame allowed, but the peer hangs up right away.
+        let mut short = (MAX_FRAME_LEN as u32).to_be_bytes().to_vec();
+        short.push(PROTOCOL_VERSION);
+        let Err(FrameError::Io(error)) = read_frame::<Response>(&mut short.as_slice()) else {
+            panic!("A truncated frame was accepted");
+        };
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn requests_are_capped_well_below_responses() {
+        let request = Request {
+            client: ClientId::anonymous(),
+            correlation_id: None,
+            timeout: None,
+            backpressure: None,
+            call: Call::Search("x".repeat(MAX_REQUEST_LEN)),
+        };
+        assert!(matches!(
+            encode_frame(&request),
+            Err(ProtocolError::FrameTooLarge(_))
+        ));
+
+        let mut frame = (MAX_REQUEST_LEN as u32 + 1).to_be_bytes().to_vec();
+        frame.push(PROTOCOL_VERSION);
+        let Err(FrameError::Protocol(error)) = read_frame::<Request>(&mut frame.as_slice()) else {
+            panic!("An oversized request was accepted");
+        };
+        assert!(matches!(error, ProtocolError::FrameTooLarge(_)));
+        let Err(FrameError::Io(_)) = read_frame::<Response>(&mut frame.as_slice()) else {
+            panic!("Responses that large are allowed");
+        };
+    }
+
+    #[test]
+    fn invalid_fields_are_rejected() {
+        let mut out = vec![PROTOCOL_VERSION, 0];
+        // A draft with an empty title.
+        String::new().encode(&mut out);
+        ticket_description().encode(&mut out);
+        let error = decode_frame::<Call>(&out).unwrap_err();
+        assert!(matches!(error, ProtocolError::Malformed(_)), "{error:?}");
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use patch::backpressure::Backpressure;
+use patch::data::TicketDraft;
+use patch::{launch_with, server, ClientError, Command, Config, TicketStoreClient};
+use ticket_fields::test_helpers::ticket_draft;
+
+/// A client whose single-slot queue is already full, plus the receiving end of it.
+fn full_queue() -> (TicketStoreClient, Receiver<Command>) {
+    let (sender, receiver) = sync_channel(1);
+    sender.send(Command::Shutdown).unwrap();
+    (TicketStoreClient::from_sender(sender), receiver)
+}
+
+#[test]
+fn launch_with_a_policy() {
+    let mut config = Config::new(5);
+    config.backpressure = Backpressure::Block;
+    let (client, _server) = launch_with(config);
+    assert_eq!(client.backpressure(), Backpressure::Block);
+    assert!(client.insert(ticket_draft!()).is_ok());
+}
+
+#[test]
+fn block_for_gives_up_at_the_deadline() {
+    let (client, _receiver) = full_queue();
+    let policy = Backpressure::BlockFor(Duration::from_millis(20));
+    let client = client.with_backpressure(policy);
+
+    let start = Instant::now();
+    assert_eq!(
+        client.insert(ticket_draft!()),
+        Err(ClientError::Overloaded(policy))
+    );
+    assert!(start.elapsed() >= Duration::from_millis(20));
+}
+
+#[test]
+fn retry_gives_up_after_max_attempts() {
+    let (client, _receiver) = full_queue();
+    let policy = Backpressure::Retry {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(2),
+    };
+    let client = client.with_backpressure(policy);
+    let err = client.insert(ticket_draft!()).unwrap_err();
+    assert_eq!(err, ClientError::Overloaded(policy));
+    assert_eq!(
+        err.to_string(),
+        "The store is overloaded (backpressure policy: retry up to 3 times with backoff)"
+    );
+}
+
+#[test]
+fn blocking_policies_wait_for_room_in_the_queue() {
+    let retry = Backpressure::Retry {
+        max_attempts: 100,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+    };
+    for policy in [
+        Backpressure::Block,
+        Backpressure::BlockFor(Duration::from_secs(5)),
+        retry,
+    ] {
+        let (client, receiver) = full_queue();
+        let client = client.with_backpressure(policy);
+
+        // Start serving only after a while: the insert has to wait for the queue to drain.
+        let server = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            server(receiver)
+        });
+        assert!(client.insert(ticket_draft!()).is_ok(), "{policy} failed");
+        drop(client);
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+}
+
+#[test]
+fn timeout_variants() {
+    let (client, receiver) = full_queue();
+    let err = client
+        .insert_timeout(ticket_draft!(), Duration::from_millis(10))
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ClientError::Overloaded(Backpressure::BlockFor(Duration::from_millis(10)))
+    );
+
+    // Make room in the queue: the command gets queued, but nobody answers.
+    drop(receiver.recv().unwrap());
+    let id = "0".parse().unwrap();
+    assert_eq!(
+        client.get_timeout(id, Duration::from_millis(10)),
+        Err(ClientError::Timeout)
+    );
+}
+
+#[test]
+fn retry_always_makes_one_attempt() {
+    let (client, _server) = launch_with(Config::new(5));
+    let client = client.with_backpressure(Backpressure::Retry {
+        max_attempts: 0,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(1),
+    });
+    assert!(client.insert(ticket_draft!()).is_ok());
+}
+
+#[test]
+fn timeouts_too_large_for_a_deadline_wait_forever() {
+    let (client, server) = launch_with(Config::new(5));
+    let id = client
+        .insert_timeout(ticket_draft!(), Duration::MAX)
+        .unwrap();
+    assert!(client.get_timeout(id, Duration::MAX).unwrap().is_some());
+    let client = client.with_backpressure(Backpressure::BlockFor(Duration::MAX));
+    client.insert(ticket_draft!()).unwrap();
+    assert_eq!(server.shutdown_timeout(Duration::MAX).store.len(), 2);
+}
@@ -0,0 +1,110 @@
+use patch::batch::{Operation, OperationError, OperationOutcome};
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::store::{TicketId, TicketStore};
+use patch::{launch, ClientError, ValidationError};
+use ticket_fields::test_helpers::ticket_draft;
+
+fn set_status(id: TicketId, status: Status) -> Operation {
+    Operation::Update(TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(status),
+    })
+}
+
+#[test]
+fn transaction_commits_on_ok() {
+    let mut store = TicketStore::new();
+    let id = store
+        .transaction(|tx| Ok::<_, ()>(tx.add_ticket(ticket_draft!())))
+        .unwrap();
+    assert!(store.get(id).is_some());
+}
+
+#[test]
+fn transaction_rolls_back_on_err() {
+    let mut store = TicketStore::new();
+    let result = store.transaction(|tx| {
+        tx.add_ticket(ticket_draft!());
+        tx.add_ticket(ticket_draft!());
+        Err::<(), _>("nope")
+    });
+    assert_eq!(result, Err("nope"));
+    assert!(store.is_empty());
+
+    // The id sequence is rolled back too.
+    assert_eq!(store.add_ticket(ticket_draft!()).to_string(), "0");
+}
+
+#[test]
+fn batch_applies_every_operation() {
+    let mut store = TicketStore::new();
+    let existing = store.add_ticket(ticket_draft!());
+
+    let outcomes = store
+        .apply_batch(vec![
+            Operation::Insert(ticket_draft!()),
+            set_status(existing, Status::Done),
+        ])
+        .unwrap();
+
+    let OperationOutcome::Inserted(inserted) = outcomes[0] else {
+        panic!("Expected an insert outcome, got {:?}", outcomes[0]);
+    };
+    assert_eq!(outcomes[1], OperationOutcome::Updated(existing));
+    assert!(store.get(inserted).is_some());
+    assert_eq!(store.get(existing).unwrap().status, Status::Done);
+}
+
+#[test]
+fn failed_batch_leaves_the_store_untouched() {
+    let mut store = TicketStore::new();
+    let existing = store.add_ticket(ticket_draft!());
+    let missing = "42".parse().unwrap();
+
+    let err = store
+        .apply_batch(vec![
+            Operation::Insert(ticket_draft!()),
+            set_status(existing, Status::Done),
+            set_status(missing, Status::Done),
+        ])
+        .unwrap_err();
+
+    assert_eq!(err.results.len(), 3);
+    assert!(err.results[0].is_ok());
+    assert_eq!(err.results[1], Ok(OperationOutcome::Updated(existing)));
+    assert_eq!(err.results[2], Err(OperationError::NotFound(missing)));
+    assert_eq!(err.to_string(), "1 out of 3 operations in the batch failed");
+
+    assert_eq!(store.len(), 1);
+    assert_eq!(store.get(existing).unwrap().status, Status::ToDo);
+}
+
+#[test]
+fn batch_command() {
+    let (client, _server) = launch(5);
+    let id = client.insert(ticket_draft!()).unwrap();
+
+    let outcomes = client
+        .batch(vec![
+            Operation::Insert(ticket_draft!()),
+            set_status(id, Status::Done),
+        ])
+        .unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(client.get(id).unwrap().unwrap().status, Status::Done);
+
+    let missing = "42".parse().unwrap();
+    let err = client
+        .batch(vec![
+            set_status(id, Status::ToDo),
+            set_status(missing, Status::ToDo),
+        ])
+        .unwrap_err();
+    let ClientError::Validation(ValidationError::Batch(err)) = err else {
+        panic!("Expected a batch validation error, got {err:?}");
+    };
+    assert_eq!(err.failures().count(), 1);
+    assert_eq!(client.get(id).unwrap().unwrap().status, Status::Done);
+}
@@ -0,0 +1,29 @@
+use patch::data::Status;
+use patch::id::{ProjectScoped, TimeOrdered};
+use patch::store::TicketStore;
+
+ticket_repository::conformance_tests!(
+    TicketStore::new(),
+    initial = Status::ToDo,
+    updated = Status::InProgress,
+);
+
+mod time_ordered {
+    use super::*;
+
+    ticket_repository::conformance_tests!(
+        TicketStore::with_id_strategy(TimeOrdered::new(0)),
+        initial = Status::ToDo,
+        updated = Status::InProgress,
+    );
+}
+
+mod project_scoped {
+    use super::*;
+
+    ticket_repository::conformance_tests!(
+        TicketStore::with_id_strategy(ProjectScoped::new("PROJ".try_into().unwrap())),
+        initial = Status::ToDo,
+        updated = Status::InProgress,
+    );
+}
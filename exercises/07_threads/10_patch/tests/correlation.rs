@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use patch::data::TicketDraft;
+use patch::{launch, launch_sharded};
+use ticket_fields::test_helpers::ticket_draft;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The correlation id of a span, inherited from its closest ancestor that has one.
+struct Correlation(String);
+
+/// Records the name and correlation id of every span, across all threads.
+#[derive(Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<(&'static str, String)>>>,
+}
+
+impl Recorder {
+    /// The names of the spans tagged with `correlation_id`.
+    fn spans(&self, correlation_id: &str) -> Vec<&'static str> {
+        let spans = self.spans.lock().unwrap();
+        spans
+            .iter()
+            .filter(|(_, id)| id == correlation_id)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+struct CorrelationVisitor(Option<String>);
+
+impl Visit for CorrelationVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "correlation_id" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+impl<S> Layer<S> for Recorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        let mut visitor = CorrelationVisitor(None);
+        attrs.record(&mut visitor);
+        let correlation_id = visitor.0.or_else(|| {
+            span.scope().skip(1).find_map(|parent| {
+                parent
+                    .extensions()
+                    .get::<Correlation>()
+                    .map(|c| c.0.clone())
+            })
+        });
+        if let Some(correlation_id) = correlation_id {
+            self.spans
+                .lock()
+                .unwrap()
+                .push((span.name(), correlation_id.clone()));
+            span.extensions_mut().insert(Correlation(correlation_id));
+        }
+    }
+}
+
+/// The server threads are not ours, so the recorder has to be the global subscriber.
+fn recorder() -> &'static Recorder {
+    static RECORDER: OnceLock<Recorder> = OnceLock::new();
+    RECORDER.get_or_init(|| {
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::set_global_default(subscriber).unwrap();
+        recorder
+    })
+}
+
+#[test]
+fn the_caller_s_correlation_id_follows_the_command() {
+    let recorder = recorder();
+    let (client, server) = launch(5);
+    let client = client.with_correlation_id("request-42");
+    assert_eq!(client.correlation_id().unwrap().as_str(), "request-42");
+
+    client.insert(ticket_draft!()).unwrap();
+    assert_eq!(
+        recorder.spans("request-42"),
+        [
+            "ticket_store.call",
+            "enqueue",
+            "ticket_server.command",
+            "dequeue",
+            "execute",
+            "reply"
+        ]
+    );
+    server.shutdown();
+}
+
+#[test]
+fn offloaded_commands_are_traced_on_the_worker() {
+    let recorder = recorder();
+    let (client, server) = launch(5);
+    let client = client.with_correlation_id("request-search");
+
+    client.search("anything").unwrap();
+    let spans = recorder.spans("request-search");
+    assert!(spans.contains(&"worker"), "{spans:?}");
+    assert_eq!(spans.last(), Some(&"reply"));
+    server.shutdown();
+}
+
+#[test]
+fn every_call_gets_its_own_id_by_default() {
+    let recorder = recorder();
+    let (client, server) = launch_sharded(2, 5);
+    assert!(client.correlation_id().is_none());
+
+    client.insert(ticket_draft!()).unwrap();
+    client.insert(ticket_draft!()).unwrap();
+    // One command per shard, both part of the same call.
+    client.search("anything").unwrap();
+
+    let spans = recorder.spans.lock().unwrap().clone();
+    let calls: Vec<_> = spans
+        .iter()
+        .filter(|(name, id)| *name == "ticket_server.command" && id.starts_with("call-"))
+        .map(|(_, id)| id.clone())
+        .collect();
+    let distinct: HashSet<_> = calls.iter().collect();
+    assert_eq!(calls.len(), distinct.len() + 1);
+    server.shutdown();
+}
@@ -0,0 +1,137 @@
+use patch::csv_io::{export_csv, import_csv, Column, ImportError, ImportMode};
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::id::ProjectScoped;
+use patch::store::TicketStore;
+
+fn draft(title: &str, description: &str) -> TicketDraft {
+    TicketDraft {
+        title: title.try_into().unwrap(),
+        description: description.try_into().unwrap(),
+    }
+}
+
+fn export(store: &TicketStore, columns: &[Column]) -> String {
+    let mut output = Vec::new();
+    export_csv(store, columns, &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn export_default_columns() {
+    let mut store = TicketStore::new();
+    store.add_ticket(draft("First", "Plain"));
+    let id = store.add_ticket(draft("Second", "Has a comma, and \"quotes\""));
+    store.update(TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(Status::InProgress),
+    });
+
+    assert_eq!(
+        export(&store, &Column::defaults()),
+        "id,title,description,status\n\
+         0,First,Plain,To Do\n\
+         1,Second,\"Has a comma, and \"\"quotes\"\"\",In Progress\n"
+    );
+}
+
+#[test]
+fn export_custom_columns() {
+    let mut store = TicketStore::with_id_strategy(ProjectScoped::new("PROJ".try_into().unwrap()));
+    store.add_ticket(draft("First", "Plain"));
+
+    let columns = [
+        Column::Key,
+        Column::Status,
+        Column::Computed {
+            header: "title_length".into(),
+            value: |ticket| ticket.title.as_str().len().to_string(),
+        },
+    ];
+    assert_eq!(
+        export(&store, &columns),
+        "key,status,title_length\nPROJ-1,To Do,5\n"
+    );
+}
+
+#[test]
+fn round_trip() {
+    let mut source = TicketStore::new();
+    source.add_ticket(draft("First", "One"));
+    let id = source.add_ticket(draft("Second", "Two"));
+    source.update(TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(Status::Done),
+    });
+    let csv = export(&source, &Column::defaults());
+
+    let mut destination = TicketStore::new();
+    let report = import_csv(&mut destination, csv.as_bytes(), ImportMode::Apply).unwrap();
+
+    assert_eq!(report.tickets.len(), 2);
+    assert_eq!(report.tickets[1].line, 3);
+    let imported: Vec<_> = destination
+        .iter()
+        .map(|t| (t.title.clone(), t.description.clone(), t.status))
+        .collect();
+    let original: Vec<_> = source
+        .iter()
+        .map(|t| (t.title.clone(), t.description.clone(), t.status))
+        .collect();
+    assert_eq!(imported, original);
+}
+
+#[test]
+fn import_reports_every_invalid_field() {
+    let csv = "Title,Description,Status\n\
+               Fine,Fine,todo\n\
+               ,Fine,Blocked\n\
+               Fine,,done\n";
+    let mut store = TicketStore::new();
+    let Err(ImportError::Invalid(errors)) =
+        import_csv(&mut store, csv.as_bytes(), ImportMode::Apply)
+    else {
+        panic!("The import should have failed");
+    };
+
+    let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        errors,
+        [
+            "line 3, column `title`: The title cannot be empty",
+            "line 3, column `status`: `Blocked` is not a valid status, expected one of `To Do`, `In Progress` or `Done`",
+            "line 4, column `description`: The description cannot be empty",
+        ]
+    );
+    assert!(store.is_empty());
+}
+
+#[test]
+fn import_requires_title_and_description() {
+    let mut store = TicketStore::new();
+    let err = import_csv(
+        &mut store,
+        "title,status\nA,Done\n".as_bytes(),
+        ImportMode::Apply,
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "The `description` column is missing");
+}
+
+#[test]
+fn dry_run_does_not_touch_the_store() {
+    let mut store = TicketStore::new();
+    store.add_ticket(draft("Existing", "Existing"));
+
+    let csv = "title,description\nNew,New\n";
+    let report = import_csv(&mut store, csv.as_bytes(), ImportMode::DryRun).unwrap();
+
+    assert_eq!(report.mode, ImportMode::DryRun);
+    assert_eq!(report.tickets.len(), 1);
+    assert_eq!(report.tickets[0].ticket.id.to_string(), "1");
+    assert_eq!(report.tickets[0].ticket.status, Status::ToDo);
+    assert_eq!(store.len(), 1);
+}
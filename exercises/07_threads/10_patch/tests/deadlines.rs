@@ -0,0 +1,129 @@
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use patch::backpressure::Backpressure;
+use patch::cancel::CancelHandle;
+use patch::data::TicketDraft;
+use patch::store::TicketStore;
+use patch::{launch, server, ClientError, Command, TicketStoreClient};
+use ticket_fields::test_helpers::ticket_draft;
+
+/// A client for a server that isn't running yet, plus the receiving end of its queue.
+fn stalled() -> (TicketStoreClient, Receiver<Command>) {
+    let (sender, receiver) = sync_channel(8);
+    (TicketStoreClient::from_sender(sender), receiver)
+}
+
+/// Serve the commands queued so far, and those sent by `client`, then return the store.
+fn serve(client: TicketStoreClient, receiver: Receiver<Command>) -> TicketStore {
+    let server = thread::spawn(move || server(receiver));
+    client.insert(ticket_draft!()).unwrap();
+    drop(client);
+    server.join().unwrap()
+}
+
+#[test]
+fn commands_past_their_deadline_are_skipped() {
+    let (client, receiver) = stalled();
+    let impatient = client.clone().with_timeout(Duration::from_millis(10));
+    assert_eq!(impatient.timeout(), Some(Duration::from_millis(10)));
+
+    assert_eq!(impatient.insert(ticket_draft!()), Err(ClientError::Timeout));
+    assert_eq!(
+        client.insert_timeout(ticket_draft!(), Duration::from_millis(10)),
+        Err(ClientError::Timeout)
+    );
+    drop(impatient);
+    // Only the insert sent once the server is up made it to the store.
+    assert_eq!(serve(client, receiver).len(), 1);
+}
+
+#[test]
+fn cancelling_wakes_up_pending_calls() {
+    let (client, receiver) = stalled();
+    let handle = CancelHandle::new();
+    let cancellable = client.clone().with_cancel(handle.clone());
+    let pending = thread::spawn(move || cancellable.insert(ticket_draft!()));
+    thread::sleep(Duration::from_millis(20));
+
+    let start = Instant::now();
+    handle.cancel();
+    assert_eq!(pending.join().unwrap(), Err(ClientError::Cancelled));
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // Calls made after the fact don't even reach the queue.
+    let cancelled = client.clone().with_cancel(handle);
+    assert_eq!(
+        cancelled.insert(ticket_draft!()),
+        Err(ClientError::Cancelled)
+    );
+    drop(cancelled);
+    assert_eq!(serve(client, receiver).len(), 1);
+}
+
+#[test]
+fn cancellable_calls_notice_when_the_server_dies() {
+    let (client, receiver) = stalled();
+    let cancellable = client.with_cancel(CancelHandle::new());
+    let (done, outcome) = sync_channel(1);
+    thread::spawn(move || done.send(cancellable.insert(ticket_draft!())));
+
+    // The server takes the command, then dies without replying.
+    drop(receiver.recv().unwrap());
+    drop(receiver);
+    assert_eq!(
+        outcome.recv_timeout(Duration::from_secs(5)),
+        Ok(Err(ClientError::ServerGone))
+    );
+}
+
+#[test]
+fn cancelling_wakes_up_calls_waiting_for_room_in_the_queue() {
+    let policies = [
+        Backpressure::Block,
+        Backpressure::BlockFor(Duration::from_secs(60)),
+        Backpressure::Retry {
+            max_attempts: 10,
+            initial_backoff: Duration::from_secs(60),
+            max_backoff: Duration::from_secs(60),
+        },
+    ];
+    for policy in policies {
+        let (sender, receiver) = sync_channel(1);
+        sender.send(Command::Shutdown).unwrap();
+        let handle = CancelHandle::new();
+        let client = TicketStoreClient::from_sender(sender)
+            .with_backpressure(policy)
+            .with_cancel(handle.clone());
+        let pending = thread::spawn(move || client.insert(ticket_draft!()));
+        thread::sleep(Duration::from_millis(20));
+
+        let start = Instant::now();
+        handle.cancel();
+        assert_eq!(
+            pending.join().unwrap(),
+            Err(ClientError::Cancelled),
+            "{policy}"
+        );
+        assert!(start.elapsed() < Duration::from_secs(1), "{policy}");
+        // The command never made it to the queue.
+        assert_eq!(receiver.try_iter().count(), 1, "{policy}");
+    }
+}
+
+#[test]
+fn skipped_commands_are_counted() {
+    let (client, server) = launch(16);
+    let client = client.with_timeout(Duration::ZERO);
+    // Expired as soon as it's queued: the server never gets to it in time.
+    assert_eq!(client.insert(ticket_draft!()), Err(ClientError::Timeout));
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while server.stats().expired == 0 {
+        assert!(Instant::now() < deadline, "The command was never skipped");
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(server.stats().cancelled, 0);
+    assert_eq!(server.shutdown().len(), 0);
+}
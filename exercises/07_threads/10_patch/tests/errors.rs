@@ -0,0 +1,90 @@
+use std::sync::mpsc::sync_channel;
+use std::thread;
+
+use patch::backpressure::Backpressure;
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::store::TicketId;
+use patch::{launch, ClientError, Command, TicketStoreClient, ValidationError};
+use ticket_fields::test_helpers::ticket_draft;
+
+fn set_status(id: TicketId, status: Status) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(status),
+    }
+}
+
+#[test]
+fn update_missing_ticket() {
+    let (client, _server) = launch(5);
+    let missing = "42".parse().unwrap();
+    assert_eq!(
+        client.update(set_status(missing, Status::Done)),
+        Err(ClientError::NotFound(missing))
+    );
+}
+
+#[test]
+fn empty_patch() {
+    let (client, _server) = launch(5);
+    let id = client.insert(ticket_draft!()).unwrap();
+    let patch = TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: None,
+    };
+    assert_eq!(
+        client.update(patch),
+        Err(ClientError::Validation(ValidationError::EmptyPatch(id)))
+    );
+}
+
+#[test]
+fn conflicting_update() {
+    let (client, _server) = launch(5);
+    let id = client.insert(ticket_draft!()).unwrap();
+    let snapshot = client.get(id).unwrap().unwrap();
+
+    client.update(set_status(id, Status::InProgress)).unwrap();
+    assert_eq!(
+        client.update_if_unchanged(snapshot, set_status(id, Status::Done)),
+        Err(ClientError::Conflict(id))
+    );
+
+    let snapshot = client.get(id).unwrap().unwrap();
+    client
+        .update_if_unchanged(snapshot, set_status(id, Status::Done))
+        .unwrap();
+    assert_eq!(client.get(id).unwrap().unwrap().status, Status::Done);
+}
+
+#[test]
+fn overloaded() {
+    // Nobody is reading from the queue, so it fills up immediately.
+    let (sender, _receiver) = sync_channel::<Command>(0);
+    let client = TicketStoreClient::from_sender(sender);
+    assert_eq!(
+        client.insert(ticket_draft!()),
+        Err(ClientError::Overloaded(Backpressure::FailFast))
+    );
+}
+
+#[test]
+fn server_gone() {
+    let (sender, receiver) = sync_channel::<Command>(5);
+    let client = TicketStoreClient::from_sender(sender);
+
+    // A server that dies without replying.
+    let server = thread::spawn(move || {
+        let _command = receiver.recv().unwrap();
+        panic!("The server crashed");
+    });
+    assert_eq!(client.insert(ticket_draft!()), Err(ClientError::ServerGone));
+    assert!(server.join().is_err());
+
+    // Nobody is listening anymore.
+    assert_eq!(client.insert(ticket_draft!()), Err(ClientError::ServerGone));
+}
@@ -0,0 +1,43 @@
+use patch::data::TicketDraft;
+use patch::id::{ProjectScoped, TicketRef, TimeOrdered};
+use patch::store::TicketStore;
+use ticket_fields::test_helpers::ticket_draft;
+
+#[test]
+fn sequential_by_default() {
+    let mut store = TicketStore::new();
+    let first = store.add_ticket(ticket_draft!());
+    let second = store.add_ticket(ticket_draft!());
+    assert_eq!(first.to_string(), "0");
+    assert_eq!(second.to_string(), "1");
+    assert_eq!(store.key(first), None);
+}
+
+#[test]
+fn time_ordered() {
+    let mut store = TicketStore::with_id_strategy(TimeOrdered::new(0));
+    let first = store.add_ticket(ticket_draft!());
+    let second = store.add_ticket(ticket_draft!());
+    assert!(first < second);
+    assert_eq!(store.get(second).unwrap().id, second);
+}
+
+#[test]
+fn lookup_by_id_or_key() {
+    let project = "PROJ".try_into().unwrap();
+    let mut store = TicketStore::with_id_strategy(ProjectScoped::new(project));
+    store.add_ticket(ticket_draft!());
+    let id = store.add_ticket(ticket_draft!());
+
+    let key = store.key(id).unwrap();
+    assert_eq!(key.to_string(), "PROJ-2");
+
+    for reference in ["2", "PROJ-2"] {
+        let reference: TicketRef = reference.parse().unwrap();
+        assert_eq!(store.lookup(&reference).unwrap().id, id);
+    }
+    for reference in ["3", "PROJ-3", "OTHER-2"] {
+        let reference: TicketRef = reference.parse().unwrap();
+        assert_eq!(store.lookup(&reference), None);
+    }
+}
@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use patch::batch::Operation;
+use patch::cancel::CancelHandle;
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::limits::{ClientId, ClientLimits, Limits, Quota, RateLimit};
+use patch::metrics::CommandKind;
+use patch::{
+    launch_sharded_with, launch_with, ClientError, Config, ServerHandle, TicketStoreClient,
+};
+use ticket_fields::test_helpers::ticket_draft;
+
+fn launch_limited(limits: ClientLimits) -> (TicketStoreClient, ServerHandle) {
+    let mut config = Config::new(16);
+    config.limits = Limits::new(limits);
+    launch_with(config)
+}
+
+fn quota(tickets: u64) -> ClientLimits {
+    ClientLimits {
+        rate: None,
+        quota: Some(Quota::per_hour(tickets)),
+    }
+}
+
+#[test]
+fn bursts_over_the_rate_limit_are_rejected() {
+    let (client, server) = launch_limited(ClientLimits {
+        rate: Some(RateLimit {
+            burst: 2,
+            per_second: 0.5,
+        }),
+        quota: None,
+    });
+    let id = client.insert(ticket_draft!()).unwrap();
+    assert!(client.get(id).unwrap().is_some());
+
+    let Err(ClientError::RateLimited { retry_after }) = client.get(id) else {
+        panic!("The third command should have been rate limited");
+    };
+    assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(2));
+    assert_eq!(server.stats().rate_limited, 1);
+    // Turned away before reaching the queue.
+    assert_eq!(server.stats().command(CommandKind::Get).handled, 1);
+    server.shutdown();
+}
+
+#[test]
+fn shards_share_the_limits() {
+    let mut config = Config::new(16);
+    config.limits = Limits::new(ClientLimits {
+        rate: Some(RateLimit {
+            burst: 3,
+            per_second: 0.1,
+        }),
+        quota: Some(Quota::per_hour(2)),
+    });
+    let (client, server) = launch_sharded_with(2, config);
+
+    // One ticket on each shard uses up the quota.
+    client.insert(ticket_draft!()).unwrap();
+    client.insert(ticket_draft!()).unwrap();
+    let error = client.insert(ticket_draft!()).unwrap_err();
+    assert!(
+        matches!(error, ClientError::RateLimited { .. }),
+        "{error:?}"
+    );
+    // And the burst, whichever shard the next command goes to.
+    let error = client.insert(ticket_draft!()).unwrap_err();
+    assert!(
+        matches!(error, ClientError::RateLimited { .. }),
+        "{error:?}"
+    );
+
+    let usage = server.usage();
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0].commands, 2);
+    assert_eq!(usage[0].tickets_created, 2);
+    assert_eq!(usage[0].rate_limited, 2);
+    assert_eq!(usage[0].quota_remaining, Some(0));
+    let stores = server.shutdown();
+    assert_eq!(stores.iter().map(|store| store.len()).sum::<usize>(), 2);
+}
+
+#[test]
+fn tickets_over_the_quota_are_not_created() {
+    let (client, server) = launch_limited(quota(3));
+    for _ in 0..3 {
+        client.insert(ticket_draft!()).unwrap();
+    }
+    let error = client.insert(ticket_draft!()).unwrap_err();
+    assert!(
+        matches!(error, ClientError::RateLimited { .. }),
+        "{error:?}"
+    );
+    let error = client
+        .batch(vec![
+            Operation::Insert(ticket_draft!()),
+            Operation::Insert(ticket_draft!()),
+        ])
+        .unwrap_err();
+    assert!(
+        matches!(error, ClientError::RateLimited { .. }),
+        "{error:?}"
+    );
+
+    let usage = server.usage();
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0].client, ClientId::anonymous());
+    assert_eq!(usage[0].tickets_created, 3);
+    assert_eq!(usage[0].rate_limited, 2);
+    assert_eq!(usage[0].quota_remaining, Some(0));
+    server.shutdown();
+}
+
+#[test]
+fn each_identity_has_its_own_limits() {
+    let mut config = Config::new(16);
+    config.limits = Limits::new(quota(1)).with_override("importer", quota(100));
+    let (client, server) = launch_with(config);
+    let alice = client.clone().with_identity("alice");
+    let bob = client.clone().with_identity("bob");
+    let importer = client.with_identity("importer");
+    assert_eq!(alice.identity().as_str(), "alice");
+
+    alice.insert(ticket_draft!()).unwrap();
+    assert!(alice.insert(ticket_draft!()).is_err());
+    bob.insert(ticket_draft!()).unwrap();
+    for _ in 0..5 {
+        importer.insert(ticket_draft!()).unwrap();
+    }
+
+    let usage: Vec<_> = server
+        .usage()
+        .into_iter()
+        .map(|usage| {
+            (
+                usage.client.to_string(),
+                usage.commands,
+                usage.tickets_created,
+                usage.quota_remaining,
+            )
+        })
+        .collect();
+    assert_eq!(
+        usage,
+        [
+            ("alice".to_string(), 1, 1, Some(0)),
+            ("bob".to_string(), 1, 1, Some(0)),
+            ("importer".to_string(), 5, 5, Some(95)),
+        ]
+    );
+    server.shutdown();
+}
+
+#[test]
+fn failed_commands_give_their_tickets_back() {
+    let (client, server) = launch_limited(quota(2));
+    let missing = "42".parse().unwrap();
+    let error = client
+        .batch(vec![
+            Operation::Insert(ticket_draft!()),
+            Operation::Update(TicketPatch {
+                id: missing,
+                title: None,
+                description: None,
+                status: Some(Status::Done),
+            }),
+        ])
+        .unwrap_err();
+    assert!(matches!(error, ClientError::Validation(_)), "{error:?}");
+    assert_eq!(server.usage()[0].quota_remaining, Some(2));
+
+    client.insert(ticket_draft!()).unwrap();
+    client.insert(ticket_draft!()).unwrap();
+    assert_eq!(server.usage()[0].tickets_created, 2);
+    server.shutdown();
+}
+
+#[test]
+fn commands_that_never_get_queued_keep_their_token() {
+    let (client, server) = launch_limited(ClientLimits {
+        rate: Some(RateLimit {
+            burst: 2,
+            per_second: 0.01,
+        }),
+        quota: None,
+    });
+    let id = client.insert(ticket_draft!()).unwrap();
+    let cancel = CancelHandle::new();
+    cancel.cancel();
+    let error = client.clone().with_cancel(cancel).get(id).unwrap_err();
+    assert_eq!(error, ClientError::Cancelled);
+
+    assert!(client.get(id).unwrap().is_some());
+    let error = client.get(id).unwrap_err();
+    assert!(
+        matches!(error, ClientError::RateLimited { .. }),
+        "{error:?}"
+    );
+    server.shutdown();
+}
@@ -0,0 +1,90 @@
+use patch::backpressure::Backpressure;
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::metrics::CommandKind;
+use patch::{launch, launch_sharded, ClientError};
+use ticket_fields::test_helpers::ticket_draft;
+
+#[test]
+fn commands_are_counted_per_kind() {
+    let (client, server) = launch(5);
+    let id = client.insert(ticket_draft!()).unwrap();
+    client.get(id).unwrap();
+    client.get(id).unwrap();
+    let missing = "42".parse().unwrap();
+    let error = client
+        .update(TicketPatch {
+            id: missing,
+            title: None,
+            description: None,
+            status: Some(Status::Done),
+        })
+        .unwrap_err();
+    assert_eq!(error, ClientError::NotFound(missing));
+
+    let stats = server.stats();
+    assert_eq!(stats.capacity, 5);
+    assert_eq!(stats.queued, 0);
+    assert_eq!(stats.command(CommandKind::Insert).handled, 1);
+    assert_eq!(stats.command(CommandKind::Get).handled, 2);
+    assert_eq!(stats.command(CommandKind::Get).latency.count(), 2);
+    assert_eq!(stats.command(CommandKind::Update).handled, 1);
+    assert_eq!(stats.command(CommandKind::Update).failed, 1);
+    assert_eq!(stats.command(CommandKind::Batch).handled, 0);
+    assert!(stats.command(CommandKind::Get).latency.mean().is_some());
+}
+
+#[test]
+fn overloads_are_counted() {
+    let (client, server) = launch(1);
+    let client = client.with_backpressure(Backpressure::FailFast);
+    // Keep the server busy with a burst of concurrent clients on a one-slot queue.
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let client = client.clone();
+            std::thread::spawn(move || {
+                (0..200)
+                    .filter(|_| {
+                        client.insert(ticket_draft!())
+                            == Err(ClientError::Overloaded(Backpressure::FailFast))
+                    })
+                    .count() as u64
+            })
+        })
+        .collect();
+    let rejected: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+    let stats = server.stats();
+    assert_eq!(stats.overloaded, rejected);
+    assert_eq!(
+        stats.command(CommandKind::Insert).handled + rejected,
+        8 * 200
+    );
+}
+
+#[test]
+fn stats_are_scrapable_as_text() {
+    let (client, server) = launch(5);
+    client.insert(ticket_draft!()).unwrap();
+
+    let text = server.stats().to_string();
+    assert!(text.contains("ticket_server_commands_total{command=\"insert\"} 1\n"));
+    assert!(text.contains("ticket_server_command_duration_seconds_count{command=\"insert\"} 1\n"));
+    assert!(text.contains(
+        "ticket_server_command_duration_seconds_bucket{command=\"insert\",le=\"+Inf\"} 1\n"
+    ));
+    assert!(text.contains("ticket_server_queue_capacity 5\n"));
+    assert!(text.contains("# TYPE ticket_server_overloaded_total counter\n"));
+}
+
+#[test]
+fn each_shard_has_its_own_stats() {
+    let (client, server) = launch_sharded(2, 5);
+    for _ in 0..4 {
+        client.insert(ticket_draft!()).unwrap();
+    }
+    let stats = server.stats();
+    assert_eq!(stats.len(), 2);
+    for shard in stats {
+        assert_eq!(shard.command(CommandKind::Insert).handled, 2);
+    }
+}
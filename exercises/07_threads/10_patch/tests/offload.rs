@@ -0,0 +1,76 @@
+use patch::csv_io::Column;
+use patch::data::TicketDraft;
+use patch::metrics::CommandKind;
+use patch::{launch, launch_sharded, launch_with, Config};
+use ticket_fields::test_helpers::ticket_description;
+use ticket_fields::TicketTitle;
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: ticket_description(),
+    }
+}
+
+#[test]
+fn search_matches_titles_ignoring_case() {
+    let (client, server) = launch(10);
+    let login = client.insert(draft("Fix the LOGIN page")).unwrap();
+    client.insert(draft("Add a dark theme")).unwrap();
+    let logout = client.insert(draft("Logout button is broken")).unwrap();
+
+    let found: Vec<_> = client
+        .search("log")
+        .unwrap()
+        .into_iter()
+        .map(|ticket| ticket.id)
+        .collect();
+    assert_eq!(found, [login, logout]);
+    assert_eq!(server.stats().command(CommandKind::Search).handled, 1);
+}
+
+#[test]
+fn searches_see_the_latest_writes() {
+    let (client, _server) = launch(10);
+    for i in 0..20 {
+        client.insert(draft(&format!("Ticket {i}"))).unwrap();
+        assert_eq!(client.search("ticket").unwrap().len(), i + 1);
+    }
+}
+
+#[test]
+fn export_runs_on_the_pool() {
+    let (client, _server) = launch(10);
+    client.insert(draft("First")).unwrap();
+    client.insert(draft("Second")).unwrap();
+
+    let csv = client.export_csv(&[Column::Id, Column::Title]).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "id,title\n0,First\n1,Second\n"
+    );
+}
+
+#[test]
+fn sharded_exports_have_a_single_header() {
+    let (client, _server) = launch_sharded(2, 10);
+    for title in ["A", "B", "C"] {
+        client.insert(draft(title)).unwrap();
+    }
+
+    let csv = String::from_utf8(client.export_csv(&[Column::Id]).unwrap()).unwrap();
+    assert_eq!(csv, "id\n0\n2\n1\n");
+    assert_eq!(client.search("").unwrap().len(), 3);
+}
+
+#[test]
+fn zero_workers_still_run_searches() {
+    let (client, server) = launch_with(Config {
+        workers: 0,
+        ..Config::new(10)
+    });
+    client.insert(draft("Lonely worker")).unwrap();
+
+    assert_eq!(client.search("lonely").unwrap().len(), 1);
+    assert_eq!(server.shutdown().len(), 1);
+}
@@ -0,0 +1,133 @@
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::id::{ProjectKey, TicketKey};
+use patch::project::{ProjectError, ProjectStore};
+use ticket_fields::test_helpers::{ticket_draft, ticket_title};
+
+fn project_key(key: &str) -> ProjectKey {
+    key.try_into().unwrap()
+}
+
+fn ticket_key(key: &str) -> TicketKey {
+    key.parse().unwrap()
+}
+
+fn store() -> ProjectStore {
+    let mut store = ProjectStore::new();
+    store
+        .create_project(project_key("WEB"), "Website".into())
+        .unwrap();
+    store
+        .create_project(project_key("OPS"), "Operations".into())
+        .unwrap();
+    store
+}
+
+#[test]
+fn projects_are_numbered_independently() {
+    let mut store = store();
+    let web1 = store
+        .add_ticket(&project_key("WEB"), ticket_draft!())
+        .unwrap();
+    let web2 = store
+        .add_ticket(&project_key("WEB"), ticket_draft!())
+        .unwrap();
+    let ops1 = store
+        .add_ticket(&project_key("OPS"), ticket_draft!())
+        .unwrap();
+
+    assert_eq!(web1.to_string(), "WEB-1");
+    assert_eq!(web2.to_string(), "WEB-2");
+    assert_eq!(ops1.to_string(), "OPS-1");
+    assert_eq!(
+        store.project(&project_key("OPS")).unwrap().name(),
+        "Operations"
+    );
+}
+
+#[test]
+fn lookup_by_qualified_key() {
+    let mut store = store();
+    let key = store
+        .add_ticket(&project_key("OPS"), ticket_draft!())
+        .unwrap();
+
+    assert_eq!(
+        store.get(&ticket_key("OPS-1")).unwrap().title,
+        ticket_title()
+    );
+    assert!(store.get(&ticket_key("WEB-1")).is_none());
+    assert!(store.get(&ticket_key("NOPE-1")).is_none());
+
+    store.get_mut(&key).unwrap().status = Status::Done;
+    assert_eq!(store.get(&key).unwrap().status, Status::Done);
+}
+
+#[test]
+fn duplicate_and_unknown_projects() {
+    let mut store = store();
+    assert_eq!(
+        store
+            .create_project(project_key("WEB"), "Again".into())
+            .err(),
+        Some(ProjectError::DuplicateProject(project_key("WEB")))
+    );
+    assert_eq!(
+        store.add_ticket(&project_key("NOPE"), ticket_draft!()),
+        Err(ProjectError::UnknownProject(project_key("NOPE")))
+    );
+}
+
+#[test]
+fn moved_tickets_keep_a_redirect() {
+    let mut store = store();
+    store
+        .add_ticket(&project_key("OPS"), ticket_draft!())
+        .unwrap();
+    let old_key = store
+        .add_ticket(&project_key("WEB"), ticket_draft!())
+        .unwrap();
+    let project = store.project_mut(&project_key("WEB")).unwrap();
+    let id = project.tickets().resolve(&old_key.clone().into()).unwrap();
+    project.tickets_mut().update(TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(Status::InProgress),
+    });
+
+    let new_key = store.move_ticket(&old_key, &project_key("OPS")).unwrap();
+    assert_eq!(new_key.to_string(), "OPS-2");
+    assert_eq!(store.resolve(&old_key), Some(new_key.clone()));
+
+    let ticket = store.get(&old_key).unwrap();
+    assert_eq!(ticket.status, Status::InProgress);
+    assert_eq!(ticket.title, ticket_title());
+    assert!(store
+        .project(&project_key("WEB"))
+        .unwrap()
+        .tickets()
+        .is_empty());
+
+    // Redirects are followed across multiple moves.
+    let newest_key = store.move_ticket(&old_key, &project_key("WEB")).unwrap();
+    assert_eq!(newest_key.to_string(), "WEB-2");
+    assert_eq!(store.resolve(&old_key), Some(newest_key.clone()));
+    assert_eq!(store.resolve(&new_key), Some(newest_key));
+}
+
+#[test]
+fn moving_errors() {
+    let mut store = store();
+    let key = store
+        .add_ticket(&project_key("WEB"), ticket_draft!())
+        .unwrap();
+    assert_eq!(
+        store.move_ticket(&key, &project_key("NOPE")),
+        Err(ProjectError::UnknownProject(project_key("NOPE")))
+    );
+    assert_eq!(
+        store.move_ticket(&ticket_key("WEB-9"), &project_key("OPS")),
+        Err(ProjectError::UnknownTicket(ticket_key("WEB-9")))
+    );
+    assert_eq!(store.move_ticket(&key, &project_key("WEB")), Ok(key));
+}
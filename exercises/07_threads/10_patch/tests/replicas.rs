@@ -0,0 +1,123 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::metrics::CommandKind;
+use patch::replication::{ReadFrom, ReplicaStats, WriteToken};
+use patch::{launch_with, Config, ServerHandle};
+use ticket_fields::test_helpers::{ticket_draft, ticket_title};
+
+fn replicated(replicas: usize, replication_log: usize) -> Config {
+    let mut config = Config::new(64);
+    config.replicas = replicas;
+    config.replication_log = replication_log;
+    config
+}
+
+const FROM_REPLICA: ReadFrom = ReadFrom::Replica {
+    max_staleness: Duration::from_secs(60),
+};
+
+/// Wait until every replica has applied every write.
+fn caught_up(server: &ServerHandle) -> Vec<ReplicaStats> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let replicas = server.replicas();
+        if replicas.iter().all(|replica| replica.behind == 0) {
+            return replicas;
+        }
+        assert!(Instant::now() < deadline, "The replicas never caught up");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn replica_reads(server: &ServerHandle) -> u64 {
+    server.replicas().iter().map(|replica| replica.reads).sum()
+}
+
+#[test]
+fn clients_read_from_the_server_by_default() {
+    let (client, server) = launch_with(replicated(2, 16));
+    assert_eq!(client.read_from(), ReadFrom::Primary);
+    let id = client.insert(ticket_draft!()).unwrap();
+    caught_up(&server);
+
+    assert!(client.get(id).unwrap().is_some());
+    assert_eq!(replica_reads(&server), 0);
+    server.shutdown();
+}
+
+#[test]
+fn replicas_serve_reads_once_caught_up() {
+    let (client, server) = launch_with(replicated(2, 16));
+    let client = client.with_read_from(FROM_REPLICA);
+    let id = client.insert(ticket_draft!()).unwrap();
+    client
+        .update(TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(Status::Done),
+        })
+        .unwrap();
+    let replicas = caught_up(&server);
+    assert!(replicas.iter().all(|replica| replica.applied == 2));
+
+    assert_eq!(client.get(id).unwrap().unwrap().status, Status::Done);
+    assert_eq!(client.search(ticket_title().as_str()).unwrap().len(), 1);
+    assert_eq!(replica_reads(&server), 2);
+    let stats = server.stats();
+    assert_eq!(stats.command(CommandKind::Get).handled, 0);
+    assert_eq!(stats.command(CommandKind::Search).handled, 0);
+    server.shutdown();
+}
+
+#[test]
+fn clients_read_their_own_writes() {
+    let (client, server) = launch_with(replicated(3, 16));
+    let client = client.with_read_from(FROM_REPLICA);
+    for _ in 0..200 {
+        let id = client.insert(ticket_draft!()).unwrap();
+        assert!(client.get(id).unwrap().is_some(), "Ticket {id} is missing");
+    }
+    server.shutdown();
+}
+
+#[test]
+fn write_tokens_carry_writes_over_to_other_clients() {
+    let (client, server) = launch_with(replicated(2, 16));
+    let writer = client.clone();
+    // Clones share their writes: give the reader a session of its own.
+    let reader = client
+        .with_read_from(FROM_REPLICA)
+        .with_read_after(&WriteToken::default());
+    for _ in 0..50 {
+        let id = writer.insert(ticket_draft!()).unwrap();
+        // A new client, that only knows what the writer told it.
+        let reader = reader.clone().with_read_after(&writer.last_write());
+        assert!(reader.get(id).unwrap().is_some(), "Ticket {id} is missing");
+    }
+    assert_ne!(writer.last_write(), WriteToken::default());
+    server.shutdown();
+}
+
+#[test]
+fn lagging_replicas_catch_up() {
+    // A tiny log: replicas that miss a couple of writes have to copy the whole store.
+    let (client, server) = launch_with(replicated(2, 2));
+    let ids: Vec<_> = (0..500)
+        .map(|_| client.insert(ticket_draft!()).unwrap())
+        .collect();
+    let replicas = caught_up(&server);
+    assert!(replicas.iter().all(|replica| replica.applied == 500));
+
+    let reader = client.with_read_from(ReadFrom::Replica {
+        max_staleness: Duration::ZERO,
+    });
+    assert_eq!(reader.search("").unwrap().len(), ids.len());
+    for id in ids {
+        assert!(reader.get(id).unwrap().is_some());
+    }
+    assert_eq!(replica_reads(&server), 501);
+    server.shutdown();
+}
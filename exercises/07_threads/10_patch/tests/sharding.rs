@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+use std::thread;
+
+use patch::batch::Operation;
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::{launch_sharded, ClientError, ValidationError};
+use ticket_fields::test_helpers::ticket_draft;
+
+fn status_patch(id: patch::store::TicketId, status: Status) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(status),
+    }
+}
+
+#[test]
+fn ids_are_unique_across_shards() {
+    let (client, server) = launch_sharded(4, 10);
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let client = client.clone();
+            thread::spawn(move || {
+                (0..25)
+                    .map(|_| client.insert(ticket_draft!()).unwrap())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let ids: HashSet<_> = handles
+        .into_iter()
+        .flat_map(|handle| handle.join().unwrap())
+        .collect();
+    assert_eq!(ids.len(), 100);
+
+    drop(client);
+    let stores = server.shutdown();
+    assert_eq!(stores.len(), 4);
+    assert_eq!(stores.iter().map(|store| store.len()).sum::<usize>(), 100);
+    // Inserts are spread evenly across the shards.
+    assert!(stores.iter().all(|store| store.len() == 25));
+}
+
+#[test]
+fn reads_and_updates_reach_the_owning_shard() {
+    let (client, server) = launch_sharded(3, 10);
+    let ids: Vec<_> = (0..6)
+        .map(|_| client.insert(ticket_draft!()).unwrap())
+        .collect();
+
+    for &id in &ids {
+        client.update(status_patch(id, Status::Done)).unwrap();
+        let ticket = client.get(id).unwrap().unwrap();
+        assert_eq!(ticket.id, id);
+        assert_eq!(ticket.status, Status::Done);
+    }
+
+    for (shard, store) in server.shutdown().into_iter().enumerate() {
+        for ticket in store.iter() {
+            assert_eq!(ticket.id.value() % 3, shard as u64);
+        }
+    }
+}
+
+#[test]
+fn a_batch_must_stay_on_one_shard() {
+    let (client, _server) = launch_sharded(2, 10);
+    let first = client.insert(ticket_draft!()).unwrap();
+    let second = client.insert(ticket_draft!()).unwrap();
+
+    let error = client
+        .batch(vec![
+            Operation::Update(status_patch(first, Status::Done)),
+            Operation::Update(status_patch(second, Status::Done)),
+        ])
+        .unwrap_err();
+    assert_eq!(
+        error,
+        ClientError::Validation(ValidationError::CrossShardBatch)
+    );
+
+    client
+        .batch(vec![
+            Operation::Update(status_patch(first, Status::Done)),
+            Operation::Insert(ticket_draft!()),
+        ])
+        .unwrap();
+    assert_eq!(client.get(first).unwrap().unwrap().status, Status::Done);
+}
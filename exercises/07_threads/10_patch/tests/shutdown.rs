@@ -0,0 +1,71 @@
+use std::sync::mpsc::sync_channel;
+use std::thread;
+use std::time::Duration;
+
+use patch::data::TicketDraft;
+use patch::{launch, ClientError};
+use ticket_fields::test_helpers::{ticket_draft, ticket_title};
+
+#[test]
+fn shutdown_returns_the_final_store() {
+    let (client, server) = launch(5);
+    let id = client.insert(ticket_draft!()).unwrap();
+
+    let store = server.shutdown();
+    assert_eq!(store.len(), 1);
+    assert_eq!(store.get(id).unwrap().title, ticket_title());
+
+    assert_eq!(client.insert(ticket_draft!()), Err(ClientError::ServerGone));
+}
+
+#[test]
+fn shutdown_with_an_idle_queue_does_not_hang() {
+    let (client, server) = launch(5);
+    let store = server.shutdown();
+    assert!(store.is_empty());
+    drop(client);
+}
+
+#[test]
+fn shutdown_drains_queued_commands() {
+    let capacity = 100;
+    let (client, server) = launch(capacity);
+
+    // Fire-and-forget a bunch of inserts from another thread, then shut down
+    // while they're (likely) still queued.
+    let (done_sender, done_receiver) = sync_channel(0);
+    let producer = {
+        let client = client.clone();
+        thread::spawn(move || {
+            let mut accepted = 0;
+            let mut handles = Vec::new();
+            for _ in 0..capacity {
+                let client = client.clone();
+                handles.push(thread::spawn(move || client.insert(ticket_draft!())));
+            }
+            done_sender.send(()).unwrap();
+            for handle in handles {
+                if handle.join().unwrap().is_ok() {
+                    accepted += 1;
+                }
+            }
+            accepted
+        })
+    };
+    done_receiver.recv().unwrap();
+
+    let store = server.shutdown();
+    let accepted = producer.join().unwrap();
+    // Every insert that was acknowledged made it into the final store.
+    assert_eq!(store.len(), accepted);
+}
+
+#[test]
+fn shutdown_timeout_returns_the_final_store() {
+    let (client, server) = launch(5);
+    client.insert(ticket_draft!()).unwrap();
+
+    let report = server.shutdown_timeout(Duration::from_secs(1));
+    assert_eq!(report.store.len(), 1);
+    assert_eq!(report.dropped_commands, 0);
+}
@@ -0,0 +1,253 @@
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::thread;
+use std::time::Duration;
+
+use patch::backpressure::Backpressure;
+use patch::batch::Operation;
+use patch::cancel::CancelHandle;
+use patch::csv_io::Column;
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::events::{EventFilter, SubscriptionError, TicketEvent};
+use patch::limits::{ClientLimits, Limits, Quota};
+use patch::socket::{ProtocolError, SocketClient, SocketServer, PROTOCOL_VERSION};
+use patch::{launch, launch_with, ClientError, Config, ServerHandle};
+use tempfile::TempDir;
+use ticket_fields::test_helpers::{ticket_draft, ticket_title};
+
+/// A store served on a socket in a fresh temporary directory.
+fn serve(config: Config) -> (TempDir, SocketServer, ServerHandle) {
+    let dir = tempfile::tempdir().unwrap();
+    let (client, server) = launch_with(config);
+    let socket = SocketServer::bind(dir.path().join("tickets.sock"), client).unwrap();
+    (dir, socket, server)
+}
+
+#[test]
+fn remote_calls_reach_the_store() {
+    let (_dir, socket, server) = serve(Config::new(16));
+    let client = SocketClient::connect(socket.path()).unwrap();
+
+    let id = client.insert(ticket_draft!()).unwrap();
+    let ticket = client.get(id).unwrap().unwrap();
+    assert_eq!(ticket.status, Status::ToDo);
+    client
+        .update(TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(Status::Done),
+        })
+        .unwrap();
+    assert_eq!(client.get(id).unwrap().unwrap().status, Status::Done);
+
+    let outcomes = client
+        .batch(vec![
+            Operation::Insert(ticket_draft!()),
+            Operation::Insert(ticket_draft!()),
+        ])
+        .unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(client.search(ticket_title().as_str()).unwrap().len(), 3);
+    let csv = client.export_csv(&Column::defaults()).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 4);
+
+    socket.shutdown();
+    assert_eq!(server.shutdown().len(), 3);
+}
+
+#[test]
+fn errors_travel_back_to_the_caller() {
+    let (_dir, socket, server) = serve(Config::new(16));
+    let client = SocketClient::connect(socket.path()).unwrap();
+    let id = client.insert(ticket_draft!()).unwrap();
+    let stale = client.get(id).unwrap().unwrap();
+    let patch = |status| TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(status),
+    };
+    client.update(patch(Status::InProgress)).unwrap();
+
+    let error = client
+        .update_if_unchanged(stale, patch(Status::Done))
+        .unwrap_err();
+    assert_eq!(error, ClientError::Conflict(id));
+
+    let computed = Column::Computed {
+        header: "shouting".into(),
+        value: |ticket| ticket.title.as_str().to_uppercase(),
+    };
+    let error = client.export_csv(&[computed]).unwrap_err();
+    assert_eq!(
+        error,
+        ClientError::Protocol(ProtocolError::UnsupportedColumn("shouting".into()))
+    );
+    socket.shutdown();
+    server.shutdown();
+}
+
+#[test]
+fn identities_are_forwarded_to_the_store() {
+    let mut config = Config::new(16);
+    config.limits = Limits::new(ClientLimits {
+        rate: None,
+        quota: Some(Quota::per_hour(1)),
+    });
+    let (_dir, socket, server) = serve(config);
+    let cron = SocketClient::connect(socket.path())
+        .unwrap()
+        .with_identity("cron");
+    let cli = cron.clone().with_identity("cli");
+
+    cron.insert(ticket_draft!()).unwrap();
+    let error = cron.insert(ticket_draft!()).unwrap_err();
+    assert!(
+        matches!(error, ClientError::RateLimited { .. }),
+        "{error:?}"
+    );
+    cli.insert(ticket_draft!()).unwrap();
+
+    let clients: Vec<_> = server
+        .usage()
+        .into_iter()
+        .map(|usage| usage.client.to_string())
+        .collect();
+    assert_eq!(clients, ["cli", "cron"]);
+    socket.shutdown();
+    server.shutdown();
+}
+
+#[test]
+fn subscriptions_stream_events() {
+    let (_dir, socket, server) = serve(Config::new(16));
+    let client = SocketClient::connect(socket.path()).unwrap();
+    let subscription = client.subscribe(EventFilter::All).unwrap();
+
+    let id = client.insert(ticket_draft!()).unwrap();
+    let event = subscription
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap()
+        .unwrap();
+    assert!(matches!(event, TicketEvent::Created(ticket) if ticket.id == id));
+
+    socket.shutdown();
+    assert_eq!(subscription.recv(), Err(SubscriptionError::Closed));
+    server.shutdown();
+}
+
+#[test]
+fn huge_timeouts_mean_no_timeout() {
+    let (_dir, socket, server) = serve(Config::new(16));
+    let client = SocketClient::connect(socket.path()).unwrap();
+
+    // Too large to add to the current time on the server's side.
+    let id = client
+        .insert_timeout(ticket_draft!(), Duration::MAX)
+        .unwrap();
+    assert!(client.get_timeout(id, Duration::MAX).unwrap().is_some());
+    socket.shutdown();
+    assert_eq!(server.shutdown().len(), 1);
+}
+
+#[test]
+fn clients_notice_when_the_server_goes_away() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tickets.sock");
+    let (store_client, server) = launch(16);
+    let socket = SocketServer::bind(&path, store_client.clone()).unwrap();
+    let client = SocketClient::connect(&path).unwrap();
+    client.insert(ticket_draft!()).unwrap();
+
+    socket.shutdown();
+    assert!(!path.exists());
+    assert_eq!(client.insert(ticket_draft!()), Err(ClientError::ServerGone));
+
+    // The next call reconnects.
+    let _socket = SocketServer::bind(&path, store_client).unwrap();
+    client.insert(ticket_draft!()).unwrap();
+    drop(_socket);
+    assert_eq!(server.shutdown().len(), 2);
+}
+
+#[test]
+fn peers_with_another_protocol_version_are_turned_away() {
+    let (_dir, socket, server) = serve(Config::new(16));
+    let mut stream = UnixStream::connect(socket.path()).unwrap();
+    let frame = [0, 0, 0, 2, PROTOCOL_VERSION + 1, 0];
+    stream.write_all(&frame).unwrap();
+
+    // The server answers in its own version, then hangs up.
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    assert_eq!(response[4], PROTOCOL_VERSION);
+    assert!(response.len() > 5);
+    socket.shutdown();
+    server.shutdown();
+}
+
+/// A socket that accepts connections, reads whatever it's sent and never answers.
+fn silent_server(dir: &TempDir) -> std::path::PathBuf {
+    let path = dir.path().join("silent.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            thread::spawn(move || std::io::copy(&mut stream, &mut std::io::sink()));
+        }
+    });
+    path
+}
+
+#[test]
+fn pending_calls_can_be_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let cancel = CancelHandle::new();
+    let client = SocketClient::connect(silent_server(&dir))
+        .unwrap()
+        .with_cancel(cancel.clone());
+    assert!(client.cancel_handle().is_some());
+
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        cancel.cancel();
+    });
+    assert_eq!(client.insert(ticket_draft!()), Err(ClientError::Cancelled));
+    canceller.join().unwrap();
+    // Cancelled for good.
+    assert_eq!(client.insert(ticket_draft!()), Err(ClientError::Cancelled));
+}
+
+#[test]
+fn calls_give_up_after_the_client_timeout() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = SocketClient::connect(silent_server(&dir))
+        .unwrap()
+        .with_timeout(Duration::from_millis(50));
+    assert_eq!(client.timeout(), Some(Duration::from_millis(50)));
+    assert_eq!(client.search(""), Err(ClientError::Timeout));
+}
+
+#[test]
+fn remote_clients_pick_their_own_options() {
+    let (_dir, socket, server) = serve(Config::new(16));
+    let client = SocketClient::connect(socket.path())
+        .unwrap()
+        .with_backpressure(Backpressure::FailFast)
+        .with_timeout(Duration::from_secs(60));
+    assert_eq!(client.backpressure(), Some(Backpressure::FailFast));
+
+    let id = client.insert(ticket_draft!()).unwrap();
+    assert!(client.get(id).unwrap().is_some());
+    let cancel = CancelHandle::new();
+    cancel.cancel();
+    let cancelled = client.clone().with_cancel(cancel);
+    assert_eq!(
+        cancelled.insert(ticket_draft!()),
+        Err(ClientError::Cancelled)
+    );
+    // The clones that weren't cancelled keep working.
+    client.insert(ticket_draft!()).unwrap();
+    socket.shutdown();
+    assert_eq!(server.shutdown().len(), 2);
+}
@@ -0,0 +1,35 @@
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::store::{StatusCounts, TicketStore};
+use ticket_fields::test_helpers::ticket_draft;
+
+#[test]
+fn tickets_are_counted_by_status() {
+    let mut store = TicketStore::new();
+    assert_eq!(store.status_counts(), StatusCounts::default());
+
+    // Enough tickets to be counted on several threads.
+    let ids: Vec<_> = (0..5_000)
+        .map(|_| store.add_ticket(ticket_draft!()))
+        .collect();
+    for (i, &id) in ids.iter().enumerate() {
+        let status = match i % 5 {
+            0 => Status::Done,
+            1 | 2 => Status::InProgress,
+            _ => continue,
+        };
+        store.update(TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(status),
+        });
+    }
+    assert_eq!(
+        store.status_counts(),
+        StatusCounts {
+            to_do: 2_000,
+            in_progress: 2_000,
+            done: 1_000,
+        }
+    );
+}
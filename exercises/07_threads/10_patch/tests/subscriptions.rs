@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use patch::batch::Operation;
+use patch::data::{Status, TicketDraft, TicketPatch};
+use patch::events::{EventFilter, SubscriptionError, TicketEvent};
+use patch::store::TicketId;
+use patch::{launch, launch_sharded};
+use ticket_fields::test_helpers::{ticket_description, ticket_draft};
+use ticket_fields::TicketTitle;
+
+fn status_patch(id: TicketId, status: Status) -> TicketPatch {
+    TicketPatch {
+        id,
+        title: None,
+        description: None,
+        status: Some(status),
+    }
+}
+
+#[test]
+fn subscribers_see_every_change() {
+    let (client, _server) = launch(10);
+    let events = client.subscribe(EventFilter::All).unwrap();
+
+    let id = client.insert(ticket_draft!()).unwrap();
+    let title = TicketTitle::try_from("A new title").unwrap();
+    client
+        .update(TicketPatch {
+            id,
+            title: Some(title.clone()),
+            description: Some(ticket_description()),
+            status: Some(Status::InProgress),
+        })
+        .unwrap();
+
+    assert!(matches!(events.recv().unwrap(), TicketEvent::Created(ticket) if ticket.id == id));
+    // The description didn't actually change, so it's left out.
+    assert_eq!(
+        events.recv().unwrap(),
+        TicketEvent::Updated {
+            id,
+            title: Some(title),
+            description: None,
+        }
+    );
+    assert_eq!(
+        events.recv().unwrap(),
+        TicketEvent::StatusChanged {
+            id,
+            from: Status::ToDo,
+            to: Status::InProgress,
+        }
+    );
+    assert_eq!(events.try_recv(), Ok(None));
+}
+
+#[test]
+fn failed_commands_emit_nothing() {
+    let (client, _server) = launch(10);
+    let id = client.insert(ticket_draft!()).unwrap();
+    let events = client.subscribe(EventFilter::All).unwrap();
+
+    client
+        .batch(vec![
+            Operation::Update(status_patch(id, Status::Done)),
+            Operation::Update(status_patch("99".parse().unwrap(), Status::Done)),
+        ])
+        .unwrap_err();
+    client.get(id).unwrap();
+    assert_eq!(events.try_recv(), Ok(None));
+}
+
+#[test]
+fn filters_select_events() {
+    let (client, _server) = launch(10);
+    let first = client.insert(ticket_draft!()).unwrap();
+    let second = client.insert(ticket_draft!()).unwrap();
+    let ticket_events = client.subscribe(EventFilter::Ticket(second)).unwrap();
+    let done_events = client.subscribe(EventFilter::Status(Status::Done)).unwrap();
+
+    client.update(status_patch(first, Status::Done)).unwrap();
+    client
+        .update(status_patch(second, Status::InProgress))
+        .unwrap();
+
+    assert_eq!(ticket_events.recv().unwrap().id(), second);
+    assert_eq!(ticket_events.try_recv(), Ok(None));
+    assert_eq!(done_events.recv().unwrap().id(), first);
+    assert_eq!(done_events.try_recv(), Ok(None));
+}
+
+#[test]
+fn slow_subscribers_are_told_they_lagged() {
+    let (client, _server) = launch(10);
+    let events = client.subscribe_with_buffer(EventFilter::All, 2).unwrap();
+
+    let ids: Vec<_> = (0..5)
+        .map(|_| client.insert(ticket_draft!()).unwrap())
+        .collect();
+    assert_eq!(events.recv().unwrap().id(), ids[0]);
+    assert_eq!(events.recv().unwrap().id(), ids[1]);
+
+    // The server kept going, dropping what didn't fit in the buffer.
+    let id = client.insert(ticket_draft!()).unwrap();
+    assert_eq!(events.recv(), Err(SubscriptionError::Lagged(3)));
+    assert_eq!(events.recv().unwrap().id(), id);
+}
+
+#[test]
+fn sharded_servers_publish_into_one_subscription() {
+    let (client, server) = launch_sharded(3, 10);
+    let events = client.subscribe(EventFilter::All).unwrap();
+
+    for _ in 0..6 {
+        client.insert(ticket_draft!()).unwrap();
+    }
+    let mut ids: Vec<_> = (0..6)
+        .map(|_| events.recv().unwrap().id().value())
+        .collect();
+    ids.sort();
+    assert_eq!(ids, [0, 1, 2, 3, 4, 5]);
+
+    drop(client);
+    server.shutdown();
+    assert_eq!(
+        events.recv_timeout(Duration::from_secs(1)),
+        Err(SubscriptionError::Closed)
+    );
+}
+
+#[test]
+fn tickets_updated_twice_by_a_batch_get_one_set_of_events() {
+    let (client, _server) = launch(10);
+    let id = client.insert(ticket_draft!()).unwrap();
+    let events = client.subscribe(EventFilter::All).unwrap();
+
+    client
+        .batch(vec![
+            Operation::Update(status_patch(id, Status::InProgress)),
+            Operation::Update(status_patch(id, Status::Done)),
+        ])
+        .unwrap();
+    let marker = client.insert(ticket_draft!()).unwrap();
+
+    assert_eq!(
+        events.recv().unwrap(),
+        TicketEvent::StatusChanged {
+            id,
+            from: Status::ToDo,
+            to: Status::Done,
+        }
+    );
+    assert!(matches!(events.recv().unwrap(), TicketEvent::Created(ticket) if ticket.id == marker));
+}
@@ -1,14 +1,13 @@
-// TODO: Fill in the missing methods for `TicketStore`.
-//  Notice how we no longer need a separate update command: `Get` now returns a handle to the ticket
-//  which allows the caller to both modify and read the ticket.
-use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
-use std::sync::{Arc, Mutex};
-
+// Notice how we no longer need a separate update command: `Get` now returns a handle to the ticket
+// which allows the caller to both modify and read the ticket.
 use crate::data::{Ticket, TicketDraft};
 use crate::store::{TicketId, TicketStore};
+use crate::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use crate::sync::{thread, Arc, Mutex};
 
 pub mod data;
 pub mod store;
+use loom_sync as sync;
 
 #[derive(Clone)]
 pub struct TicketStoreClient {
@@ -45,11 +44,11 @@ pub struct OverloadedError;
 
 pub fn launch(capacity: usize) -> TicketStoreClient {
     let (sender, receiver) = sync_channel(capacity);
-    std::thread::spawn(move || server(receiver));
+    thread::spawn(move || server(receiver));
     TicketStoreClient { sender }
 }
 
-enum Command {
+pub enum Command {
     Insert {
         draft: TicketDraft,
         response_channel: SyncSender<TicketId>,
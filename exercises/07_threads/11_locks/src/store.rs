@@ -1,11 +1,11 @@
 use crate::data::{Status, Ticket, TicketDraft};
+use crate::sync::{Arc, Mutex};
 use std::collections::BTreeMap;
-use std::sync::{Arc, Mutex};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TicketId(u64);
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct TicketStore {
     tickets: BTreeMap<TicketId, Arc<Mutex<Ticket>>>,
     counter: u64,
@@ -13,10 +13,7 @@ pub struct TicketStore {
 
 impl TicketStore {
     pub fn new() -> Self {
-        Self {
-            tickets: BTreeMap::new(),
-            counter: 0,
-        }
+        Self::default()
     }
 
     pub fn add_ticket(&mut self, ticket: TicketDraft) -> TicketId {
@@ -28,13 +25,13 @@ impl TicketStore {
             description: ticket.description,
             status: Status::ToDo,
         };
-        todo!();
+        self.tickets.insert(id, Arc::new(Mutex::new(ticket)));
         id
     }
 
     // The `get` method should return a handle to the ticket
     // which allows the caller to either read or modify the ticket.
-    pub fn get(&self, id: TicketId) -> Option<todo!()> {
-        todo!()
+    pub fn get(&self, id: TicketId) -> Option<Arc<Mutex<Ticket>>> {
+        self.tickets.get(&id).cloned()
     }
 }
@@ -0,0 +1,77 @@
+//! Model checks for the store, exploring every interleaving of the client and server threads.
+//!
+//! Run with:
+//!
+//! ```bash
+//! RUSTFLAGS="--cfg loom" cargo test --release --test loom
+//! ```
+//!
+//! `loom` fails the test if any interleaving deadlocks.
+#![cfg(loom)]
+
+use locks::data::{Status, TicketDraft};
+use locks::store::TicketId;
+use locks::{launch, TicketStoreClient};
+use loom_sync::{model, thread};
+use ticket_fields::test_helpers::ticket_draft;
+use ticket_fields::TicketDescription;
+
+#[test]
+fn concurrent_inserts_get_unique_ids() {
+    model(|| {
+        let client = launch(2);
+        let other = thread::spawn({
+            let client = client.clone();
+            move || client.insert(ticket_draft!()).unwrap()
+        });
+        let mine = client.insert(ticket_draft!()).unwrap();
+        let theirs = other.join().unwrap();
+        assert_ne!(mine, theirs);
+        assert!(client.get(mine).unwrap().is_some());
+        assert!(client.get(theirs).unwrap().is_some());
+    });
+}
+
+/// Read-modify-write the ticket's description through the handle returned by `get`.
+fn append(client: &TicketStoreClient, id: TicketId, suffix: &str) {
+    let ticket = client.get(id).unwrap().unwrap();
+    let mut ticket = ticket.lock().unwrap();
+    let description = format!("{} {suffix}", ticket.description.as_str());
+    ticket.description = TicketDescription::try_from(description).unwrap();
+}
+
+#[test]
+fn concurrent_updates_are_not_lost() {
+    model(|| {
+        let client = launch(2);
+        let id = client.insert(ticket_draft!()).unwrap();
+        let other = thread::spawn({
+            let client = client.clone();
+            move || append(&client, id, "theirs")
+        });
+        append(&client, id, "mine");
+        other.join().unwrap();
+
+        let ticket = client.get(id).unwrap().unwrap();
+        let ticket = ticket.lock().unwrap();
+        assert!(ticket.description.as_str().contains("mine"));
+        assert!(ticket.description.as_str().contains("theirs"));
+        assert_eq!(ticket.status, Status::ToDo);
+    });
+}
+
+#[test]
+fn reads_race_with_inserts() {
+    model(|| {
+        let client = launch(2);
+        let id = client.insert(ticket_draft!()).unwrap();
+        let other = thread::spawn({
+            let client = client.clone();
+            move || client.insert(ticket_draft!()).unwrap()
+        });
+        let ticket = client.get(id).unwrap().unwrap();
+        assert_eq!(ticket.lock().unwrap().id, id);
+        let inserted = other.join().unwrap();
+        assert_ne!(inserted, id);
+    });
+}
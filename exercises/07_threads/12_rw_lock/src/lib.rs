@@ -1,87 +1,242 @@
-// TODO: Replace `Mutex` with `RwLock` in the `TicketStore` struct and
-//  all other relevant places to allow multiple readers to access the ticket store concurrently.
-use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
-use std::sync::{Arc, Mutex};
-
 use crate::data::{Ticket, TicketDraft};
-use crate::store::{TicketId, TicketStore};
+use crate::priority::{ClassLoad, Config, Load, Priority, Scheduler};
+use crate::store::{TicketHandle, TicketId, TicketStore};
+use crate::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+use crate::sync::{thread, Arc};
+use allocations::Allocations;
+use std::time::Duration;
 
 pub mod data;
+pub mod priority;
 pub mod store;
+use loom_sync as sync;
 
+/// A handle to the store.
+///
+/// Each [`Priority`] class has its own bounded queue: a burst of bulk inserts can
+/// fill up the bulk queue, but interactive reads keep going through theirs.
 #[derive(Clone)]
 pub struct TicketStoreClient {
-    sender: SyncSender<Command>,
+    queues: [SyncSender<Command>; 3],
+    // One token per queued command, so that the server can sleep until there is work
+    // in any of the queues.
+    wake: Sender<()>,
+    load: Arc<Load>,
+    allocations: Allocations,
 }
 
 impl TicketStoreClient {
     pub fn insert(&self, draft: TicketDraft) -> Result<TicketId, OverloadedError> {
         let (response_sender, response_receiver) = sync_channel(1);
-        self.sender
-            .try_send(Command::Insert {
-                draft,
-                response_channel: response_sender,
-            })
-            .map_err(|_| OverloadedError)?;
+        self.send(Command::Insert {
+            draft,
+            response_channel: response_sender,
+        })?;
         Ok(response_receiver.recv().unwrap())
     }
 
-    pub fn get(&self, id: TicketId) -> Result<Option<Arc<Mutex<Ticket>>>, OverloadedError> {
+    /// Insert all `drafts` with a single command, at [`Priority::Bulk`].
+    pub fn insert_bulk(&self, drafts: Vec<TicketDraft>) -> Result<Vec<TicketId>, OverloadedError> {
         let (response_sender, response_receiver) = sync_channel(1);
-        self.sender
-            .try_send(Command::Get {
-                id,
-                response_channel: response_sender,
-            })
-            .map_err(|_| OverloadedError)?;
+        self.send(Command::BulkInsert {
+            drafts,
+            response_channel: response_sender,
+        })?;
+        Ok(response_receiver.recv().unwrap())
+    }
+
+    pub fn get(&self, id: TicketId) -> Result<Option<TicketHandle>, OverloadedError> {
+        let (response_sender, response_receiver) = sync_channel(1);
+        self.send(Command::Get {
+            id,
+            response_channel: response_sender,
+        })?;
         Ok(response_receiver.recv().unwrap())
     }
+
+    /// Block until `predicate` holds for the ticket with this `id`, then return a copy
+    /// of it, see [`TicketHandle::wait_for`].
+    ///
+    /// Only looking the ticket up goes through the server: the waiting happens on the
+    /// caller's thread, and the server keeps handling other commands in the meantime.
+    pub fn wait_for(
+        &self,
+        id: TicketId,
+        predicate: impl FnMut(&Ticket) -> bool,
+        timeout: Duration,
+    ) -> Result<Ticket, WaitError> {
+        self.get(id)?
+            .ok_or(WaitError::NotFound(id))?
+            .wait_for(predicate, timeout)
+    }
+
+    /// How busy the queue of the `priority` class is.
+    pub fn load(&self, priority: Priority) -> ClassLoad {
+        self.load.of(priority)
+    }
+
+    /// The tickets created by the server and not dropped yet.
+    ///
+    /// Once the server has shut down, check that every handle returned by
+    /// [`get`](Self::get) was released with
+    /// [`assert_all_released`](allocations::assert_all_released).
+    pub fn allocations(&self) -> &Allocations {
+        &self.allocations
+    }
+
+    fn send(&self, command: Command) -> Result<(), OverloadedError> {
+        let priority = command.priority();
+        // Count the command before sending it, the server may be done with it right away.
+        self.load.enqueued(priority);
+        if self.queues[priority.index()].try_send(command).is_err() {
+            self.load.handled(priority);
+            self.load.rejected(priority);
+            return Err(OverloadedError { priority });
+        }
+        // The server only goes away once every client is gone.
+        let _ = self.wake.send(());
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("The store is overloaded: the {priority} queue is full")]
+pub struct OverloadedError {
+    pub priority: Priority,
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("The store is overloaded")]
-pub struct OverloadedError;
+pub enum WaitError {
+    #[error("There is no ticket with id {0:?}")]
+    NotFound(TicketId),
+    #[error("Ticket {0:?} didn't reach the expected state in time")]
+    Timeout(TicketId),
+    #[error(transparent)]
+    Overloaded(#[from] OverloadedError),
+}
 
+/// Start a server where every priority class can queue up to `capacity` commands.
 pub fn launch(capacity: usize) -> TicketStoreClient {
-    let (sender, receiver) = sync_channel(capacity);
-    std::thread::spawn(move || server(receiver));
-    TicketStoreClient { sender }
+    launch_with(Config::new(capacity))
+}
+
+pub fn launch_with(config: Config) -> TicketStoreClient {
+    let (client, queues) = channels(config);
+    thread::spawn(move || server(queues));
+    client
+}
+
+/// The queues between clients and a server, without starting the server:
+/// hand the [`ServerQueues`] over to [`server`] when you are ready.
+pub fn channels(config: Config) -> (TicketStoreClient, ServerQueues) {
+    let (interactive_sender, interactive) = sync_channel(config.interactive.capacity);
+    let (write_sender, write) = sync_channel(config.write.capacity);
+    let (bulk_sender, bulk) = sync_channel(config.bulk.capacity);
+    let (wake_sender, wake) = channel();
+    let load = Arc::new(Load::new(&config));
+    let allocations = Allocations::new();
+    let client = TicketStoreClient {
+        queues: [interactive_sender, write_sender, bulk_sender],
+        wake: wake_sender,
+        load: Arc::clone(&load),
+        allocations: allocations.clone(),
+    };
+    let queues = ServerQueues {
+        queues: [interactive, write, bulk],
+        wake,
+        pending: [None, None, None],
+        scheduler: Scheduler::new(&config),
+        load,
+        allocations,
+    };
+    (client, queues)
+}
+
+/// The receiving end of the queues created by [`channels`].
+pub struct ServerQueues {
+    queues: [Receiver<Command>; 3],
+    wake: Receiver<()>,
+    // The command at the head of each queue, once it has been taken out of it.
+    pending: [Option<Command>; 3],
+    scheduler: Scheduler,
+    load: Arc<Load>,
+    allocations: Allocations,
+}
+
+impl ServerQueues {
+    /// Wait for the next command to handle, or `None` once every client is gone.
+    fn next(&mut self) -> Option<Command> {
+        self.wake.recv().ok()?;
+        // Look at the head of every queue, then pick one of them. There is a command
+        // somewhere for every token, so at least one of them is there.
+        for (queue, head) in self.queues.iter().zip(self.pending.iter_mut()) {
+            if head.is_none() {
+                *head = queue.try_recv().ok();
+            }
+        }
+        let priority = self
+            .scheduler
+            .pick(|priority| self.pending[priority.index()].is_some())?;
+        self.load.handled(priority);
+        self.pending[priority.index()].take()
+    }
 }
 
-enum Command {
+pub enum Command {
     Insert {
         draft: TicketDraft,
         response_channel: SyncSender<TicketId>,
     },
+    BulkInsert {
+        drafts: Vec<TicketDraft>,
+        response_channel: SyncSender<Vec<TicketId>>,
+    },
     Get {
         id: TicketId,
-        response_channel: SyncSender<Option<Arc<Mutex<Ticket>>>>,
+        response_channel: SyncSender<Option<TicketHandle>>,
     },
 }
 
-pub fn server(receiver: Receiver<Command>) {
-    let mut store = TicketStore::new();
-    loop {
-        match receiver.recv() {
-            Ok(Command::Insert {
+impl Command {
+    pub fn priority(&self) -> Priority {
+        match self {
+            Command::Get { .. } => Priority::Interactive,
+            Command::Insert { .. } => Priority::Write,
+            Command::BulkInsert { .. } => Priority::Bulk,
+        }
+    }
+}
+
+pub fn server(mut queues: ServerQueues) {
+    let mut store = TicketStore::with_allocations(queues.allocations.clone());
+    // `next` returns `None` when there are no more clients, so we can safely
+    // shut down the server.
+    while let Some(command) = queues.next() {
+        match command {
+            Command::Insert {
                 draft,
                 response_channel,
-            }) => {
+            } => {
                 let id = store.add_ticket(draft);
                 let _ = response_channel.send(id);
             }
-            Ok(Command::Get {
+            Command::BulkInsert {
+                drafts,
+                response_channel,
+            } => {
+                let ids = drafts
+                    .into_iter()
+                    .map(|draft| store.add_ticket(draft))
+                    .collect();
+                let _ = response_channel.send(ids);
+            }
+            Command::Get {
                 id,
                 response_channel,
-            }) => {
+            } => {
                 let ticket = store.get(id);
                 let _ = response_channel.send(ticket);
             }
-            Err(_) => {
-                // There are no more senders, so we can safely break
-                // and shut down the server.
-                break;
-            }
         }
     }
 }
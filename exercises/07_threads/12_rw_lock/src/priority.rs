@@ -0,0 +1,190 @@
+use std::fmt;
+
+use crate::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// How urgently a command must be handled.
+///
+/// Each class has its own queue, so a burst of commands in one class can't
+/// fill up the queue of another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Reads a user is waiting on.
+    Interactive,
+    Write,
+    /// Large imports and other background work.
+    Bulk,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 3] = [Priority::Interactive, Priority::Write, Priority::Bulk];
+
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let priority = match self {
+            Priority::Interactive => "interactive",
+            Priority::Write => "write",
+            Priority::Bulk => "bulk",
+        };
+        f.write_str(priority)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClassConfig {
+    /// How many commands of this class can be queued before the store is overloaded.
+    pub capacity: usize,
+    /// The class's share of the server's time when several classes have work queued:
+    /// a class with weight 2 gets twice as many commands handled as a class with weight 1.
+    pub weight: u32,
+}
+
+/// How to set up a server started with [`launch_with`](crate::launch_with).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub interactive: ClassConfig,
+    pub write: ClassConfig,
+    pub bulk: ClassConfig,
+}
+
+impl Config {
+    /// Every class can queue up to `capacity` commands. Interactive commands get
+    /// four turns for every two writes and every bulk command.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            interactive: ClassConfig {
+                capacity,
+                weight: 4,
+            },
+            write: ClassConfig {
+                capacity,
+                weight: 2,
+            },
+            bulk: ClassConfig {
+                capacity,
+                weight: 1,
+            },
+        }
+    }
+
+    pub fn class(&self, priority: Priority) -> ClassConfig {
+        match priority {
+            Priority::Interactive => self.interactive,
+            Priority::Write => self.write,
+            Priority::Bulk => self.bulk,
+        }
+    }
+}
+
+/// How busy the queue of a priority class is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClassLoad {
+    pub priority: Priority,
+    pub capacity: usize,
+    /// Commands waiting to be handled.
+    pub queued: usize,
+    /// Commands turned away because the queue was full, since the server started.
+    pub rejected: u64,
+}
+
+/// The per-class counters shared by the clients and the server.
+pub(crate) struct Load {
+    capacity: [usize; 3],
+    queued: [AtomicUsize; 3],
+    rejected: [AtomicU64; 3],
+}
+
+impl Load {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            capacity: Priority::ALL.map(|priority| config.class(priority).capacity),
+            queued: Default::default(),
+            rejected: Default::default(),
+        }
+    }
+
+    pub(crate) fn enqueued(&self, priority: Priority) {
+        self.queued[priority.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn handled(&self, priority: Priority) {
+        self.queued[priority.index()].fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn rejected(&self, priority: Priority) {
+        self.rejected[priority.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn of(&self, priority: Priority) -> ClassLoad {
+        let index = priority.index();
+        ClassLoad {
+            priority,
+            capacity: self.capacity[index],
+            queued: self.queued[index].load(Ordering::Relaxed),
+            rejected: self.rejected[index].load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Smooth weighted round-robin: over any window, each class with work queued
+/// is picked in proportion to its weight, and picks of the same class are spread out
+/// rather than bunched together.
+pub(crate) struct Scheduler {
+    weights: [i64; 3],
+    current: [i64; 3],
+}
+
+impl Scheduler {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            weights: Priority::ALL.map(|priority| i64::from(config.class(priority).weight)),
+            current: [0; 3],
+        }
+    }
+
+    /// The class to serve next, among those for which `ready` returns true.
+    pub(crate) fn pick(&mut self, ready: impl Fn(Priority) -> bool) -> Option<Priority> {
+        let ready: Vec<_> = Priority::ALL.into_iter().filter(|p| ready(*p)).collect();
+        let total: i64 = ready.iter().map(|p| self.weights[p.index()]).sum();
+        for p in &ready {
+            self.current[p.index()] += self.weights[p.index()];
+        }
+        // Ties go to the more urgent class.
+        let chosen = *ready.iter().rev().max_by_key(|p| self.current[p.index()])?;
+        self.current[chosen.index()] -= total;
+        Some(chosen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classes_are_served_in_proportion_to_their_weight() {
+        let mut scheduler = Scheduler::new(&Config::new(10));
+        let picks: Vec<_> = (0..7).map(|_| scheduler.pick(|_| true).unwrap()).collect();
+        let count = |priority| picks.iter().filter(|p| **p == priority).count();
+        assert_eq!(count(Priority::Interactive), 4);
+        assert_eq!(count(Priority::Write), 2);
+        assert_eq!(count(Priority::Bulk), 1);
+        // Interactive commands don't all go first.
+        assert_ne!(picks[..4], [Priority::Interactive; 4]);
+    }
+
+    #[test]
+    fn idle_classes_are_skipped() {
+        let mut scheduler = Scheduler::new(&Config::new(10));
+        for _ in 0..5 {
+            assert_eq!(
+                scheduler.pick(|p| p == Priority::Bulk),
+                Some(Priority::Bulk)
+            );
+        }
+        assert_eq!(scheduler.pick(|_| false), None);
+    }
+}
@@ -1,24 +1,148 @@
 use crate::data::{Status, Ticket, TicketDraft};
+use crate::sync::{Arc, Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::WaitError;
+use allocations::{Allocations, Tracked};
 use std::collections::BTreeMap;
-use std::sync::{Arc, Mutex};
+use std::ops::{Deref, DerefMut};
+use std::sync::{LockResult, PoisonError};
+use std::time::{Duration, Instant};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TicketId(u64);
 
+/// A shared handle to a ticket: lock it to read or modify the ticket,
+/// or [`wait_for`](Self::wait_for) someone else to modify it.
+///
+/// Tickets are counted by the store's [`Allocations`], so that tests can check
+/// that no handle outlives the server.
 #[derive(Clone)]
+pub struct TicketHandle(Arc<Slot>);
+
+struct Slot {
+    ticket: RwLock<Tracked<Ticket>>,
+    /// Held by waiters while they check the ticket, and by writers once they are
+    /// done, to notify `changed`: waiters can't miss a write.
+    waiting: Mutex<()>,
+    changed: Condvar,
+}
+
+/// Write access to a ticket. Wakes up the threads waiting for it once dropped.
+pub struct TicketWriteGuard<'a> {
+    /// Only `None` while being dropped.
+    guard: Option<RwLockWriteGuard<'a, Tracked<Ticket>>>,
+    slot: &'a Slot,
+}
+
+impl TicketHandle {
+    fn new(ticket: Tracked<Ticket>) -> Self {
+        Self(Arc::new(Slot {
+            ticket: RwLock::new(ticket),
+            waiting: Mutex::new(()),
+            changed: Condvar::new(),
+        }))
+    }
+
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, Tracked<Ticket>>> {
+        self.0.ticket.read()
+    }
+
+    pub fn write(&self) -> LockResult<TicketWriteGuard<'_>> {
+        let guarded = |guard| TicketWriteGuard {
+            guard: Some(guard),
+            slot: &self.0,
+        };
+        match self.0.ticket.write() {
+            Ok(guard) => Ok(guarded(guard)),
+            Err(poisoned) => Err(PoisonError::new(guarded(poisoned.into_inner()))),
+        }
+    }
+
+    /// Block until `predicate` holds for the ticket, then return a copy of it.
+    /// Gives up with [`WaitError::Timeout`] after `timeout`.
+    ///
+    /// `predicate` is checked right away, then again after every write.
+    /// Don't hold a write guard on the ticket while waiting: nobody else could change it.
+    pub fn wait_for(
+        &self,
+        mut predicate: impl FnMut(&Ticket) -> bool,
+        timeout: Duration,
+    ) -> Result<Ticket, WaitError> {
+        // `None` if too far in the future to ever be reached, e.g. for `Duration::MAX`.
+        let deadline = Instant::now().checked_add(timeout);
+        let mut waiting = self.0.waiting.lock().unwrap();
+        loop {
+            let id = {
+                let ticket = self.read().unwrap();
+                if predicate(&ticket) {
+                    return Ok(Ticket::clone(&ticket));
+                }
+                ticket.id
+            };
+            waiting = match deadline {
+                None => self.0.changed.wait(waiting).unwrap(),
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(WaitError::Timeout(id));
+                    }
+                    self.0.changed.wait_timeout(waiting, remaining).unwrap().0
+                }
+            };
+        }
+    }
+}
+
+impl Deref for TicketWriteGuard<'_> {
+    type Target = Tracked<Ticket>;
+
+    fn deref(&self) -> &Self::Target {
+        self.guard.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for TicketWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard.as_mut().unwrap()
+    }
+}
+
+impl Drop for TicketWriteGuard<'_> {
+    fn drop(&mut self) {
+        // Release the ticket first: waiters read it while holding `waiting`.
+        drop(self.guard.take());
+        let _waiting = self
+            .slot
+            .waiting
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.slot.changed.notify_all();
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct TicketStore {
-    tickets: BTreeMap<TicketId, Arc<Mutex<Ticket>>>,
+    tickets: BTreeMap<TicketId, TicketHandle>,
     counter: u64,
+    allocations: Allocations,
 }
 
 impl TicketStore {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store that counts its tickets in `allocations`.
+    pub fn with_allocations(allocations: Allocations) -> Self {
         Self {
-            tickets: BTreeMap::new(),
-            counter: 0,
+            allocations,
+            ..Self::default()
         }
     }
 
+    pub fn allocations(&self) -> &Allocations {
+        &self.allocations
+    }
+
     pub fn add_ticket(&mut self, ticket: TicketDraft) -> TicketId {
         let id = TicketId(self.counter);
         self.counter += 1;
@@ -28,14 +152,27 @@ impl TicketStore {
             description: ticket.description,
             status: Status::ToDo,
         };
-        let ticket = Arc::new(Mutex::new(ticket));
+        let ticket = TicketHandle::new(self.allocations.track(ticket));
         self.tickets.insert(id, ticket);
         id
     }
 
     // The `get` method should return a handle to the ticket
     // which allows the caller to either read or modify the ticket.
-    pub fn get(&self, id: TicketId) -> Option<Arc<Mutex<Ticket>>> {
+    pub fn get(&self, id: TicketId) -> Option<TicketHandle> {
         self.tickets.get(&id).cloned()
     }
+
+    /// Block until `predicate` holds for the ticket with this `id`, see
+    /// [`TicketHandle::wait_for`].
+    pub fn wait_for(
+        &self,
+        id: TicketId,
+        predicate: impl FnMut(&Ticket) -> bool,
+        timeout: Duration,
+    ) -> Result<Ticket, WaitError> {
+        self.get(id)
+            .ok_or(WaitError::NotFound(id))?
+            .wait_for(predicate, timeout)
+    }
 }
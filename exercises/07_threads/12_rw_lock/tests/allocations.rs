@@ -0,0 +1,57 @@
+use std::thread;
+
+use allocations::assert_all_released;
+use rwlock::data::{Status, Ticket, TicketDraft};
+use rwlock::priority::Config;
+use rwlock::{channels, server};
+use ticket_fields::test_helpers::ticket_draft;
+
+#[test]
+fn all_tickets_are_released_after_shutdown() {
+    let (client, queues) = channels(Config::new(10));
+    let allocations = client.allocations().clone();
+    let server = thread::spawn(move || server(queues));
+
+    let ids = client
+        .insert_bulk(vec![ticket_draft!(), ticket_draft!()])
+        .unwrap();
+    let readers: Vec<_> = ids
+        .iter()
+        .map(|&id| {
+            let client = client.clone();
+            thread::spawn(move || {
+                let ticket = client.get(id).unwrap().unwrap();
+                ticket.write().unwrap().status = Status::Done;
+            })
+        })
+        .collect();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert_eq!(allocations.stats::<Ticket>().live(), 2);
+
+    drop(client);
+    server.join().unwrap();
+    assert_all_released(&allocations);
+    assert_eq!(allocations.stats::<Ticket>().dropped, 2);
+}
+
+#[test]
+fn handles_kept_past_shutdown_are_reported() {
+    let (client, queues) = channels(Config::new(10));
+    let allocations = client.allocations().clone();
+    let server = thread::spawn(move || server(queues));
+
+    let id = client.insert(ticket_draft!()).unwrap();
+    let leaked = client.get(id).unwrap().unwrap();
+    drop(client);
+    server.join().unwrap();
+
+    let leaks = allocations.leaks();
+    assert_eq!(leaks.len(), 1);
+    assert_eq!(leaks[0].live(), 1);
+    assert!(leaks[0].type_name.ends_with("Ticket"));
+
+    drop(leaked);
+    assert_all_released(&allocations);
+}
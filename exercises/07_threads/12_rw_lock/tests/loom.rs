@@ -0,0 +1,112 @@
+//! Model checks for the store, exploring every interleaving of the client and server threads.
+//!
+//! Run with:
+//!
+//! ```bash
+//! RUSTFLAGS="--cfg loom" cargo test --release --test loom
+//! ```
+//!
+//! `loom` fails the test if any interleaving deadlocks.
+#![cfg(loom)]
+
+use std::time::Duration;
+
+use loom_sync::{model, thread};
+use rwlock::data::{Status, TicketDraft};
+use rwlock::store::TicketId;
+use rwlock::{launch, TicketStoreClient};
+use ticket_fields::test_helpers::ticket_draft;
+use ticket_fields::TicketTitle;
+
+#[test]
+fn inserts_and_bulk_inserts_get_unique_ids() {
+    model(|| {
+        let client = launch(2);
+        let other = thread::spawn({
+            let client = client.clone();
+            move || {
+                client
+                    .insert_bulk(vec![ticket_draft!(), ticket_draft!()])
+                    .unwrap()
+            }
+        });
+        let mine = client.insert(ticket_draft!()).unwrap();
+        let theirs = other.join().unwrap();
+        assert!(!theirs.contains(&mine));
+        assert_ne!(theirs[0], theirs[1]);
+        assert!(client.get(mine).unwrap().is_some());
+    });
+}
+
+#[test]
+fn readers_never_see_half_an_update() {
+    model(|| {
+        let client = launch(2);
+        let id = client.insert(ticket_draft!()).unwrap();
+        let writer = thread::spawn({
+            let client = client.clone();
+            move || {
+                let ticket = client.get(id).unwrap().unwrap();
+                let mut ticket = ticket.write().unwrap();
+                ticket.title = TicketTitle::try_from("Done").unwrap();
+                ticket.status = Status::Done;
+            }
+        });
+        let ticket = client.get(id).unwrap().unwrap();
+        {
+            let ticket = ticket.read().unwrap();
+            let done = ticket.status == Status::Done;
+            assert_eq!(ticket.title.as_str() == "Done", done);
+        }
+        writer.join().unwrap();
+        assert_eq!(ticket.read().unwrap().status, Status::Done);
+    });
+}
+
+/// Move the ticket one step forward through the handle returned by `get`.
+fn advance(client: &TicketStoreClient, id: TicketId) {
+    let ticket = client.get(id).unwrap().unwrap();
+    let mut ticket = ticket.write().unwrap();
+    ticket.status = match ticket.status {
+        Status::ToDo => Status::InProgress,
+        Status::InProgress | Status::Done => Status::Done,
+    };
+}
+
+#[test]
+fn concurrent_updates_are_not_lost() {
+    model(|| {
+        let client = launch(2);
+        let id = client.insert(ticket_draft!()).unwrap();
+        let other = thread::spawn({
+            let client = client.clone();
+            move || advance(&client, id)
+        });
+        advance(&client, id);
+        other.join().unwrap();
+        let ticket = client.get(id).unwrap().unwrap();
+        assert_eq!(ticket.read().unwrap().status, Status::Done);
+    });
+}
+
+#[test]
+fn waiters_never_miss_the_write_they_wait_for() {
+    model(|| {
+        let client = launch(2);
+        let id = client.insert(ticket_draft!()).unwrap();
+        let writer = thread::spawn({
+            let client = client.clone();
+            move || advance(&client, id)
+        });
+        // `loom` never times out: a missed notification shows up as a deadlock.
+        let ticket = client
+            .wait_for(
+                id,
+                |ticket| ticket.status != Status::ToDo,
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        assert_eq!(ticket.status, Status::InProgress);
+        writer.join().unwrap();
+    });
+}
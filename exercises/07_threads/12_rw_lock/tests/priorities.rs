@@ -0,0 +1,105 @@
+use std::thread;
+use std::time::Duration;
+
+use rwlock::data::TicketDraft;
+use rwlock::priority::{ClassConfig, Config, Priority};
+use rwlock::{channels, server, OverloadedError, TicketStoreClient};
+use ticket_fields::test_helpers::ticket_draft;
+
+fn wait_for_queued(client: &TicketStoreClient, priority: Priority, queued: usize) {
+    while client.load(priority).queued < queued {
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn writes_and_bulk_inserts_are_interleaved_by_weight() {
+    let (client, queues) = channels(Config::new(10));
+    let writes: Vec<_> = (0..4)
+        .map(|_| {
+            let client = client.clone();
+            thread::spawn(move || client.insert(ticket_draft!()).unwrap())
+        })
+        .collect();
+    let bulk: Vec<_> = (0..2)
+        .map(|_| {
+            let client = client.clone();
+            thread::spawn(move || client.insert_bulk(vec![ticket_draft!()]).unwrap())
+        })
+        .collect();
+    wait_for_queued(&client, Priority::Write, 4);
+    wait_for_queued(&client, Priority::Bulk, 2);
+
+    let server = thread::spawn(move || server(queues));
+    let mut order = Vec::new();
+    for write in writes {
+        order.push((write.join().unwrap(), Priority::Write));
+    }
+    for bulk in bulk {
+        let ids = bulk.join().unwrap();
+        order.push((ids[0], Priority::Bulk));
+    }
+    order.sort_by_key(|(id, _)| *id);
+    let order: Vec<_> = order.into_iter().map(|(_, priority)| priority).collect();
+    // Two writes for every bulk insert, spread out.
+    assert_eq!(
+        order,
+        [
+            Priority::Write,
+            Priority::Bulk,
+            Priority::Write,
+            Priority::Write,
+            Priority::Bulk,
+            Priority::Write,
+        ]
+    );
+
+    drop(client);
+    server.join().unwrap();
+}
+
+#[test]
+fn a_full_bulk_queue_does_not_block_reads() {
+    let config = Config {
+        bulk: ClassConfig {
+            capacity: 1,
+            weight: 1,
+        },
+        ..Config::new(10)
+    };
+    let (client, queues) = channels(config);
+    let queued = thread::spawn({
+        let client = client.clone();
+        move || {
+            client
+                .insert_bulk(vec![ticket_draft!(), ticket_draft!()])
+                .unwrap()
+        }
+    });
+    wait_for_queued(&client, Priority::Bulk, 1);
+
+    let error = client.insert_bulk(vec![ticket_draft!()]).unwrap_err();
+    assert!(matches!(
+        error,
+        OverloadedError {
+            priority: Priority::Bulk
+        }
+    ));
+    assert_eq!(
+        error.to_string(),
+        "The store is overloaded: the bulk queue is full"
+    );
+    let load = client.load(Priority::Bulk);
+    assert_eq!((load.capacity, load.queued, load.rejected), (1, 1, 1));
+    assert_eq!(client.load(Priority::Interactive).rejected, 0);
+
+    let server = thread::spawn(move || server(queues));
+    let ids = queued.join().unwrap();
+    assert_eq!(ids.len(), 2);
+    let ticket = client.get(ids[1]).unwrap().unwrap();
+    assert_eq!(ticket.read().unwrap().id, ids[1]);
+    assert_eq!(client.load(Priority::Bulk).queued, 0);
+
+    drop(client);
+    server.join().unwrap();
+}
@@ -0,0 +1,94 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rwlock::data::{Status, TicketDraft};
+use rwlock::store::TicketStore;
+use rwlock::{launch, WaitError};
+use ticket_fields::test_helpers::ticket_draft;
+
+#[test]
+fn waiters_wake_up_once_the_ticket_is_done() {
+    let client = launch(5);
+    let id = client.insert(ticket_draft!()).unwrap();
+    let waiter = thread::spawn({
+        let client = client.clone();
+        move || {
+            client.wait_for(
+                id,
+                |ticket| ticket.status == Status::Done,
+                Duration::from_secs(5),
+            )
+        }
+    });
+    thread::sleep(Duration::from_millis(20));
+
+    // The server keeps going while someone waits.
+    let other = client.insert(ticket_draft!()).unwrap();
+    client.get(other).unwrap().unwrap().write().unwrap().status = Status::Done;
+    client.get(id).unwrap().unwrap().write().unwrap().status = Status::InProgress;
+    client.get(id).unwrap().unwrap().write().unwrap().status = Status::Done;
+
+    let ticket = waiter.join().unwrap().unwrap();
+    assert_eq!((ticket.id, ticket.status), (id, Status::Done));
+}
+
+#[test]
+fn predicates_that_already_hold_return_right_away() {
+    let client = launch(5);
+    let id = client.insert(ticket_draft!()).unwrap();
+    let start = Instant::now();
+    let ticket = client
+        .wait_for(
+            id,
+            |ticket| ticket.status == Status::ToDo,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+    assert_eq!(ticket.id, id);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn waiting_gives_up_after_the_timeout() {
+    let client = launch(5);
+    let id = client.insert(ticket_draft!()).unwrap();
+    let start = Instant::now();
+    let error = client
+        .wait_for(
+            id,
+            |ticket| ticket.status == Status::Done,
+            Duration::from_millis(20),
+        )
+        .unwrap_err();
+    assert!(matches!(error, WaitError::Timeout(timed_out) if timed_out == id));
+    assert!(start.elapsed() >= Duration::from_millis(20));
+}
+
+#[test]
+fn huge_timeouts_mean_no_timeout() {
+    let client = launch(5);
+    let id = client.insert(ticket_draft!()).unwrap();
+    let waiter = thread::spawn({
+        let client = client.clone();
+        move || client.wait_for(id, |ticket| ticket.status == Status::Done, Duration::MAX)
+    });
+    thread::sleep(Duration::from_millis(20));
+
+    client.get(id).unwrap().unwrap().write().unwrap().status = Status::Done;
+    let ticket = waiter.join().unwrap().unwrap();
+    assert_eq!(ticket.status, Status::Done);
+}
+
+#[test]
+fn waiting_for_a_missing_ticket_fails() {
+    let mut other = TicketStore::new();
+    other.add_ticket(ticket_draft!());
+    let missing = other.add_ticket(ticket_draft!());
+    let mut store = TicketStore::new();
+    store.add_ticket(ticket_draft!());
+
+    let error = store
+        .wait_for(missing, |_| true, Duration::from_secs(5))
+        .unwrap_err();
+    assert!(matches!(error, WaitError::NotFound(id) if id == missing));
+}
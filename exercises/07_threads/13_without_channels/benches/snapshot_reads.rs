@@ -0,0 +1,119 @@
+//! Reader latency under heavy insert load: an outer `RwLock<TicketStore>`
+//! versus the lock-free `SharedTicketStore`.
+//!
+//! Run with `cargo bench -p without_channels --bench snapshot_reads`.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ticket_fields::test_helpers::ticket_draft;
+use without_channels::data::TicketDraft;
+use without_channels::shared::SharedTicketStore;
+use without_channels::store::TicketStore;
+
+const WRITERS: usize = 4;
+const READERS: usize = 4;
+const RUN_FOR: Duration = Duration::from_secs(1);
+
+/// Run `write` in a loop on the writer threads while the reader threads time `read`.
+/// Returns how many writes went through, and every read latency, sorted.
+fn measure(
+    write: impl Fn() + Send + Sync + 'static,
+    read: impl Fn() + Send + Sync + 'static,
+) -> (usize, Vec<Duration>) {
+    let write = Arc::new(write);
+    let read = Arc::new(read);
+    let done = Arc::new(AtomicBool::new(false));
+
+    let writers: Vec<_> = (0..WRITERS)
+        .map(|_| {
+            let (write, done) = (Arc::clone(&write), Arc::clone(&done));
+            thread::spawn(move || {
+                let mut writes = 0;
+                while !done.load(Ordering::Relaxed) {
+                    write();
+                    writes += 1;
+                }
+                writes
+            })
+        })
+        .collect();
+    let readers: Vec<_> = (0..READERS)
+        .map(|_| {
+            let (read, done) = (Arc::clone(&read), Arc::clone(&done));
+            thread::spawn(move || {
+                let mut latencies = Vec::new();
+                while !done.load(Ordering::Relaxed) {
+                    let start = Instant::now();
+                    read();
+                    latencies.push(start.elapsed());
+                }
+                latencies
+            })
+        })
+        .collect();
+
+    thread::sleep(RUN_FOR);
+    done.store(true, Ordering::Relaxed);
+    let writes = writers
+        .into_iter()
+        .map(|writer| writer.join().unwrap())
+        .sum();
+    let mut latencies: Vec<_> = readers
+        .into_iter()
+        .flat_map(|reader| reader.join().unwrap())
+        .collect();
+    latencies.sort();
+    (writes, latencies)
+}
+
+fn report(name: &str, (writes, latencies): (usize, Vec<Duration>)) {
+    let percentile = |p: f64| latencies[((latencies.len() - 1) as f64 * p) as usize];
+    println!(
+        "{name:<20} {writes:>9} writes {:>9} reads   p50 {:>10.2?}   p99 {:>10.2?}   p99.9 {:>10.2?}   max {:>10.2?}",
+        latencies.len(),
+        percentile(0.5),
+        percentile(0.99),
+        percentile(0.999),
+        latencies[latencies.len() - 1],
+    );
+}
+
+fn main() {
+    let locked = Arc::new(RwLock::new(TicketStore::new()));
+    let results = measure(
+        {
+            let store = Arc::clone(&locked);
+            move || {
+                store.write().unwrap().add_ticket(ticket_draft!());
+            }
+        },
+        {
+            let store = Arc::clone(&locked);
+            move || {
+                let store = store.read().unwrap();
+                std::hint::black_box(store.len());
+            }
+        },
+    );
+    report("RwLock<TicketStore>", results);
+
+    let shared = Arc::new(SharedTicketStore::new());
+    let results = measure(
+        {
+            let store = Arc::clone(&shared);
+            move || {
+                store.add_ticket(ticket_draft!());
+            }
+        },
+        {
+            let store = Arc::clone(&shared);
+            move || {
+                let snapshot = store.snapshot();
+                std::hint::black_box(snapshot.len());
+            }
+        },
+    );
+    report("SharedTicketStore", results);
+}
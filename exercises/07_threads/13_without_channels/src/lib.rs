@@ -4,4 +4,9 @@
 //  Fix the `todo!()` in the testing code and see how the new design can be used.
 
 pub mod data;
+// The shared `TicketRepository` conformance suite: `cargo test --features repository`.
+#[cfg(feature = "repository")]
+mod repository;
+pub mod shared;
 pub mod store;
+pub mod transaction;
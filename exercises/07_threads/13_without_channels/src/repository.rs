@@ -0,0 +1,58 @@
+use std::sync::RwLock;
+
+use ticket_fields::{TicketDescription, TicketTitle};
+use ticket_repository::{TicketChanges, TicketNotFound, TicketRecord, TicketRepository};
+
+use crate::data::{Status, Ticket, TicketDraft};
+use crate::store::{TicketId, TicketStore};
+
+impl TicketRepository for TicketStore {
+    type Id = TicketId;
+    type Status = Status;
+
+    fn insert(&mut self, title: TicketTitle, description: TicketDescription) -> TicketId {
+        self.add_ticket(TicketDraft { title, description })
+    }
+
+    fn get(&self, id: TicketId) -> Option<TicketRecord<TicketId, Status>> {
+        TicketStore::get(self, id).map(|ticket| record(&ticket))
+    }
+
+    fn update(
+        &mut self,
+        id: TicketId,
+        changes: TicketChanges<Status>,
+    ) -> Result<(), TicketNotFound> {
+        let ticket = TicketStore::get(self, id).ok_or(TicketNotFound)?;
+        let mut ticket = ticket.write().unwrap();
+        if let Some(title) = changes.title {
+            ticket.title = title;
+        }
+        if let Some(description) = changes.description {
+            ticket.description = description;
+        }
+        if let Some(status) = changes.status {
+            ticket.status = status;
+        }
+        Ok(())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = TicketRecord<TicketId, Status>> + '_ {
+        TicketStore::iter(self).map(|ticket| record(ticket))
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+}
+
+// Each read only holds the ticket lock for as long as it takes to copy the ticket out.
+fn record(ticket: &RwLock<Ticket>) -> TicketRecord<TicketId, Status> {
+    let ticket = ticket.read().unwrap();
+    TicketRecord {
+        id: ticket.id,
+        title: ticket.title.clone(),
+        description: ticket.description.clone(),
+        status: ticket.status,
+    }
+}
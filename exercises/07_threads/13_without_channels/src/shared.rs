@@ -0,0 +1,183 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use arc_swap::ArcSwap;
+
+use crate::data::{Status, Ticket, TicketDraft};
+use crate::store::TicketId;
+
+/// A ticket store that can be shared across threads without an outer lock.
+///
+/// The tickets live in a persistent (immutable, structurally shared) map behind an
+/// atomic pointer. Readers grab the current map with [`snapshot`](Self::snapshot),
+/// which never blocks, and see a consistent point-in-time view for as long as they hold it.
+/// Writers build a new version of the map and swap it in, retrying if another writer
+/// got there first.
+#[derive(Default)]
+pub struct SharedTicketStore {
+    tickets: ArcSwap<Snapshot>,
+    counter: AtomicU64,
+    /// Bumped after every update, so that [`wait_for`](SharedTicketStore::wait_for)
+    /// callers can tell whether they missed one while checking their predicate.
+    version: AtomicU64,
+    /// How many `wait_for` callers there are: writers only notify when there are some.
+    waiters: AtomicUsize,
+    /// Held by `wait_for` callers from their last version check until they wait,
+    /// and by writers to notify them, so that no update goes unnoticed.
+    waiting: Mutex<()>,
+    changed: Condvar,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum WaitError {
+    #[error("There is no ticket with id {0:?}")]
+    NotFound(TicketId),
+    #[error("Ticket {0:?} didn't reach the expected state in time")]
+    Timeout(TicketId),
+}
+
+/// The state of a [`SharedTicketStore`] at a point in time.
+#[derive(Clone, Default)]
+pub struct Snapshot {
+    tickets: im::OrdMap<TicketId, Arc<Ticket>>,
+}
+
+impl Snapshot {
+    pub fn get(&self, id: TicketId) -> Option<&Ticket> {
+        self.tickets.get(&id).map(|ticket| &**ticket)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+
+    /// The tickets, in id order.
+    pub fn iter(&self) -> impl Iterator<Item = &Ticket> {
+        self.tickets.values().map(|ticket| &**ticket)
+    }
+}
+
+impl SharedTicketStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_ticket(&self, ticket: TicketDraft) -> TicketId {
+        let id = TicketId(self.counter.fetch_add(1, Ordering::Relaxed));
+        let ticket = Arc::new(Ticket {
+            id,
+            title: ticket.title,
+            description: ticket.description,
+            status: Status::ToDo,
+        });
+        self.tickets.rcu(|current| {
+            let mut next = Snapshot::clone(current);
+            next.tickets.insert(id, Arc::clone(&ticket));
+            next
+        });
+        id
+    }
+
+    /// Apply `f` to a copy of the ticket and store the result.
+    /// Returns the updated ticket, or `None` if there is no ticket with this `id`.
+    ///
+    /// `f` may be called more than once if other writers race with this one,
+    /// only the last call counts.
+    pub fn update(&self, id: TicketId, f: impl Fn(&mut Ticket)) -> Option<Arc<Ticket>> {
+        let mut updated = None;
+        self.tickets.rcu(|current| {
+            let mut next = Snapshot::clone(current);
+            updated = next.tickets.get_mut(&id).map(|ticket| {
+                f(Arc::make_mut(ticket));
+                Arc::clone(ticket)
+            });
+            next
+        });
+        if updated.is_some() {
+            // Both `SeqCst`: either we see the waiter, or it sees the new version.
+            self.version.fetch_add(1, Ordering::SeqCst);
+            if self.waiters.load(Ordering::SeqCst) > 0 {
+                let _waiting = self.waiting.lock().unwrap();
+                self.changed.notify_all();
+            }
+        }
+        updated
+    }
+
+    /// Block until `predicate` holds for the ticket with this `id`, then return it.
+    /// Gives up with [`WaitError::Timeout`] after `timeout`.
+    ///
+    /// `predicate` is checked right away, then again after every [`update`](Self::update),
+    /// without holding any lock: it doesn't hold up writers, and may even write itself.
+    pub fn wait_for(
+        &self,
+        id: TicketId,
+        mut predicate: impl FnMut(&Ticket) -> bool,
+        timeout: Duration,
+    ) -> Result<Arc<Ticket>, WaitError> {
+        // `None` if too far in the future to ever be reached, e.g. for `Duration::MAX`.
+        let deadline = Instant::now().checked_add(timeout);
+        let _waiter = Waiter::register(&self.waiters);
+        loop {
+            let version = self.version.load(Ordering::SeqCst);
+            let ticket = self.get(id).ok_or(WaitError::NotFound(id))?;
+            if predicate(&ticket) {
+                return Ok(ticket);
+            }
+            let waiting = self.waiting.lock().unwrap();
+            if self.version.load(Ordering::SeqCst) != version {
+                // Updated while we were checking: check again.
+                continue;
+            }
+            match deadline {
+                None => drop(self.changed.wait(waiting).unwrap()),
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(WaitError::Timeout(id));
+                    }
+                    drop(self.changed.wait_timeout(waiting, remaining).unwrap());
+                }
+            }
+        }
+    }
+
+    /// The current state of the store. Never blocks.
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.tickets.load_full()
+    }
+
+    pub fn get(&self, id: TicketId) -> Option<Arc<Ticket>> {
+        self.tickets.load().tickets.get(&id).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tickets.load().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickets.load().is_empty()
+    }
+}
+
+/// Counts a [`wait_for`](SharedTicketStore::wait_for) caller for as long as it's alive,
+/// even if its predicate panics.
+struct Waiter<'a>(&'a AtomicUsize);
+
+impl<'a> Waiter<'a> {
+    fn register(waiters: &'a AtomicUsize) -> Self {
+        waiters.fetch_add(1, Ordering::SeqCst);
+        Self(waiters)
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -4,9 +4,9 @@ use std::sync::{Arc, RwLock};
 use crate::data::{Status, Ticket, TicketDraft};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct TicketId(u64);
+pub struct TicketId(pub(crate) u64);
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct TicketStore {
     tickets: BTreeMap<TicketId, Arc<RwLock<Ticket>>>,
     counter: u64,
@@ -37,4 +37,16 @@ impl TicketStore {
     pub fn get(&self, id: TicketId) -> Option<Arc<RwLock<Ticket>>> {
         self.tickets.get(&id).cloned()
     }
+
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<RwLock<Ticket>>> {
+        self.tickets.values()
+    }
 }
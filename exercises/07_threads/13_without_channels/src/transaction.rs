@@ -0,0 +1,119 @@
+use std::sync::{RwLock, RwLockWriteGuard, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::data::Ticket;
+use crate::store::{TicketId, TicketStore};
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum TransactionError<E> {
+    #[error("There is no ticket with id {0:?}")]
+    NotFound(TicketId),
+    #[error("Ticket {0:?} was listed more than once")]
+    Duplicate(TicketId),
+    #[error("Ticket {0:?} is locked by someone else")]
+    Timeout(TicketId),
+    #[error("The lock of ticket {0:?} is poisoned: a thread panicked while holding it")]
+    Poisoned(TicketId),
+    /// The closure failed: every change it made has been rolled back.
+    #[error("The transaction was aborted")]
+    Aborted(E),
+}
+
+impl TicketStore {
+    /// Lock the tickets with the given `ids` for writing, all at once, and run `f` on them.
+    ///
+    /// `f` gets the tickets in the same order as `ids`. If it returns an error,
+    /// the tickets are restored to how they were before the call.
+    ///
+    /// Locks are always acquired in ascending `TicketId` order, so concurrent transactions
+    /// can't deadlock each other, whatever order they list their tickets in.
+    pub fn with_tickets_mut<T, E>(
+        &self,
+        ids: &[TicketId],
+        f: impl FnOnce(&mut [&mut Ticket]) -> Result<T, E>,
+    ) -> Result<T, TransactionError<E>> {
+        self.transaction(ids, None, f)
+    }
+
+    /// Like [`with_tickets_mut`](Self::with_tickets_mut), but give up with
+    /// [`TransactionError::Timeout`] if the locks can't all be acquired within `timeout`.
+    /// The locks acquired so far are released.
+    pub fn with_tickets_mut_timeout<T, E>(
+        &self,
+        ids: &[TicketId],
+        timeout: Duration,
+        f: impl FnOnce(&mut [&mut Ticket]) -> Result<T, E>,
+    ) -> Result<T, TransactionError<E>> {
+        self.transaction(ids, Instant::now().checked_add(timeout), f)
+    }
+
+    fn transaction<T, E>(
+        &self,
+        ids: &[TicketId],
+        deadline: Option<Instant>,
+        f: impl FnOnce(&mut [&mut Ticket]) -> Result<T, E>,
+    ) -> Result<T, TransactionError<E>> {
+        let mut sorted = ids.to_vec();
+        sorted.sort();
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(TransactionError::Duplicate(pair[0]));
+        }
+        let locks = sorted
+            .iter()
+            .map(|&id| self.get(id).ok_or(TransactionError::NotFound(id)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // If anything goes wrong, the guards acquired so far are dropped on the way out.
+        let mut guards = Vec::with_capacity(locks.len());
+        for (&id, lock) in sorted.iter().zip(&locks) {
+            guards.push(write(lock, id, deadline)?);
+        }
+        let snapshot: Vec<Ticket> = guards.iter().map(|guard| (**guard).clone()).collect();
+
+        let mut slots: Vec<Option<&mut Ticket>> =
+            guards.iter_mut().map(|guard| Some(&mut **guard)).collect();
+        let mut tickets: Vec<&mut Ticket> = ids
+            .iter()
+            .map(|id| {
+                let position = sorted.binary_search(id).unwrap();
+                slots[position].take().unwrap()
+            })
+            .collect();
+        match f(&mut tickets) {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                drop(tickets);
+                for (guard, ticket) in guards.iter_mut().zip(snapshot) {
+                    **guard = ticket;
+                }
+                Err(TransactionError::Aborted(e))
+            }
+        }
+    }
+}
+
+fn write<'a, E>(
+    lock: &'a RwLock<Ticket>,
+    id: TicketId,
+    deadline: Option<Instant>,
+) -> Result<RwLockWriteGuard<'a, Ticket>, TransactionError<E>> {
+    let Some(deadline) = deadline else {
+        return lock.write().map_err(|_| TransactionError::Poisoned(id));
+    };
+    // `RwLock` has no timed lock: poll with a short, growing sleep instead.
+    let mut pause = Duration::from_micros(50);
+    loop {
+        match lock.try_write() {
+            Ok(guard) => return Ok(guard),
+            Err(TryLockError::Poisoned(_)) => return Err(TransactionError::Poisoned(id)),
+            Err(TryLockError::WouldBlock) => {}
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(TransactionError::Timeout(id));
+        }
+        thread::sleep(pause.min(deadline - now));
+        pause = (pause * 2).min(Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,10 @@
+#![cfg(feature = "repository")]
+
+use without_channels::data::Status;
+use without_channels::store::TicketStore;
+
+ticket_repository::conformance_tests!(
+    TicketStore::new(),
+    initial = Status::ToDo,
+    updated = Status::InProgress,
+);
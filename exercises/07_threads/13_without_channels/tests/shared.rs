@@ -0,0 +1,130 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ticket_fields::test_helpers::ticket_draft;
+use without_channels::data::{Status, TicketDraft};
+use without_channels::shared::{SharedTicketStore, WaitError};
+
+#[test]
+fn snapshots_do_not_see_later_writes() {
+    let store = SharedTicketStore::new();
+    let id = store.add_ticket(ticket_draft!());
+    let before = store.snapshot();
+
+    store.add_ticket(ticket_draft!());
+    store
+        .update(id, |ticket| ticket.status = Status::Done)
+        .unwrap();
+
+    assert_eq!(before.len(), 1);
+    assert_eq!(before.get(id).unwrap().status, Status::ToDo);
+    assert_eq!(store.len(), 2);
+    assert_eq!(store.get(id).unwrap().status, Status::Done);
+}
+
+#[test]
+fn updating_a_missing_ticket_returns_none() {
+    let store = SharedTicketStore::new();
+    let id = store.add_ticket(ticket_draft!());
+    let other = SharedTicketStore::new();
+    other.add_ticket(ticket_draft!());
+    let missing = other.add_ticket(ticket_draft!());
+
+    assert!(store.update(missing, |_| {}).is_none());
+    assert!(store.update(id, |_| {}).is_some());
+}
+
+#[test]
+fn concurrent_writers_do_not_lose_tickets() {
+    let store = Arc::new(SharedTicketStore::new());
+    let writers: Vec<_> = (0..4)
+        .map(|_| {
+            let store = Arc::clone(&store);
+            thread::spawn(move || {
+                for _ in 0..250 {
+                    store.add_ticket(ticket_draft!());
+                }
+            })
+        })
+        .collect();
+    // Every snapshot is internally consistent, and never shrinks.
+    let mut seen = 0;
+    while seen < 1_000 {
+        let snapshot = store.snapshot();
+        assert!(snapshot.len() >= seen);
+        assert_eq!(snapshot.iter().count(), snapshot.len());
+        seen = snapshot.len();
+    }
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    assert_eq!(store.len(), 1_000);
+}
+
+#[test]
+fn waiters_wake_up_once_the_ticket_is_done() {
+    let store = Arc::new(SharedTicketStore::new());
+    let id = store.add_ticket(ticket_draft!());
+    let other = store.add_ticket(ticket_draft!());
+    let waiter = thread::spawn({
+        let store = Arc::clone(&store);
+        move || store.wait_for(id, |ticket| ticket.status == Status::Done, Duration::MAX)
+    });
+    thread::sleep(Duration::from_millis(20));
+
+    store.update(other, |ticket| ticket.status = Status::Done);
+    store.update(id, |ticket| ticket.status = Status::InProgress);
+    store.update(id, |ticket| ticket.status = Status::Done);
+    let ticket = waiter.join().unwrap().unwrap();
+    assert_eq!((ticket.id, ticket.status), (id, Status::Done));
+}
+
+#[test]
+fn waiting_gives_up_after_the_timeout() {
+    let store = SharedTicketStore::new();
+    let id = store.add_ticket(ticket_draft!());
+    let start = Instant::now();
+    let error = store
+        .wait_for(
+            id,
+            |ticket| ticket.status == Status::Done,
+            Duration::from_millis(20),
+        )
+        .unwrap_err();
+    assert_eq!(error, WaitError::Timeout(id));
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    let other = SharedTicketStore::new();
+    other.add_ticket(ticket_draft!());
+    let missing = other.add_ticket(ticket_draft!());
+    assert_eq!(
+        store
+            .wait_for(missing, |_| true, Duration::MAX)
+            .unwrap_err(),
+        WaitError::NotFound(missing)
+    );
+}
+
+#[test]
+fn predicates_can_write_to_the_store() {
+    let store = SharedTicketStore::new();
+    let id = store.add_ticket(ticket_draft!());
+    // Each check moves the ticket one step forward, until it's done.
+    let ticket = store
+        .wait_for(
+            id,
+            |ticket| {
+                let next = match ticket.status {
+                    Status::ToDo => Status::InProgress,
+                    Status::InProgress => Status::Done,
+                    Status::Done => return true,
+                };
+                store.update(id, |ticket| ticket.status = next);
+                false
+            },
+            Duration::from_secs(5),
+        )
+        .unwrap();
+    assert_eq!(ticket.status, Status::Done);
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_fields::TicketTitle;
+use without_channels::data::{Status, TicketDraft};
+use without_channels::store::{TicketId, TicketStore};
+use without_channels::transaction::TransactionError;
+
+fn store_with(n: usize) -> (TicketStore, Vec<TicketId>) {
+    let mut store = TicketStore::new();
+    let ids = (0..n)
+        .map(|_| {
+            store.add_ticket(TicketDraft {
+                title: ticket_title(),
+                description: ticket_description(),
+            })
+        })
+        .collect();
+    (store, ids)
+}
+
+#[test]
+fn tickets_are_handed_over_in_the_requested_order() {
+    let (store, ids) = store_with(2);
+    let title = TicketTitle::try_from("Blocked").unwrap();
+
+    store
+        .with_tickets_mut(&[ids[1], ids[0]], |tickets| {
+            assert_eq!(tickets[0].id, ids[1]);
+            tickets[0].title = title.clone();
+            tickets[0].status = Status::InProgress;
+            tickets[1].status = Status::InProgress;
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+
+    let second = store.get(ids[1]).unwrap();
+    assert_eq!(second.read().unwrap().title, title);
+    let first = store.get(ids[0]).unwrap();
+    assert_eq!(first.read().unwrap().status, Status::InProgress);
+}
+
+#[test]
+fn errors_roll_back_every_change() {
+    let (store, ids) = store_with(2);
+
+    let outcome = store.with_tickets_mut(&ids, |tickets| {
+        tickets[0].status = Status::Done;
+        tickets[1].status = Status::Done;
+        Err::<(), _>("Changed my mind")
+    });
+
+    assert_eq!(outcome, Err(TransactionError::Aborted("Changed my mind")));
+    for id in ids {
+        assert_eq!(store.get(id).unwrap().read().unwrap().status, Status::ToDo);
+    }
+}
+
+#[test]
+fn unknown_and_repeated_ids_are_rejected() {
+    let (store, ids) = store_with(1);
+    // Ids from a bigger store, that don't exist in ours.
+    let (_, others) = store_with(2);
+    let unknown = others[1];
+
+    let outcome = store.with_tickets_mut(&[ids[0], unknown], |_| Ok::<_, ()>(()));
+    assert_eq!(outcome, Err(TransactionError::NotFound(unknown)));
+    let outcome = store.with_tickets_mut(&[ids[0], ids[0]], |_| Ok::<_, ()>(()));
+    assert_eq!(outcome, Err(TransactionError::Duplicate(ids[0])));
+}
+
+#[test]
+fn timeouts_release_the_locks_acquired_so_far() {
+    let (store, ids) = store_with(2);
+    let busy = store.get(ids[1]).unwrap();
+    let guard = busy.write().unwrap();
+
+    let outcome =
+        store.with_tickets_mut_timeout(&ids, Duration::from_millis(20), |_| Ok::<_, ()>(()));
+    assert_eq!(outcome, Err(TransactionError::Timeout(ids[1])));
+    assert!(store.get(ids[0]).unwrap().try_write().is_ok());
+    drop(guard);
+}
+
+#[test]
+fn huge_timeouts_mean_no_timeout() {
+    let (store, ids) = store_with(2);
+    let outcome =
+        store.with_tickets_mut_timeout(&ids, Duration::MAX, |tickets| Ok::<_, ()>(tickets.len()));
+    assert_eq!(outcome, Ok(2));
+}
+
+#[test]
+fn opposite_orders_do_not_deadlock() {
+    let (store, ids) = store_with(2);
+    let store = Arc::new(store);
+
+    let handles: Vec<_> = [[ids[0], ids[1]], [ids[1], ids[0]]]
+        .into_iter()
+        .map(|order| {
+            let store = Arc::clone(&store);
+            thread::spawn(move || {
+                for _ in 0..1_000 {
+                    store
+                        .with_tickets_mut(&order, |tickets| {
+                            let status = tickets[0].status;
+                            tickets[0].status = tickets[1].status;
+                            tickets[1].status = status;
+                            Ok::<_, ()>(())
+                        })
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+//! A thread-safe take on the `DropTracker` exercise: count how many values
+//! of each type are created and dropped, from any thread, to catch leaks in tests.
+//!
+//! ```
+//! use allocations::{assert_all_released, Allocations};
+//!
+//! let allocations = Allocations::new();
+//! let handle = std::sync::Arc::new(allocations.track(42));
+//! std::thread::spawn({
+//!     let handle = std::sync::Arc::clone(&handle);
+//!     move || assert_eq!(**handle, 42)
+//! })
+//! .join()
+//! .unwrap();
+//! assert_eq!(allocations.stats::<i32>().live(), 1);
+//!
+//! drop(handle);
+//! assert_all_released(&allocations);
+//! ```
+use std::any::{type_name, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Creation and drop counts, per type. Cloning it is cheap and the clones share the counts.
+#[derive(Clone, Default)]
+pub struct Allocations {
+    types: Arc<Mutex<HashMap<TypeId, Arc<Counters>>>>,
+}
+
+struct Counters {
+    type_name: &'static str,
+    created: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl Allocations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap `value` so that it's counted as created now, and as dropped when the wrapper is.
+    pub fn track<T: 'static>(&self, value: T) -> Tracked<T> {
+        Tracked::new(value, self.counters::<T>())
+    }
+
+    /// The counts for `T`, all zero if no `T` was ever tracked.
+    pub fn stats<T: 'static>(&self) -> TypeStats {
+        self.counters::<T>().stats()
+    }
+
+    /// The counts for every type tracked so far, by type name.
+    pub fn all(&self) -> Vec<TypeStats> {
+        let types = self.types.lock().unwrap();
+        let mut stats: Vec<_> = types.values().map(|counters| counters.stats()).collect();
+        stats.sort_by_key(|stats| stats.type_name);
+        stats
+    }
+
+    /// The types that still have live values.
+    pub fn leaks(&self) -> Vec<TypeStats> {
+        self.all()
+            .into_iter()
+            .filter(|stats| stats.live() > 0)
+            .collect()
+    }
+
+    fn counters<T: 'static>(&self) -> Arc<Counters> {
+        let mut types = self.types.lock().unwrap();
+        let counters = types.entry(TypeId::of::<T>()).or_insert_with(|| {
+            Arc::new(Counters {
+                type_name: type_name::<T>(),
+                created: AtomicUsize::new(0),
+                dropped: AtomicUsize::new(0),
+            })
+        });
+        Arc::clone(counters)
+    }
+}
+
+impl Counters {
+    fn stats(&self) -> TypeStats {
+        // Read the drops first: a value dropped after we read `dropped`
+        // makes us overestimate the live count, never underestimate it.
+        let dropped = self.dropped.load(Ordering::Acquire);
+        let created = self.created.load(Ordering::Acquire);
+        TypeStats {
+            type_name: self.type_name,
+            created,
+            dropped,
+        }
+    }
+}
+
+/// How many values of a type were created and dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TypeStats {
+    pub type_name: &'static str,
+    pub created: usize,
+    pub dropped: usize,
+}
+
+impl TypeStats {
+    /// How many values are still around.
+    pub fn live(&self) -> usize {
+        self.created.saturating_sub(self.dropped)
+    }
+}
+
+impl fmt::Display for TypeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} live ({} created, {} dropped)",
+            self.type_name,
+            self.live(),
+            self.created,
+            self.dropped
+        )
+    }
+}
+
+/// A value counted by an [`Allocations`]. Derefs to the value.
+///
+/// Cloning it counts as creating a new value.
+pub struct Tracked<T> {
+    value: T,
+    counters: Arc<Counters>,
+}
+
+impl<T> Tracked<T> {
+    fn new(value: T, counters: Arc<Counters>) -> Self {
+        counters.created.fetch_add(1, Ordering::Release);
+        Self { value, counters }
+    }
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Tracked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Clone> Clone for Tracked<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone(), Arc::clone(&self.counters))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Tracked<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<T> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        self.counters.dropped.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Panic if any value tracked by `allocations` is still alive, listing the culprits.
+///
+/// Call it once everything that could hold on to a value is gone, e.g. after
+/// a server has shut down and its thread has been joined.
+#[track_caller]
+pub fn assert_all_released(allocations: &Allocations) {
+    let leaks = allocations.leaks();
+    if !leaks.is_empty() {
+        let leaks: Vec<_> = leaks.iter().map(TypeStats::to_string).collect();
+        panic!("Not all values were released:\n{}", leaks.join("\n"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn counts_across_threads() {
+        let allocations = Allocations::new();
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let allocations = allocations.clone();
+                thread::spawn(move || {
+                    let values: Vec<_> = (0..10).map(|j| allocations.track(i * j)).collect();
+                    drop(values);
+                    allocations.track(format!("kept by {i}"))
+                })
+            })
+            .collect();
+        let kept: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        let numbers = allocations.stats::<i32>();
+        assert_eq!((numbers.created, numbers.dropped), (40, 40));
+        assert_eq!(allocations.stats::<String>().live(), 4);
+        assert_eq!(allocations.leaks().len(), 1);
+
+        drop(kept);
+        assert_all_released(&allocations);
+    }
+
+    #[test]
+    fn clones_are_counted() {
+        let allocations = Allocations::new();
+        let original = allocations.track(vec![1, 2, 3]);
+        let copy = original.clone();
+        assert_eq!(*copy, [1, 2, 3]);
+        assert_eq!(allocations.stats::<Vec<i32>>().live(), 2);
+        drop(original);
+        assert_eq!(allocations.stats::<Vec<i32>>().live(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "alloc::string::String: 1 live (1 created, 0 dropped)")]
+    fn leaks_are_reported() {
+        let allocations = Allocations::new();
+        let leaked = allocations.track(String::from("leaked"));
+        std::mem::forget(leaked);
+        assert_all_released(&allocations);
+    }
+}
@@ -0,0 +1,165 @@
+//! The synchronization primitives used by the lock-based stores.
+//!
+//! Normally these are the ones from `std`. When compiled with `--cfg loom` they come
+//! from [`loom`](https://docs.rs/loom) instead, so that the stores' `tests/loom.rs`
+//! can explore every interleaving of the client and server threads:
+//!
+//! ```bash
+//! RUSTFLAGS="--cfg loom" cargo test --release --test loom
+//! ```
+#[cfg(not(loom))]
+pub use std::sync::{atomic, mpsc, Arc, Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(not(loom))]
+pub use std::thread;
+
+#[cfg(loom)]
+pub use loom::sync::{atomic, Arc, Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(loom)]
+pub use loom::thread;
+
+/// `loom`'s channel is unbounded, so we build our own on top of its `Mutex` and `Condvar`.
+#[cfg(loom)]
+pub mod mpsc {
+    use std::collections::VecDeque;
+    pub use std::sync::mpsc::{RecvError, SendError, TryRecvError, TrySendError};
+
+    use loom::sync::{Arc, Condvar, Mutex};
+
+    struct State<T> {
+        queue: VecDeque<T>,
+        senders: usize,
+        receiver: bool,
+    }
+
+    struct Channel<T> {
+        state: Mutex<State<T>>,
+        capacity: usize,
+        /// Signalled whenever a message is sent or received, or one of the ends goes away.
+        changed: Condvar,
+    }
+
+    /// Unlike `std`'s, a channel with a capacity of zero never accepts a message:
+    /// the store only uses them to model a queue that is always full.
+    pub fn sync_channel<T>(capacity: usize) -> (SyncSender<T>, Receiver<T>) {
+        let channel = Arc::new(Channel {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                senders: 1,
+                receiver: true,
+            }),
+            capacity,
+            changed: Condvar::new(),
+        });
+        (
+            SyncSender {
+                channel: Arc::clone(&channel),
+            },
+            Receiver { channel },
+        )
+    }
+
+    pub type Sender<T> = SyncSender<T>;
+
+    pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+        sync_channel(usize::MAX)
+    }
+
+    pub struct SyncSender<T> {
+        channel: Arc<Channel<T>>,
+    }
+
+    impl<T> SyncSender<T> {
+        pub fn send(&self, message: T) -> Result<(), SendError<T>> {
+            let mut state = self.channel.state.lock().unwrap();
+            loop {
+                if !state.receiver {
+                    return Err(SendError(message));
+                }
+                if state.queue.len() < self.channel.capacity {
+                    state.queue.push_back(message);
+                    self.channel.changed.notify_all();
+                    return Ok(());
+                }
+                state = self.channel.changed.wait(state).unwrap();
+            }
+        }
+
+        pub fn try_send(&self, message: T) -> Result<(), TrySendError<T>> {
+            let mut state = self.channel.state.lock().unwrap();
+            if !state.receiver {
+                return Err(TrySendError::Disconnected(message));
+            }
+            if state.queue.len() >= self.channel.capacity {
+                return Err(TrySendError::Full(message));
+            }
+            state.queue.push_back(message);
+            self.channel.changed.notify_all();
+            Ok(())
+        }
+    }
+
+    impl<T> Clone for SyncSender<T> {
+        fn clone(&self) -> Self {
+            self.channel.state.lock().unwrap().senders += 1;
+            Self {
+                channel: Arc::clone(&self.channel),
+            }
+        }
+    }
+
+    impl<T> Drop for SyncSender<T> {
+        fn drop(&mut self) {
+            self.channel.state.lock().unwrap().senders -= 1;
+            self.channel.changed.notify_all();
+        }
+    }
+
+    pub struct Receiver<T> {
+        channel: Arc<Channel<T>>,
+    }
+
+    impl<T> Receiver<T> {
+        pub fn recv(&self) -> Result<T, RecvError> {
+            let mut state = self.channel.state.lock().unwrap();
+            loop {
+                if let Some(message) = state.queue.pop_front() {
+                    self.channel.changed.notify_all();
+                    return Ok(message);
+                }
+                if state.senders == 0 {
+                    return Err(RecvError);
+                }
+                state = self.channel.changed.wait(state).unwrap();
+            }
+        }
+
+        pub fn try_recv(&self) -> Result<T, TryRecvError> {
+            let mut state = self.channel.state.lock().unwrap();
+            match state.queue.pop_front() {
+                Some(message) => {
+                    self.channel.changed.notify_all();
+                    Ok(message)
+                }
+                None if state.senders == 0 => Err(TryRecvError::Disconnected),
+                None => Err(TryRecvError::Empty),
+            }
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            self.channel.state.lock().unwrap().receiver = false;
+            self.channel.changed.notify_all();
+        }
+    }
+}
+
+/// Check `f` under every interleaving `loom` explores.
+#[cfg(loom)]
+pub fn model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = loom::model::Builder::new();
+    // Bounding preemptions keeps the run short while still catching
+    // the vast majority of bugs, see the `loom` docs.
+    builder.preemption_bound.get_or_insert(3);
+    builder.check(f);
+}
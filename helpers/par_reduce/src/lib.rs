@@ -0,0 +1,219 @@
+//! Parallel reductions over slices, using scoped threads.
+//!
+//! The slice is split in two, one half is handed over to a new thread while the current
+//! thread takes care of the other, recursively, until either every thread is busy or the
+//! pieces are too small to be worth a thread. Partial results are combined in order,
+//! so the operation must be associative, but it doesn't have to be commutative.
+use std::num::NonZeroUsize;
+use std::thread;
+
+/// How to split a reduction across threads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParReduce {
+    threads: usize,
+    chunk_size: usize,
+}
+
+impl Default for ParReduce {
+    /// One thread per core, and at least 1024 items per thread.
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            chunk_size: 1024,
+        }
+    }
+}
+
+impl ParReduce {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use up to `threads` threads, including the current one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is zero.
+    pub fn threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "At least one thread is needed");
+        self.threads = threads;
+        self
+    }
+
+    /// Don't bother spawning a thread for fewer than `chunk_size` items.
+    /// Inputs with fewer than `2 * chunk_size` items are reduced sequentially.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "Chunks can't be empty");
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Combine all `items` with `op`, starting from `identity`.
+    ///
+    /// `identity` must not change the result, i.e. `op(identity, x) == x`
+    /// (`0` for a sum, `i32::MAX` for a minimum, ...).
+    pub fn reduce<T>(&self, items: &[T], identity: T, op: impl Fn(T, T) -> T + Sync) -> T
+    where
+        T: Clone + Send + Sync,
+    {
+        self.fold(
+            items,
+            || identity.clone(),
+            |accumulator, item| op(accumulator, item.clone()),
+            &op,
+        )
+    }
+
+    /// The general form of [`reduce`](Self::reduce), for when the result doesn't have
+    /// the same type as the items, e.g. statistics over a collection of records.
+    ///
+    /// Each thread starts from `identity()` and adds its items one by one with `fold`;
+    /// the partial results are then merged with `combine`.
+    pub fn fold<T, A>(
+        &self,
+        items: &[T],
+        identity: impl Fn() -> A + Sync,
+        fold: impl Fn(A, &T) -> A + Sync,
+        combine: impl Fn(A, A) -> A + Sync,
+    ) -> A
+    where
+        T: Sync,
+        A: Send,
+    {
+        split(
+            items,
+            self.threads,
+            self.chunk_size,
+            &identity,
+            &fold,
+            &combine,
+        )
+    }
+}
+
+fn split<T, A>(
+    items: &[T],
+    threads: usize,
+    chunk_size: usize,
+    identity: &(impl Fn() -> A + Sync),
+    fold: &(impl Fn(A, &T) -> A + Sync),
+    combine: &(impl Fn(A, A) -> A + Sync),
+) -> A
+where
+    T: Sync,
+    A: Send,
+{
+    if threads < 2 || items.len() < 2 * chunk_size {
+        return items.iter().fold(identity(), fold);
+    }
+    let (left, right) = items.split_at(items.len() / 2);
+    let left_threads = threads / 2;
+    thread::scope(|scope| {
+        let right = scope.spawn(|| {
+            split(
+                right,
+                threads - left_threads,
+                chunk_size,
+                identity,
+                fold,
+                combine,
+            )
+        });
+        let left = split(left, left_threads, chunk_size, identity, fold, combine);
+        combine(left, right.join().unwrap())
+    })
+}
+
+/// [`ParReduce::reduce`] with the default settings.
+pub fn par_reduce<T>(items: &[T], identity: T, op: impl Fn(T, T) -> T + Sync) -> T
+where
+    T: Clone + Send + Sync,
+{
+    ParReduce::default().reduce(items, identity, op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers() -> Vec<i64> {
+        (1..=10_000).collect()
+    }
+
+    #[test]
+    fn sum_min_max() {
+        let numbers = numbers();
+        let config = ParReduce::new().threads(4).chunk_size(100);
+        assert_eq!(config.reduce(&numbers, 0, |a, b| a + b), 50_005_000);
+        assert_eq!(config.reduce(&numbers, i64::MAX, i64::min), 1);
+        assert_eq!(config.reduce(&numbers, i64::MIN, i64::max), 10_000);
+    }
+
+    #[test]
+    fn order_is_preserved() {
+        let words: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        let config = ParReduce::new().threads(8).chunk_size(3);
+        let joined = config.reduce(&words, String::new(), |a, b| a + &b);
+        assert_eq!(joined, words.concat());
+    }
+
+    #[test]
+    fn small_inputs_stay_on_the_current_thread() {
+        let current = thread::current().id();
+        let config = ParReduce::new().threads(4).chunk_size(10);
+        let same_thread = config.fold(
+            &[1, 2, 3],
+            || true,
+            |same, _| same && thread::current().id() == current,
+            |a, b| a && b,
+        );
+        assert!(same_thread);
+        assert_eq!(par_reduce::<i32>(&[], 0, |a, b| a + b), 0);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Stats {
+        count: usize,
+        sum: i64,
+        min: i64,
+        max: i64,
+    }
+
+    #[test]
+    fn custom_accumulators() {
+        let stats = ParReduce::new().threads(3).chunk_size(7).fold(
+            &numbers(),
+            || Stats {
+                count: 0,
+                sum: 0,
+                min: i64::MAX,
+                max: i64::MIN,
+            },
+            |stats, &n| Stats {
+                count: stats.count + 1,
+                sum: stats.sum + n,
+                min: stats.min.min(n),
+                max: stats.max.max(n),
+            },
+            |a, b| Stats {
+                count: a.count + b.count,
+                sum: a.sum + b.sum,
+                min: a.min.min(b.min),
+                max: a.max.max(b.max),
+            },
+        );
+        assert_eq!(
+            stats,
+            Stats {
+                count: 10_000,
+                sum: 50_005_000,
+                min: 1,
+                max: 10_000
+            }
+        );
+    }
+}
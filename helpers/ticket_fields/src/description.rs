@@ -9,6 +9,12 @@ pub enum TicketDescriptionError {
     TooLong,
 }
 
+impl TicketDescription {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 impl TryFrom<String> for TicketDescription {
     type Error = TicketDescriptionError;
 
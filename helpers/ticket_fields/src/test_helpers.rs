@@ -12,3 +12,19 @@ pub fn ticket_title() -> TicketTitle {
 pub fn ticket_description() -> TicketDescription {
     valid_description().try_into().unwrap()
 }
+
+/// A valid `TicketDraft`, for test purposes.
+///
+/// Every exercise has a `TicketDraft` type of its own, with the same `title` and
+/// `description` fields: the macro builds whichever one is in scope where it's called.
+#[macro_export]
+macro_rules! ticket_draft {
+    () => {
+        TicketDraft {
+            title: $crate::test_helpers::ticket_title(),
+            description: $crate::test_helpers::ticket_description(),
+        }
+    };
+}
+
+pub use crate::ticket_draft;
@@ -11,6 +11,12 @@ pub enum TicketTitleError {
     TooLong,
 }
 
+impl TicketTitle {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 impl TryFrom<String> for TicketTitle {
     type Error = TicketTitleError;
 
@@ -0,0 +1,208 @@
+//! A test suite that every `TicketRepository` implementation must pass.
+//!
+//! Each check is a plain generic function, so it can be called from any test.
+//! Most backends will want to use [`conformance_tests!`](crate::conformance_tests) instead,
+//! which generates one `#[test]` per check.
+use crate::{TicketChanges, TicketRepository};
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_fields::{TicketDescription, TicketTitle};
+
+fn other_title() -> TicketTitle {
+    "Another title".try_into().unwrap()
+}
+
+fn other_description() -> TicketDescription {
+    "Another description".try_into().unwrap()
+}
+
+pub fn starts_empty<R: TicketRepository>(repository: R) {
+    assert_eq!(repository.count(), 0);
+    assert_eq!(repository.iter().count(), 0);
+}
+
+pub fn insert_then_get<R: TicketRepository>(mut repository: R, initial: R::Status) {
+    let id = repository.insert(ticket_title(), ticket_description());
+
+    let ticket = repository.get(id).unwrap();
+    assert_eq!(ticket.id, id);
+    assert_eq!(ticket.title, ticket_title());
+    assert_eq!(ticket.description, ticket_description());
+    assert_eq!(ticket.status, initial);
+}
+
+pub fn ids_are_unique<R: TicketRepository>(mut repository: R) {
+    let n_tickets = 10;
+    let mut ids = Vec::new();
+    for _ in 0..n_tickets {
+        let id = repository.insert(ticket_title(), ticket_description());
+        assert!(!ids.contains(&id), "{id:?} was handed out twice");
+        ids.push(id);
+    }
+    assert_eq!(repository.count(), n_tickets);
+}
+
+pub fn iter_yields_every_ticket<R: TicketRepository>(mut repository: R) {
+    let first = repository.insert(ticket_title(), ticket_description());
+    let second = repository.insert(other_title(), other_description());
+
+    let tickets: Vec<_> = repository.iter().collect();
+    assert_eq!(tickets.len(), 2);
+    assert!(tickets
+        .iter()
+        .any(|t| t.id == first && t.title == ticket_title()));
+    assert!(tickets
+        .iter()
+        .any(|t| t.id == second && t.title == other_title()));
+}
+
+pub fn update_changes_only_the_given_fields<R: TicketRepository>(
+    mut repository: R,
+    updated: R::Status,
+) {
+    let id = repository.insert(ticket_title(), ticket_description());
+
+    repository
+        .update(
+            id,
+            TicketChanges {
+                status: Some(updated),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let ticket = repository.get(id).unwrap();
+    assert_eq!(ticket.status, updated);
+    assert_eq!(ticket.title, ticket_title());
+    assert_eq!(ticket.description, ticket_description());
+
+    repository
+        .update(
+            id,
+            TicketChanges {
+                title: Some(other_title()),
+                description: Some(other_description()),
+                status: None,
+            },
+        )
+        .unwrap();
+    let ticket = repository.get(id).unwrap();
+    assert_eq!(ticket.status, updated);
+    assert_eq!(ticket.title, other_title());
+    assert_eq!(ticket.description, other_description());
+}
+
+pub fn update_is_isolated_to_one_ticket<R: TicketRepository>(
+    mut repository: R,
+    initial: R::Status,
+    updated: R::Status,
+) {
+    let first = repository.insert(ticket_title(), ticket_description());
+    let second = repository.insert(ticket_title(), ticket_description());
+
+    repository
+        .update(
+            first,
+            TicketChanges {
+                status: Some(updated),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(repository.get(first).unwrap().status, updated);
+    assert_eq!(repository.get(second).unwrap().status, initial);
+    assert_eq!(repository.count(), 2);
+}
+
+pub fn iter_reflects_updates<R: TicketRepository>(mut repository: R, updated: R::Status) {
+    let id = repository.insert(ticket_title(), ticket_description());
+    repository
+        .update(
+            id,
+            TicketChanges {
+                status: Some(updated),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let ticket = repository.iter().find(|t| t.id == id).unwrap();
+    assert_eq!(ticket.status, updated);
+}
+
+/// `other` is a second empty store, used to get hold of an id that `repository` never handed out.
+pub fn update_of_a_missing_ticket_fails<R: TicketRepository>(
+    mut repository: R,
+    mut other: R,
+    updated: R::Status,
+) {
+    let missing = other.insert(ticket_title(), ticket_description());
+
+    let changes = TicketChanges {
+        status: Some(updated),
+        ..Default::default()
+    };
+    assert!(repository.update(missing, changes).is_err());
+    assert!(repository.get(missing).is_none());
+    assert_eq!(repository.count(), 0);
+}
+
+/// Generate the whole conformance suite as `#[test]` functions.
+///
+/// `$repository` is evaluated once per test and must produce an empty store.
+/// `initial` is the status of freshly inserted tickets, `updated` any other status.
+///
+/// ```ignore
+/// ticket_repository::conformance_tests!(
+///     TicketStore::new(),
+///     initial = Status::ToDo,
+///     updated = Status::InProgress,
+/// );
+/// ```
+#[macro_export]
+macro_rules! conformance_tests {
+    ($repository:expr, initial = $initial:expr, updated = $updated:expr $(,)?) => {
+        #[test]
+        fn starts_empty() {
+            $crate::conformance::starts_empty($repository);
+        }
+
+        #[test]
+        fn insert_then_get() {
+            $crate::conformance::insert_then_get($repository, $initial);
+        }
+
+        #[test]
+        fn ids_are_unique() {
+            $crate::conformance::ids_are_unique($repository);
+        }
+
+        #[test]
+        fn iter_yields_every_ticket() {
+            $crate::conformance::iter_yields_every_ticket($repository);
+        }
+
+        #[test]
+        fn update_changes_only_the_given_fields() {
+            $crate::conformance::update_changes_only_the_given_fields($repository, $updated);
+        }
+
+        #[test]
+        fn update_is_isolated_to_one_ticket() {
+            $crate::conformance::update_is_isolated_to_one_ticket($repository, $initial, $updated);
+        }
+
+        #[test]
+        fn iter_reflects_updates() {
+            $crate::conformance::iter_reflects_updates($repository, $updated);
+        }
+
+        #[test]
+        fn update_of_a_missing_ticket_fails() {
+            $crate::conformance::update_of_a_missing_ticket_fails(
+                $repository,
+                $repository,
+                $updated,
+            );
+        }
+    };
+}
@@ -0,0 +1,62 @@
+use std::fmt::Debug;
+use ticket_fields::{TicketDescription, TicketTitle};
+
+pub mod conformance;
+
+/// A copy of a ticket, detached from the store it was read from.
+///
+/// Backends hand out tickets in different shapes (`&Ticket`, `Arc<RwLock<Ticket>>`, ...):
+/// the repository interface always returns an owned snapshot instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TicketRecord<Id, Status> {
+    pub id: Id,
+    pub title: TicketTitle,
+    pub description: TicketDescription,
+    pub status: Status,
+}
+
+/// The set of fields to change on an existing ticket.
+/// Fields left to `None` are not touched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TicketChanges<Status> {
+    pub title: Option<TicketTitle>,
+    pub description: Option<TicketDescription>,
+    pub status: Option<Status>,
+}
+
+impl<Status> Default for TicketChanges<Status> {
+    fn default() -> Self {
+        Self {
+            title: None,
+            description: None,
+            status: None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("The ticket does not exist")]
+pub struct TicketNotFound;
+
+/// The common interface shared by every ticket store backend.
+pub trait TicketRepository {
+    type Id: Copy + PartialEq + Debug;
+    type Status: Copy + PartialEq + Debug;
+
+    /// Store a new ticket and return the id it was assigned.
+    fn insert(&mut self, title: TicketTitle, description: TicketDescription) -> Self::Id;
+
+    fn get(&self, id: Self::Id) -> Option<TicketRecord<Self::Id, Self::Status>>;
+
+    fn update(
+        &mut self,
+        id: Self::Id,
+        changes: TicketChanges<Self::Status>,
+    ) -> Result<(), TicketNotFound>;
+
+    /// Iterate over all the tickets in the store.
+    /// The iteration order is backend-specific.
+    fn iter(&self) -> impl Iterator<Item = TicketRecord<Self::Id, Self::Status>> + '_;
+
+    fn count(&self) -> usize;
+}